@@ -0,0 +1,31 @@
+#!/usr/bin/env bash
+# Runs each coordinator scenario (one deployment_<scenario>.xml per scenario) and checks that all
+# of its VMs passed. Scenarios run on platform_<scenario>.xml if it exists, on cluster.xml
+# otherwise.
+#
+# Usage: run.sh path/to/tansiv [scenario...]
+
+tansiv=$1
+shift
+if [ $# -eq 0 ]; then
+  scenarios=$(ls deployment_*.xml | sed 's/^deployment_\(.*\)\.xml$/\1/')
+else
+  scenarios="$@"
+fi
+
+status=0
+for scenario in $scenarios; do
+  platform=cluster.xml
+  if [ -f "platform_$scenario.xml" ]; then
+    platform="platform_$scenario.xml"
+  fi
+  expected=$(grep -c 'function="vsg_vm"' "deployment_$scenario.xml")
+  passed=$(timeout 60 "$tansiv" "$platform" "deployment_$scenario.xml" 2>&1 | tee "$scenario.log" | grep -c "^scenario $scenario passed$")
+  if [ "$passed" -eq "$expected" ]; then
+    echo "$scenario: ok"
+  else
+    echo "$scenario: FAILED ($passed/$expected VMs passed, see $scenario.log)"
+    status=1
+  fi
+done
+exit $status
@@ -0,0 +1,446 @@
+#include <arpa/inet.h>
+#include <atomic>
+#include <cstring>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string>
+#include <unistd.h>
+#include <vector>
+
+extern "C" {
+#include <tansiv-client.h>
+}
+
+// Coordinator scenarios, see run.sh
+//
+// Usage: scenarios socket_name scenario address [--alias alias]... [argument...]
+//
+// Each VM of a scenario prints "scenario <scenario> passed" on success and exits with an error
+// otherwise.
+
+// Host time to wait for a message before giving up, in milliseconds
+#define RECV_TIMEOUT_MS 10000
+
+void die(const char* msg, int error)
+{
+  fprintf(stderr, "%s", msg);
+  if (error)
+    fprintf(stderr, "\t%s\n", std::strerror(error));
+  exit(1);
+}
+
+void recv_cb(uintptr_t arg) {}
+
+void deadline_cb(uintptr_t arg, struct timespec deadline) {}
+
+vsg_context* start(const char* socket_name, const char* address, const std::vector<const char*>& aliases)
+{
+  std::vector<const char*> vsg_argv = {"-a", socket_name, "-n", address, "-t", "1970-01-01T00:00:00"};
+  for (const char* alias : aliases) {
+    vsg_argv.push_back("--alias");
+    vsg_argv.push_back(alias);
+  }
+  vsg_context* context = vsg_init(vsg_argv.size(), vsg_argv.data(), NULL, recv_cb, 0, deadline_cb, 0);
+  if (!context) {
+    die("Unable to initialize the context", 0);
+  }
+
+  int ret = vsg_start(context, NULL);
+  if (ret) {
+    die("Unable to start the vsg client", ret);
+  }
+  return context;
+}
+
+// Waits for a message, IPv4 addresses are returned as IPv4-mapped addresses
+std::string recv_any(vsg_context* context, struct in6_addr* src, struct in6_addr* dest)
+{
+  uint8_t buffer[MAX_PACKET_SIZE];
+  uint32_t buffer_len = sizeof(buffer);
+  int ret             = EAGAIN;
+  for (int i = 0; i < RECV_TIMEOUT_MS; i++) {
+    buffer_len = sizeof(buffer);
+    ret        = vsg_recv6(context, src, dest, &buffer_len, buffer);
+    if (ret != EAGAIN)
+      break;
+    usleep(1000);
+  }
+  if (ret) {
+    die("vsg_recv6() failed", ret);
+  }
+  return std::string((char*)buffer, buffer_len);
+}
+
+// Waits for a message and checks its source, its destination if expected_dest is not NULL, and
+// its payload
+void recv_expect(vsg_context* context, const struct in6_addr* expected_src, const struct in6_addr* expected_dest,
+                 const std::string& expected_msg)
+{
+  struct in6_addr src;
+  struct in6_addr dest;
+  std::string msg = recv_any(context, &src, &dest);
+  if (memcmp(&src, expected_src, sizeof(src)) != 0) {
+    die("Unexpected source address\n", 0);
+  }
+  if (expected_dest && memcmp(&dest, expected_dest, sizeof(dest)) != 0) {
+    die("Unexpected destination address\n", 0);
+  }
+  if (msg != expected_msg) {
+    die("Unexpected payload\n", 0);
+  }
+}
+
+void send_to(vsg_context* context, const struct in6_addr* dest, const std::string& msg)
+{
+  int ret = vsg_send6(context, dest, msg.length(), (const uint8_t*)msg.c_str());
+  if (ret) {
+    die("vsg_send6() failed", ret);
+  }
+}
+
+// IPv4 addresses are parsed as IPv4-mapped addresses
+struct in6_addr parse_address(const char* address)
+{
+  struct in6_addr in6;
+  struct in_addr in;
+  if (inet_pton(AF_INET6, address, &in6) == 1) {
+    return in6;
+  }
+  if (inet_pton(AF_INET, address, &in) != 1) {
+    die("Invalid address\n", 0);
+  }
+  memset(&in6, 0, sizeof(in6));
+  in6.s6_addr[10] = 0xff;
+  in6.s6_addr[11] = 0xff;
+  memcpy(&in6.s6_addr[12], &in, sizeof(in));
+  return in6;
+}
+
+// Two VMs having IPv6 addresses exchange a message
+void ipv6(vsg_context* context, const char* address, int num_peers, char* peers[])
+{
+  if (num_peers != 1) {
+    die("ipv6 takes one peer\n", 0);
+  }
+  struct in6_addr self = parse_address(address);
+  struct in6_addr peer = parse_address(peers[0]);
+
+  send_to(context, &peer, std::string("hello from ") + address);
+  recv_expect(context, &peer, &self, std::string("hello from ") + peers[0]);
+}
+
+// Each VM broadcasts a message and receives the ones of all the other VMs, but not its own
+void broadcast(vsg_context* context, const char* address, int num_peers, char* peers[])
+{
+  struct in6_addr all = parse_address("255.255.255.255");
+  send_to(context, &all, std::string("hello from ") + address);
+
+  std::vector<bool> received(num_peers, false);
+  for (int n = 0; n < num_peers; n++) {
+    struct in6_addr src;
+    struct in6_addr dest;
+    std::string msg = recv_any(context, &src, &dest);
+    if (memcmp(&dest, &all, sizeof(dest)) != 0) {
+      die("Unexpected destination address\n", 0);
+    }
+    int i;
+    for (i = 0; i < num_peers; i++) {
+      struct in6_addr peer = parse_address(peers[i]);
+      if (memcmp(&src, &peer, sizeof(src)) == 0 && msg == std::string("hello from ") + peers[i]) {
+        break;
+      }
+    }
+    if (i == num_peers || received[i]) {
+      die("Unexpected message\n", 0);
+    }
+    received[i] = true;
+  }
+}
+
+// The sender sends a message to a group that the member joined and the non-member did not
+//
+// Peers, depending on the role:
+// - sender: group member non_member,
+// - member: group sender,
+// - non_member: sender.
+void multicast(vsg_context* context, const char* address, int num_peers, char* peers[])
+{
+  if (num_peers < 2) {
+    die("multicast takes a role and peers\n", 0);
+  }
+  std::string role = peers[0];
+  if (role == "sender" && num_peers == 4) {
+    struct in6_addr group      = parse_address(peers[1]);
+    struct in6_addr member     = parse_address(peers[2]);
+    struct in6_addr non_member = parse_address(peers[3]);
+    // Both are ready once the join of the member reached the coordinator
+    for (int n = 0; n < 2; n++) {
+      struct in6_addr src;
+      struct in6_addr dest;
+      std::string msg = recv_any(context, &src, &dest);
+      if (msg != "ready" || (memcmp(&src, &member, sizeof(src)) != 0 && memcmp(&src, &non_member, sizeof(src)) != 0)) {
+        die("Unexpected message\n", 0);
+      }
+    }
+    send_to(context, &group, "to the group");
+    // The non-member would have received the group message before this one
+    recv_expect(context, &member, NULL, "received");
+    send_to(context, &non_member, "done");
+  } else if (role == "member" && num_peers == 3) {
+    struct in6_addr group  = parse_address(peers[1]);
+    struct in6_addr sender = parse_address(peers[2]);
+    struct in_addr group4;
+    inet_pton(AF_INET, peers[1], &group4);
+    int ret = vsg_join_group(context, group4.s_addr);
+    if (ret) {
+      die("vsg_join_group() failed", ret);
+    }
+    send_to(context, &sender, "ready");
+    recv_expect(context, &sender, &group, "to the group");
+    send_to(context, &sender, "received");
+  } else if (role == "non_member" && num_peers == 2) {
+    struct in6_addr sender = parse_address(peers[1]);
+    send_to(context, &sender, "ready");
+    recv_expect(context, &sender, NULL, "done");
+  } else {
+    die("Invalid multicast role or peers\n", 0);
+  }
+}
+
+// Reports received by report_cb, which runs in the deadline handler and must not take locks
+#define MAX_REPORTS 8
+static struct vsg_delivery_report reports[MAX_REPORTS];
+static std::atomic<int> num_reports(0);
+
+void report_cb(uintptr_t arg, const struct vsg_delivery_report* report)
+{
+  int n = num_reports.load(std::memory_order_relaxed);
+  if (n < MAX_REPORTS) {
+    reports[n] = *report;
+    num_reports.store(n + 1, std::memory_order_release);
+  }
+}
+
+// Finds the report of the message having seq_id among the received reports
+const struct vsg_delivery_report* find_report(uint64_t seq_id)
+{
+  for (int i = 0; i < RECV_TIMEOUT_MS; i++) {
+    int n = num_reports.load(std::memory_order_acquire);
+    for (int r = 0; r < n; r++) {
+      if (reports[r].seq_id == seq_id) {
+        return &reports[r];
+      }
+    }
+    usleep(1000);
+  }
+  die("Missing delivery report\n", 0);
+  return NULL;
+}
+
+// The sender gets a report for a message delivered to the receiver and for a message to an
+// unknown address
+//
+// Arguments: sender receiver unknown_address, or receiver sender
+void report(vsg_context* context, const char* address, int num_peers, char* peers[])
+{
+  std::string role = num_peers > 0 ? peers[0] : "";
+  if (role == "sender" && num_peers == 3) {
+    struct in6_addr self     = parse_address(address);
+    struct in6_addr receiver = parse_address(peers[1]);
+    struct in6_addr unknown  = parse_address(peers[2]);
+    int ret                  = vsg_set_report_callback(context, report_cb, 0);
+    if (ret) {
+      die("vsg_set_report_callback() failed", ret);
+    }
+    std::string msg = "report me";
+    uint64_t delivered_id;
+    uint64_t dropped_id;
+    ret = vsg_send_ex(context, &self, &receiver, NULL, msg.length(), (const uint8_t*)msg.c_str(), &delivered_id);
+    if (ret) {
+      die("vsg_send_ex() failed", ret);
+    }
+    ret = vsg_send_ex(context, &self, &unknown, NULL, msg.length(), (const uint8_t*)msg.c_str(), &dropped_id);
+    if (ret) {
+      die("vsg_send_ex() failed", ret);
+    }
+    if (find_report(delivered_id)->drop_reason != VSG_DELIVERED) {
+      die("Message not reported as delivered\n", 0);
+    }
+    if (find_report(dropped_id)->drop_reason != VSG_DROP_UNREACHABLE) {
+      die("Message not reported as unreachable\n", 0);
+    }
+  } else if (role == "receiver" && num_peers == 2) {
+    struct in6_addr sender = parse_address(peers[1]);
+    recv_expect(context, &sender, NULL, "report me");
+  } else {
+    die("Invalid report role or peers\n", 0);
+  }
+}
+
+// Link state changes seen by link_state_cb, which runs in the deadline handler
+static std::atomic<bool> link_down(false);
+static std::atomic<bool> link_up_again(false);
+static struct in6_addr link_address;
+
+void link_state_cb(uintptr_t arg, const struct in6_addr* address, int up)
+{
+  if (memcmp(address, &link_address, sizeof(link_address)) != 0) {
+    return;
+  }
+  if (!up) {
+    link_down.store(true, std::memory_order_release);
+  } else if (link_down.load(std::memory_order_relaxed)) {
+    link_up_again.store(true, std::memory_order_release);
+  }
+}
+
+// The link of the first VM goes down and up again according to the state profile of the platform.
+// The other VM only keeps the simulation running until the first one is done.
+//
+// Arguments: watcher peer, or idle peer
+void link_state(vsg_context* context, const char* address, int num_peers, char* peers[])
+{
+  std::string role = num_peers > 0 ? peers[0] : "";
+  if (role == "watcher" && num_peers == 2) {
+    struct in6_addr peer = parse_address(peers[1]);
+    link_address         = parse_address(address);
+    int ret              = vsg_set_link_state_callback(context, link_state_cb, 0);
+    if (ret) {
+      die("vsg_set_link_state_callback() failed", ret);
+    }
+    int i;
+    for (i = 0; i < RECV_TIMEOUT_MS && !link_up_again.load(std::memory_order_acquire); i++) {
+      usleep(1000);
+    }
+    if (i == RECV_TIMEOUT_MS) {
+      die("Missing link state change\n", 0);
+    }
+    send_to(context, &peer, "done");
+  } else if (role == "idle" && num_peers == 2) {
+    struct in6_addr peer = parse_address(peers[1]);
+    recv_expect(context, &peer, NULL, "done");
+  } else {
+    die("Invalid link_state role or peers\n", 0);
+  }
+}
+
+// The sender sends a message with flow metadata, which the receiver gets with the message and
+// acknowledges
+//
+// Arguments: sender receiver, or receiver sender
+void flow(vsg_context* context, const char* address, int num_peers, char* peers[])
+{
+  std::string role = num_peers > 0 ? peers[0] : "";
+  struct vsg_packet_attr expected = {};
+  expected.protocol               = 17;
+  expected.priority               = 46;
+  expected.src_port               = 4000;
+  expected.dst_port               = 5000;
+  expected.flow_id                = 42;
+  std::string expected_msg        = "flow";
+  if (role == "sender" && num_peers == 2) {
+    struct in6_addr self     = parse_address(address);
+    struct in6_addr receiver = parse_address(peers[1]);
+    int ret = vsg_send_ex(context, &self, &receiver, &expected, expected_msg.length(),
+                          (const uint8_t*)expected_msg.c_str(), NULL);
+    if (ret) {
+      die("vsg_send_ex() failed", ret);
+    }
+    // Queued messages are lost if the process exits
+    recv_expect(context, &receiver, &self, "received");
+  } else if (role == "receiver" && num_peers == 2) {
+    struct in6_addr sender = parse_address(peers[1]);
+    struct in6_addr src;
+    struct vsg_packet_attr attr;
+    uint8_t buffer[MAX_PACKET_SIZE];
+    uint32_t buffer_len = sizeof(buffer);
+    int ret             = EAGAIN;
+    for (int i = 0; i < RECV_TIMEOUT_MS && ret == EAGAIN; i++) {
+      buffer_len = sizeof(buffer);
+      ret        = vsg_recv_ex(context, &src, NULL, &attr, &buffer_len, buffer);
+      if (ret == EAGAIN)
+        usleep(1000);
+    }
+    if (ret) {
+      die("vsg_recv_ex() failed", ret);
+    }
+    if (memcmp(&src, &sender, sizeof(src)) != 0 || std::string((char*)buffer, buffer_len) != expected_msg) {
+      die("Unexpected message\n", 0);
+    }
+    if (attr.protocol != expected.protocol || attr.priority != expected.priority ||
+        attr.src_port != expected.src_port || attr.dst_port != expected.dst_port || attr.flow_id != expected.flow_id) {
+      die("Unexpected flow metadata\n", 0);
+    }
+    send_to(context, &sender, "received");
+  } else {
+    die("Invalid flow role or peers\n", 0);
+  }
+}
+
+// The owner of an alias receives a message sent to the alias and answers from the alias
+//
+// Arguments: owner alias peer (the VM started with --alias alias), or peer alias
+void alias(vsg_context* context, const char* address, int num_peers, char* peers[])
+{
+  std::string role = num_peers > 0 ? peers[0] : "";
+  if (role == "owner" && num_peers == 3) {
+    struct in6_addr alias = parse_address(peers[1]);
+    struct in6_addr peer  = parse_address(peers[2]);
+    recv_expect(context, &peer, &alias, "to the alias");
+    std::string msg = "from the alias";
+    int ret         = vsg_send_from6(context, &alias, &peer, msg.length(), (const uint8_t*)msg.c_str());
+    if (ret) {
+      die("vsg_send_from6() failed", ret);
+    }
+  } else if (role == "peer" && num_peers == 2) {
+    struct in6_addr self  = parse_address(address);
+    struct in6_addr alias = parse_address(peers[1]);
+    send_to(context, &alias, "to the alias");
+    recv_expect(context, &alias, &self, "from the alias");
+  } else {
+    die("Invalid alias role or peers\n", 0);
+  }
+}
+
+struct scenario {
+  const char* name;
+  void (*run)(vsg_context* context, const char* address, int num_peers, char* peers[]);
+};
+
+static const scenario scenarios[] = {
+    {"ipv6", ipv6},
+    {"broadcast", broadcast},
+    {"multicast", multicast},
+    {"report", report},
+    {"link_state", link_state},
+    {"alias", alias},
+    {"flow", flow},
+};
+
+int main(int argc, char* argv[])
+{
+  if (argc < 4) {
+    die("Usage: scenarios socket_name scenario address [--alias alias]... [argument...]\n", 0);
+  }
+  const char* socket_name = argv[1];
+  const char* name        = argv[2];
+  const char* address     = argv[3];
+  int next_arg            = 4;
+  std::vector<const char*> aliases;
+  while (next_arg + 1 < argc && strcmp(argv[next_arg], "--alias") == 0) {
+    aliases.push_back(argv[next_arg + 1]);
+    next_arg += 2;
+  }
+
+  for (const scenario& s : scenarios) {
+    if (strcmp(s.name, name) == 0) {
+      vsg_context* context = start(socket_name, address, aliases);
+      s.run(context, address, argc - next_arg, &argv[next_arg]);
+      printf("scenario %s passed\n", name);
+      fflush(stdout);
+      // Like in the send example, the end of the simulation is not awaited
+      return 0;
+    }
+  }
+  die("Unknown scenario\n", 0);
+}
@@ -2,7 +2,7 @@
 #[macro_use(local_vsg_address_str, local_vsg_address, remote_vsg_address)]
 extern crate tansiv_client;
 
-use tansiv_client::{Context, Error, Result};
+use tansiv_client::{Context, DeliveryReport, DiskOp, DropReason, Error, PacketAttributes, Result, Stats, TerminationReason, VsgAddress};
 use libc::{self, uintptr_t};
 #[allow(unused_imports)]
 use log::{debug, error};
@@ -40,8 +40,151 @@ unsafe fn parse_os_args<F, T>(argc: c_int, argv: *const *const c_char, parse: F)
     }
 }
 
+/// Extended packet metadata, see `struct vsg_packet_attr` in tansiv-client.h. An all-zero
+/// structure means no attributes.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct VsgPacketAttr {
+    pub protocol: u8,
+    pub priority: u8,
+    pub src_port: u16,
+    pub dst_port: u16,
+    pub flow_id: u64,
+    pub wire_size: u32,
+}
+
+impl From<&VsgPacketAttr> for PacketAttributes {
+    fn from(attr: &VsgPacketAttr) -> PacketAttributes {
+        PacketAttributes {
+            protocol: attr.protocol,
+            src_port: attr.src_port,
+            dst_port: attr.dst_port,
+            priority: attr.priority,
+            flow_id: attr.flow_id,
+            wire_size: attr.wire_size,
+        }
+    }
+}
+
+impl From<PacketAttributes> for VsgPacketAttr {
+    fn from(attributes: PacketAttributes) -> VsgPacketAttr {
+        VsgPacketAttr {
+            protocol: attributes.protocol,
+            priority: attributes.priority,
+            src_port: attributes.src_port,
+            dst_port: attributes.dst_port,
+            flow_id: attributes.flow_id,
+            wire_size: attributes.wire_size,
+        }
+    }
+}
+
+/// Outcome of a sent packet, see `struct vsg_delivery_report` in tansiv-client.h
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct VsgDeliveryReport {
+    pub seq_id: u64,
+    pub time: libc::timespec,
+    pub drop_reason: c_int,
+}
+
+pub const VSG_DELIVERED: c_int = 0;
+pub const VSG_DROP_UNKNOWN: c_int = 1;
+pub const VSG_DROP_UNREACHABLE: c_int = 2;
+pub const VSG_DROP_LOSS: c_int = 3;
+pub const VSG_DROP_QUEUE_FULL: c_int = 4;
+
+pub const VSG_END_ACTOR_REQUEST: c_int = 1;
+pub const VSG_END_PROTOCOL_ERROR: c_int = 2;
+pub const VSG_END_IO_ERROR: c_int = 3;
+pub const VSG_END_DEADLINE_OVERRUN: c_int = 4;
+
+fn termination_reason_to_c(reason: TerminationReason) -> c_int {
+    match reason {
+        TerminationReason::ActorRequest => VSG_END_ACTOR_REQUEST,
+        TerminationReason::ProtocolError => VSG_END_PROTOCOL_ERROR,
+        TerminationReason::IoError => VSG_END_IO_ERROR,
+        TerminationReason::DeadlineOverrun => VSG_END_DEADLINE_OVERRUN,
+    }
+}
+
+pub const VSG_DISK_READ: c_int = 0;
+pub const VSG_DISK_WRITE: c_int = 1;
+
+impl From<DeliveryReport> for VsgDeliveryReport {
+    fn from(report: DeliveryReport) -> VsgDeliveryReport {
+        VsgDeliveryReport {
+            seq_id: report.seq_id,
+            time: duration_to_timespec(report.time),
+            drop_reason: match report.drop_reason {
+                None => VSG_DELIVERED,
+                Some(DropReason::Unknown) => VSG_DROP_UNKNOWN,
+                Some(DropReason::Unreachable) => VSG_DROP_UNREACHABLE,
+                Some(DropReason::Loss) => VSG_DROP_LOSS,
+                Some(DropReason::QueueFull) => VSG_DROP_QUEUE_FULL,
+            },
+        }
+    }
+}
+
+/// Counters of a context, see `struct vsg_stats` in tansiv-client.h
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct VsgStats {
+    pub packets_sent: u64,
+    pub bytes_sent: u64,
+    pub packets_received: u64,
+    pub bytes_received: u64,
+    pub drops_no_buffer: u64,
+    pub drops_unjoined_group: u64,
+    pub drops_reassembly: u64,
+    pub drops_input_queue_full: u64,
+    pub drops_unknown: u64,
+    pub drops_unreachable: u64,
+    pub drops_loss: u64,
+    pub drops_queue_full: u64,
+    pub output_buffers_high_water: u64,
+    pub input_queue_high_water: u64,
+    pub deadlines: u64,
+    pub deadline_handler_ns: u64,
+    pub deadline_handler_max_ns: u64,
+    pub late_timestamps: u64,
+    pub faults: u64,
+}
+
+impl From<Stats> for VsgStats {
+    fn from(stats: Stats) -> VsgStats {
+        VsgStats {
+            packets_sent: stats.packets_sent,
+            bytes_sent: stats.bytes_sent,
+            packets_received: stats.packets_received,
+            bytes_received: stats.bytes_received,
+            drops_no_buffer: stats.drops_no_buffer,
+            drops_unjoined_group: stats.drops_unjoined_group,
+            drops_reassembly: stats.drops_reassembly,
+            drops_input_queue_full: stats.drops_input_queue_full,
+            drops_unknown: stats.drops_unknown,
+            drops_unreachable: stats.drops_unreachable,
+            drops_loss: stats.drops_loss,
+            drops_queue_full: stats.drops_queue_full,
+            output_buffers_high_water: stats.output_buffers_high_water,
+            input_queue_high_water: stats.input_queue_high_water,
+            deadlines: stats.deadlines,
+            deadline_handler_ns: stats.deadline_handler_time.as_nanos() as u64,
+            deadline_handler_max_ns: stats.deadline_handler_max_time.as_nanos() as u64,
+            late_timestamps: stats.late_timestamps,
+            faults: stats.faults,
+        }
+    }
+}
+
 type CRecvCallback = unsafe extern "C" fn(uintptr_t);
 type CDeadlineCallback = unsafe extern "C" fn(uintptr_t, libc::timespec);
+type CReportCallback = unsafe extern "C" fn(uintptr_t, *const VsgDeliveryReport);
+type CLinkStateCallback = unsafe extern "C" fn(uintptr_t, *const libc::in6_addr, c_int);
+type CDiskCallback = unsafe extern "C" fn(uintptr_t, u64, libc::timespec);
+type CEndCallback = unsafe extern "C" fn(uintptr_t, c_int);
+type CTapCallback = unsafe extern "C" fn(uintptr_t, libc::timespec, *const libc::in6_addr, *const libc::in6_addr, u32, *const u8);
 
 fn duration_to_timespec(duration: std::time::Duration) -> libc::timespec {
     libc::timespec {
@@ -69,6 +212,153 @@ pub unsafe extern fn vsg_init(argc: c_int, argv: *const *const c_char, next_arg_
     }
 }
 
+/// Sets the callback called with the outcome of each packet sent while a callback is set.
+/// Passing a `NULL` callback disables delivery reports. Reports are correlated with sent packets
+/// using the sequence id returned by [`vsg_send_ex`]. Broadcast and multicast packets get one
+/// report per receiver.
+///
+/// # Safety
+///
+/// * `context` should point to a valid context, as previously returned by [`vsg_init`].
+///
+/// * `report_callback` is called by the deadline handler, with the same restrictions as the
+///   receive callback. The report it is passed is only valid during the call.
+///
+/// # Error codes
+///
+/// * Fails with `libc::EINVAL` whenever `context` is NULL.
+#[no_mangle]
+pub unsafe extern "C" fn vsg_set_report_callback(context: *const Context, report_callback: Option<CReportCallback>, report_callback_arg: uintptr_t) -> c_int {
+    if let Some(context) = context.as_ref() {
+        let report_callback = report_callback.map(|report_callback| -> tansiv_client::ReportCallback {
+            Box::new(move |report| {
+                let report: VsgDeliveryReport = report.into();
+                report_callback(report_callback_arg, &report)
+            })
+        });
+        (*context).set_report_callback(report_callback);
+        0
+    } else {
+        libc::EINVAL
+    }
+}
+
+/// Sets the callback called when the simulated link of one of the addresses of the context goes
+/// down (`up == 0`) or up (`up == 1`). IPv4 addresses are passed as IPv4-mapped IPv6 addresses.
+/// Passing a `NULL` callback disables notifications.
+///
+/// # Safety
+///
+/// * `context` should point to a valid context, as previously returned by [`vsg_init`].
+///
+/// * `link_state_callback` is called by the deadline handler, with the same restrictions as the
+///   receive callback. The address it is passed is only valid during the call.
+///
+/// # Error codes
+///
+/// * Fails with `libc::EINVAL` whenever `context` is NULL.
+#[no_mangle]
+pub unsafe extern "C" fn vsg_set_link_state_callback(context: *const Context, link_state_callback: Option<CLinkStateCallback>, link_state_callback_arg: uintptr_t) -> c_int {
+    if let Some(context) = context.as_ref() {
+        let link_state_callback = link_state_callback.map(|link_state_callback| -> tansiv_client::LinkStateCallback {
+            Box::new(move |address, up| {
+                let address = libc::in6_addr { s6_addr: address.ipv6() };
+                link_state_callback(link_state_callback_arg, &address, up as c_int)
+            })
+        });
+        (*context).set_link_state_callback(link_state_callback);
+        0
+    } else {
+        libc::EINVAL
+    }
+}
+
+/// Sets the callback called when the simulation ends, with the reason as one of the
+/// `VSG_END_*` constants. After this callback, time is not controlled by the simulator anymore and
+/// the application or VM should shut down. Passing a `NULL` callback disables the notification.
+/// Without a callback, the qemu and qemukvm backends abort the VM when the simulation ends, since
+/// nothing else would stop it.
+///
+/// # Safety
+///
+/// * `context` should point to a valid context, as previously returned by [`vsg_init`].
+///
+/// * `end_callback` is called by the deadline handler, with the same restrictions as the receive
+///   callback.
+///
+/// # Error codes
+///
+/// * Fails with `libc::EINVAL` whenever `context` is NULL.
+#[no_mangle]
+pub unsafe extern "C" fn vsg_set_end_callback(context: *const Context, end_callback: Option<CEndCallback>, end_callback_arg: uintptr_t) -> c_int {
+    if let Some(context) = context.as_ref() {
+        let end_callback = end_callback.map(|end_callback| -> tansiv_client::EndCallback {
+            Box::new(move |reason| end_callback(end_callback_arg, termination_reason_to_c(reason)))
+        });
+        (*context).set_end_callback(end_callback);
+        0
+    } else {
+        libc::EINVAL
+    }
+}
+
+/// Sets the callback called with the request id and the simulated completion time of each disk
+/// request submitted with [`vsg_disk_submit`]. Passing a `NULL` callback drops completions.
+///
+/// # Safety
+///
+/// * `context` should point to a valid context, as previously returned by [`vsg_init`].
+///
+/// * `disk_callback` is called by the deadline handler, with the same restrictions as the receive
+///   callback.
+///
+/// # Error codes
+///
+/// * Fails with `libc::EINVAL` whenever `context` is NULL.
+#[no_mangle]
+pub unsafe extern "C" fn vsg_set_disk_callback(context: *const Context, disk_callback: Option<CDiskCallback>, disk_callback_arg: uintptr_t) -> c_int {
+    if let Some(context) = context.as_ref() {
+        let disk_callback = disk_callback.map(|disk_callback| -> tansiv_client::DiskCallback {
+            Box::new(move |completion| disk_callback(disk_callback_arg, completion.request_id, duration_to_timespec(completion.time)))
+        });
+        (*context).set_disk_callback(disk_callback);
+        0
+    } else {
+        libc::EINVAL
+    }
+}
+
+/// Sets the callback called with the send time, source and destination addresses and payload of
+/// each outgoing packet, just before it is sent to the simulator. IPv4 addresses are passed as
+/// IPv4-mapped IPv6 addresses. Passing a `NULL` callback disables the tap.
+///
+/// # Safety
+///
+/// * `context` should point to a valid context, as previously returned by [`vsg_init`].
+///
+/// * `tap_callback` is called by the deadline handler, with the same restrictions as the receive
+///   callback. The addresses and payload it is passed are only valid during the call.
+///
+/// # Error codes
+///
+/// * Fails with `libc::EINVAL` whenever `context` is NULL.
+#[no_mangle]
+pub unsafe extern "C" fn vsg_set_tap_callback(context: *const Context, tap_callback: Option<CTapCallback>, tap_callback_arg: uintptr_t) -> c_int {
+    if let Some(context) = context.as_ref() {
+        let tap_callback = tap_callback.map(|tap_callback| -> tansiv_client::TapCallback {
+            Box::new(move |send_time, src, dst, payload| {
+                let src = libc::in6_addr { s6_addr: src.ipv6() };
+                let dst = libc::in6_addr { s6_addr: dst.ipv6() };
+                tap_callback(tap_callback_arg, duration_to_timespec(send_time), &src, &dst, payload.len() as u32, payload.as_ptr())
+            })
+        });
+        (*context).set_tap_callback(tap_callback);
+        0
+    } else {
+        libc::EINVAL
+    }
+}
+
 #[no_mangle]
 pub unsafe extern fn vsg_cleanup(context: *const Context) {
     if !context.is_null() {
@@ -181,7 +471,7 @@ pub unsafe extern fn vsg_gettimeofday(context: *const Context, timeval: *mut lib
 /// * Fails with `libc::EINVAL` whenever context is `NULL` or `msg` is `NULL` with `msglen > 0`.
 ///
 /// * Fails with `libc::EMSGSIZE` whenever the payload is bigger than the maximum message size that
-///   vsg can handle.
+///   vsg can handle. This is `MAX_PACKET_SIZE`, unless fragmentation is enabled with `--fragment`.
 ///
 /// * Fails with `libc::ENOMEM` whenever there is no more buffers to hold the message to send.
 #[no_mangle]
@@ -213,96 +503,952 @@ pub unsafe extern fn vsg_send(context: *const Context, dst: libc::in_addr_t, msg
     }
 }
 
-/// Picks the next message in the receive queue, stores its payload in `msg[0..*msglen]` and
-/// optionnally returns sender and destination addresses in `*psrc` and `*pdst` respectively.
-/// `*msglen` initially contains the size of the buffer pointed to by `msg`. When `vsg_recv`
-/// returns with success, `*msglen` contains the actual length of the received payload.
+/// Same as [`vsg_send`] with an IPv6 destination address `*dst`. IPv4-mapped addresses
+/// (`::ffff:a.b.c.d`) are sent as IPv4 addresses.
 ///
 /// # Safety
 ///
 /// * `context` should point to a valid context, as previously returned by [`vsg_init`].
 ///
-/// * `psrc` and `pdst` can be `NULL`, in which case the correponding addresses will not be returned.
+/// * `dst` should point to a valid IPv6 address.
+///
+/// * If `msglen` is `0`, it is allowed that `msg` is `NULL`.
+///
+/// # Error codes
+///
+/// * Same as [`vsg_send`].
+///
+/// * Fails with `libc::EINVAL` whenever `dst` is `NULL`.
+#[no_mangle]
+pub unsafe extern "C" fn vsg_send6(context: *const Context, dst: *const libc::in6_addr, msglen: u32, msg: *const u8) -> c_int {
+    if let (Some(context), Some(dst)) = (context.as_ref(), dst.as_ref()) {
+        let ptr = if msglen == 0 {
+            std::ptr::NonNull::dangling().as_ptr()
+        } else {
+            if msg.is_null() {
+                return libc::EINVAL;
+            };
+            msg
+        };
+        let payload = std::slice::from_raw_parts(ptr, msglen as usize);
+
+        match (*context).send6(dst.s6_addr, payload) {
+            Ok(_) => 0,
+            Err(e) => match e {
+                Error::NoMemoryAvailable => libc::ENOMEM,
+                Error::SizeTooBig => libc::EMSGSIZE,
+                _ => // Unknown error, fallback to EIO
+                    libc::EIO,
+            },
+        }
+    } else {
+        libc::EINVAL
+    }
+}
+
+/// Same as [`vsg_send`] with source address `src` instead of the default address of the
+/// context. `src` must be one of the addresses given to [`vsg_init`] (`-n` or `--alias`).
+///
+/// # Safety
+///
+/// * Same as [`vsg_send`].
+///
+/// # Error codes
+///
+/// * Same as [`vsg_send`].
+///
+/// * Fails with `libc::EADDRNOTAVAIL` if `src` is not an address of the context.
+#[no_mangle]
+pub unsafe extern "C" fn vsg_send_from(context: *const Context, src: libc::in_addr_t, dst: libc::in_addr_t, msglen: u32, msg: *const u8) -> c_int {
+    if let Some(context) = context.as_ref() {
+        send_ex(context, src.into(), dst.into(), &PacketAttributes::default(), msglen, msg, std::ptr::null_mut())
+    } else {
+        libc::EINVAL
+    }
+}
+
+/// Same as [`vsg_send_from`] with IPv6 addresses `*src` and `*dst`. IPv4-mapped addresses
+/// (`::ffff:a.b.c.d`) are used as IPv4 addresses.
+///
+/// # Safety
+///
+/// * `context` should point to a valid context, as previously returned by [`vsg_init`].
+///
+/// * `src` and `dst` should point to valid IPv6 addresses.
+///
+/// * If `msglen` is `0`, it is allowed that `msg` is `NULL`.
+///
+/// # Error codes
+///
+/// * Same as [`vsg_send_from`].
+///
+/// * Fails with `libc::EINVAL` whenever `src` or `dst` is `NULL`.
+#[no_mangle]
+pub unsafe extern "C" fn vsg_send_from6(context: *const Context, src: *const libc::in6_addr, dst: *const libc::in6_addr, msglen: u32, msg: *const u8) -> c_int {
+    if let (Some(context), Some(src), Some(dst)) = (context.as_ref(), src.as_ref(), dst.as_ref()) {
+        send_ex(context, VsgAddress::from_ipv6(src.s6_addr), VsgAddress::from_ipv6(dst.s6_addr), &PacketAttributes::default(), msglen, msg, std::ptr::null_mut())
+    } else {
+        libc::EINVAL
+    }
+}
+
+/// Same as [`vsg_send_from6`] with extended metadata `*attr` for the simulator, for instance to
+/// implement QoS or per-flow statistics.
+///
+/// A non-zero `attr->wire_size` is the size modeled by the simulator instead of `msglen`, which
+/// lets applications account for framing overhead, or carry only part of the bytes of a packet.
+///
+/// # Safety
+///
+/// * `context` should point to a valid context, as previously returned by [`vsg_init`].
+///
+/// * `src` can be `NULL`, in which case the default address of the context is used.
+///
+/// * `dst` should point to a valid IPv6 address.
+///
+/// * `attr` can be `NULL`, in which case the message has no attributes.
+///
+/// * If `msglen` is `0`, it is allowed that `msg` is `NULL`.
+///
+/// * `seq_id` can be `NULL`. Otherwise the sequence id of the message, as found in delivery
+///   reports (see [`vsg_set_report_callback`]), is stored in `*seq_id`.
+///
+/// # Error codes
+///
+/// * Same as [`vsg_send_from6`].
+#[no_mangle]
+pub unsafe extern "C" fn vsg_send_ex(context: *const Context, src: *const libc::in6_addr, dst: *const libc::in6_addr, attr: *const VsgPacketAttr, msglen: u32, msg: *const u8, seq_id: *mut u64) -> c_int {
+    if let (Some(context), Some(dst)) = (context.as_ref(), dst.as_ref()) {
+        let src = match src.as_ref() {
+            Some(src) => VsgAddress::from_ipv6(src.s6_addr),
+            None => context.addresses()[0],
+        };
+        let attributes = match attr.as_ref() {
+            Some(attr) => attr.into(),
+            None => PacketAttributes::default(),
+        };
+        send_ex(context, src, VsgAddress::from_ipv6(dst.s6_addr), &attributes, msglen, msg, seq_id)
+    } else {
+        libc::EINVAL
+    }
+}
+
+/// Sends a message without payload from the default address of the context to `*dst`, that the
+/// simulator models as `size` bytes on the wire. This is meant for traffic generators, which do
+/// not need to carry any payload. The receiver reads it with [`vsg_recv_synthetic`].
+///
+/// # Safety
+///
+/// * `context` should point to a valid context, as previously returned by [`vsg_init`].
+///
+/// * `dst` should point to a valid IPv6 address. IPv4 addresses are given as IPv4-mapped
+///   addresses.
+///
+/// # Error codes
+///
+/// * Same as [`vsg_send_from6`].
+///
+/// * Fails with `libc::EINVAL` whenever `size` is `0`.
+#[no_mangle]
+pub unsafe extern "C" fn vsg_send_synthetic(context: *const Context, dst: *const libc::in6_addr, size: u32) -> c_int {
+    if let (Some(context), Some(dst)) = (context.as_ref(), dst.as_ref()) {
+        match context.send_synthetic(VsgAddress::from_ipv6(dst.s6_addr), size) {
+            Ok(()) => 0,
+            Err(e) => match e {
+                Error::InvalidSize => libc::EINVAL,
+                Error::NoMemoryAvailable => libc::ENOMEM,
+                Error::SizeTooBig => libc::EMSGSIZE,
+                _ => // Unknown error, fallback to EIO
+                    libc::EIO,
+            },
+        }
+    } else {
+        libc::EINVAL
+    }
+}
+
+unsafe fn send_ex(context: &Context, src: VsgAddress, dst: VsgAddress, attributes: &PacketAttributes, msglen: u32, msg: *const u8, pseq_id: *mut u64) -> c_int {
+    let ptr = if msglen == 0 {
+        std::ptr::NonNull::dangling().as_ptr()
+    } else {
+        if msg.is_null() {
+            return libc::EINVAL;
+        };
+        msg
+    };
+    let payload = std::slice::from_raw_parts(ptr, msglen as usize);
+
+    match context.send_ex(src, dst, attributes, payload) {
+        Ok(seq_id) => {
+            if let Some(pseq_id) = pseq_id.as_mut() {
+                *pseq_id = seq_id;
+            }
+            0
+        },
+        Err(e) => match e {
+            Error::InvalidAddress => libc::EADDRNOTAVAIL,
+            Error::NoMemoryAvailable => libc::ENOMEM,
+            Error::SizeTooBig => libc::EMSGSIZE,
+            _ => // Unknown error, fallback to EIO
+                libc::EIO,
+        },
+    }
+}
+
+/// Delivers a message having source address `*src` and a payload stored in `msg[0..msglen]` to
+/// the default address of the context at the next deadline, as if the simulator had delivered it.
+/// The receive callback is called and the message is read with [`vsg_recv`] and friends. This is
+/// meant to drive an application from test harnesses.
+///
+/// # Safety
+///
+/// * `context` should point to a valid context, as previously returned by [`vsg_init`].
+///
+/// * `src` should point to a valid IPv6 address. IPv4 addresses are given as IPv4-mapped
+///   addresses.
+///
+/// * If `msglen` is `0`, it is allowed that `msg` is `NULL`.
+///
+/// # Error codes
+///
+/// * Fails with `libc::EINVAL` whenever `context` or `src` is `NULL`, or `msg` is `NULL` with
+///   `msglen > 0`.
+///
+/// * Fails with `libc::ENOMEM` whenever too many injected messages are pending.
+#[no_mangle]
+pub unsafe extern "C" fn vsg_inject(context: *const Context, src: *const libc::in6_addr, msglen: u32, msg: *const u8) -> c_int {
+    if let (Some(context), Some(src)) = (context.as_ref(), src.as_ref()) {
+        let ptr = if msglen == 0 {
+            std::ptr::NonNull::dangling().as_ptr()
+        } else {
+            if msg.is_null() {
+                return libc::EINVAL;
+            };
+            msg
+        };
+        let payload = std::slice::from_raw_parts(ptr, msglen as usize);
+
+        match context.inject_delivery(VsgAddress::from_ipv6(src.s6_addr), payload) {
+            Ok(_) => 0,
+            Err(e) => match e {
+                Error::NoMemoryAvailable => libc::ENOMEM,
+                _ => // Unknown error, fallback to EIO
+                    libc::EIO,
+            },
+        }
+    } else {
+        libc::EINVAL
+    }
+}
+
+/// Submits a block I/O request of `size` bytes to the disk named `disk` in the simulated platform.
+/// `op` is either `VSG_DISK_READ` or `VSG_DISK_WRITE`. The request is sent to the simulator at the
+/// next deadline and its completion is reported to the callback set with
+/// [`vsg_set_disk_callback`].
+///
+/// # Safety
+///
+/// * `context` should point to a valid context, as previously returned by [`vsg_init`].
+///
+/// * `disk` must point to a valid NUL-terminated UTF-8 string.
+///
+/// * `request_id` can be `NULL`. Otherwise the request id, as passed to the completion callback,
+///   is stored in `*request_id`.
+///
+/// # Error codes
+///
+/// * Fails with `libc::EINVAL` whenever `context` or `disk` is NULL, `disk` is not valid UTF-8 or
+///   `op` is invalid.
+///
+/// * Fails with `libc::ENAMETOOLONG` whenever `disk` is too long.
+///
+/// * Fails with `libc::ENOMEM` whenever too many requests are pending.
+#[no_mangle]
+pub unsafe extern "C" fn vsg_disk_submit(context: *const Context, disk: *const c_char, op: c_int, size: u64, request_id: *mut u64) -> c_int {
+    if let Some(context) = context.as_ref() {
+        if disk.is_null() {
+            return libc::EINVAL;
+        }
+        let disk = match std::ffi::CStr::from_ptr(disk).to_str() {
+            Ok(disk) => disk,
+            Err(_) => return libc::EINVAL,
+        };
+        let op = match op {
+            VSG_DISK_READ => DiskOp::Read,
+            VSG_DISK_WRITE => DiskOp::Write,
+            _ => return libc::EINVAL,
+        };
+
+        match context.disk_submit(disk, op, size) {
+            Ok(id) => {
+                if let Some(request_id) = request_id.as_mut() {
+                    *request_id = id;
+                }
+                0
+            },
+            Err(e) => match e {
+                Error::NoMemoryAvailable => libc::ENOMEM,
+                Error::SizeTooBig => libc::ENAMETOOLONG,
+                _ => // Unknown error, fallback to EIO
+                    libc::EIO,
+            },
+        }
+    } else {
+        libc::EINVAL
+    }
+}
+
+/// Records an application event, such as a milestone, stamped with the current simulation time.
+/// Events are forwarded to the simulator at the next deadline to be shown in its trace.
+///
+/// # Safety
+///
+/// * `context` should point to a valid context, as previously returned by [`vsg_init`].
+///
+/// * `label` must point to a valid NUL-terminated UTF-8 string.
+///
+/// # Error codes
+///
+/// * Fails with `libc::EINVAL` whenever `context` or `label` is NULL or `label` is not valid UTF-8.
+///
+/// * Fails with `libc::ENAMETOOLONG` whenever `label` is too long.
+///
+/// * Fails with `libc::ENOMEM` whenever too many events are pending.
+#[no_mangle]
+pub unsafe extern "C" fn vsg_mark(context: *const Context, label: *const c_char, value: i64) -> c_int {
+    if let Some(context) = context.as_ref() {
+        if label.is_null() {
+            return libc::EINVAL;
+        }
+        let label = match std::ffi::CStr::from_ptr(label).to_str() {
+            Ok(label) => label,
+            Err(_) => return libc::EINVAL,
+        };
+
+        match context.mark(label, value) {
+            Ok(_) => 0,
+            Err(e) => match e {
+                Error::NoMemoryAvailable => libc::ENOMEM,
+                Error::SizeTooBig => libc::ENAMETOOLONG,
+                _ => // Unknown error, fallback to EIO
+                    libc::EIO,
+            },
+        }
+    } else {
+        libc::EINVAL
+    }
+}
+
+/// Charges a computation of `flops` floating-point operations to the simulated host and blocks
+/// until the simulator reports its completion. With the process backend, simulated time skips
+/// forward during the computation instead of following real time, so other application threads
+/// should be idle meanwhile.
+///
+/// # Safety
+///
+/// * `context` should point to a valid context, as previously returned by [`vsg_init`].
+///
+/// # Error codes
+///
+/// * Fails with `libc::EINVAL` whenever `context` is NULL or `flops` is negative or not a number.
+///
+/// * Fails with `libc::ENOTCONN` whenever `context` is not started yet.
+///
+/// * Fails with `libc::ESHUTDOWN` whenever the simulation has ended or ends before the computation
+///   completes.
+#[no_mangle]
+pub unsafe extern "C" fn vsg_execute(context: *const Context, flops: f64) -> c_int {
+    if let Some(context) = context.as_ref() {
+        if flops.is_nan() || flops < 0.0 {
+            return libc::EINVAL;
+        }
+
+        match context.execute(flops) {
+            Ok(_) => 0,
+            Err(e) => match e {
+                Error::NoMemoryAvailable => libc::ENOMEM,
+                Error::NotStarted => libc::ENOTCONN,
+                Error::SimulationEnded => libc::ESHUTDOWN,
+                _ => // Unknown error, fallback to EIO
+                    libc::EIO,
+            },
+        }
+    } else {
+        libc::EINVAL
+    }
+}
+
+/// Saves the state of the context that is not part of the application memory, such as packets not
+/// yet sent to the simulator, received packets not yet read and the deadlines, to be restored with
+/// [`vsg_load_state`] along with a VM snapshot. `*state_len` initially contains the size of the
+/// buffer pointed to by `state`. When `vsg_save_state` returns with success, `*state_len` contains
+/// the actual length of the saved state.
+///
+/// The deadline handler must not run meanwhile: the simulation must not be started, or have
+/// ended, or the VM must be paused (QEMU backends).
+///
+/// # Safety
+///
+/// * `context` should point to a valid context, as previously returned by [`vsg_init`].
+///
+/// * `state` must point to a valid memory range of at least `*state_len` bytes. It can be `NULL`
+///   if `*state_len` is `0`.
+///
+/// # Error codes
+///
+/// * Fails with `libc::EINVAL` whenever `context` or `state_len` is NULL, or `state` is NULL and
+///   `*state_len` is not `0`.
+///
+/// * Fails with `libc::EBUSY` whenever the simulation is running.
+///
+/// * Fails with `libc::ENOBUFS` whenever the buffer is too small. `*state_len` then contains the
+///   required size.
+#[no_mangle]
+pub unsafe extern "C" fn vsg_save_state(context: *const Context, state: *mut u8, state_len: *mut usize) -> c_int {
+    if let (Some(context), Some(state_len)) = (context.as_ref(), state_len.as_mut()) {
+        if state.is_null() && *state_len != 0 {
+            return libc::EINVAL;
+        }
+
+        let saved = match context.save_state() {
+            Ok(saved) => saved,
+            Err(e) => return match e {
+                Error::SimulationRunning => libc::EBUSY,
+                _ => // Unknown error, fallback to EIO
+                    libc::EIO,
+            },
+        };
+        if saved.len() > *state_len {
+            *state_len = saved.len();
+            return libc::ENOBUFS;
+        }
+        std::ptr::copy_nonoverlapping(saved.as_ptr(), state, saved.len());
+        *state_len = saved.len();
+        0
+    } else {
+        libc::EINVAL
+    }
+}
+
+/// Restores a state saved by [`vsg_save_state`], possibly in another simulation. Must be called
+/// before [`vsg_start`]. Simulation time then resumes at the saved deadline, so the simulator must
+/// give a later first deadline. A state can be loaded only once. The context cannot be used
+/// anymore if loading a valid state fails.
+///
+/// # Safety
+///
+/// * `context` should point to a valid context, as previously returned by [`vsg_init`].
+///
+/// * `state` must point to a valid memory range of at least `state_len` bytes.
+///
+/// # Error codes
+///
+/// * Fails with `libc::EINVAL` whenever `context` or `state` is NULL, or `state` is not a saved
+///   state of a supported version.
+///
+/// * Fails with `libc::EALREADY` whenever the context is already started or a state was already
+///   loaded.
+///
+/// * Fails with `libc::ENOMEM` whenever the saved messages do not fit in the context buffers.
+#[no_mangle]
+pub unsafe extern "C" fn vsg_load_state(context: *const Context, state: *const u8, state_len: usize) -> c_int {
+    if let Some(context) = context.as_ref() {
+        if state.is_null() {
+            return libc::EINVAL;
+        }
+        let state = std::slice::from_raw_parts(state, state_len);
+
+        match context.load_state(state) {
+            Ok(_) => 0,
+            Err(e) => match e {
+                Error::AlreadyLoaded => libc::EALREADY,
+                Error::AlreadyStarted => libc::EALREADY,
+                Error::InvalidState => libc::EINVAL,
+                Error::NoMemoryAvailable => libc::ENOMEM,
+                _ => // Unknown error, fallback to EIO
+                    libc::EIO,
+            },
+        }
+    } else {
+        libc::EINVAL
+    }
+}
+
+/// Copies the counters of the context to `*stats`, for instance to check the health of a VM at the
+/// end of an experiment. Counters start at the creation of the context.
+///
+/// # Safety
+///
+/// * `context` should point to a valid context, as previously returned by [`vsg_init`].
+///
+/// * `stats` should point to a valid `struct vsg_stats`.
+///
+/// # Error codes
+///
+/// * Fails with `libc::EINVAL` whenever `context` or `stats` is NULL.
+#[no_mangle]
+pub unsafe extern "C" fn vsg_get_stats(context: *const Context, stats: *mut VsgStats) -> c_int {
+    if let (Some(context), Some(stats)) = (context.as_ref(), stats.as_mut()) {
+        *stats = context.stats().into();
+        0
+    } else {
+        libc::EINVAL
+    }
+}
+
+/// Writes the histograms of the deadline handler timing to the file at `path`, as CSV lines
+/// `histogram,low_ns,high_ns,count`. Histograms are `jitter` (delay to handle a deadline after it
+/// expired), `frozen` (time spent handling a deadline) and `slice` (host time between the start of
+/// two deadline handlers). The file is overwritten.
+///
+/// # Safety
+///
+/// * `context` should point to a valid context, as previously returned by [`vsg_init`].
+///
+/// * `path` should point to a NUL-terminated string.
+///
+/// # Error codes
+///
+/// * Fails with `libc::EINVAL` whenever `context` or `path` is NULL, or `path` is not valid UTF-8.
+///
+/// * Fails with the error code of the system call whenever the file cannot be written.
+#[no_mangle]
+pub unsafe extern "C" fn vsg_dump_timing(context: *const Context, path: *const c_char) -> c_int {
+    if let Some(context) = context.as_ref() {
+        if path.is_null() {
+            return libc::EINVAL;
+        }
+        let path = match std::ffi::CStr::from_ptr(path).to_str() {
+            Ok(path) => path,
+            Err(_) => return libc::EINVAL,
+        };
+
+        let res = std::fs::File::create(path).and_then(|file| {
+            let mut writer = std::io::BufWriter::new(file);
+            context.timing_report().write_csv(&mut writer)?;
+            std::io::Write::flush(&mut writer)
+        });
+        match res {
+            Ok(_) => 0,
+            Err(e) => match e.raw_os_error() {
+                Some(errno) => errno,
+                None => // Unknown error, fallback to EIO
+                    libc::EIO,
+            },
+        }
+    } else {
+        libc::EINVAL
+    }
+}
+
+/// Writes the latest records logged by the deadline handler to the file descriptor `fd`, one per
+/// line, including records already forwarded to the log. The deadline handler logs to a
+/// preallocated in-memory ring, only with the `deadline-handler-debug` feature. Without the
+/// feature, nothing is written.
+///
+/// This function is async-signal-safe and can be called from a crash handler or at exit.
+///
+/// # Error codes
+///
+/// * Fails with the error code of `write()` whenever writing to `fd` fails.
+#[no_mangle]
+pub extern "C" fn vsg_dump_deadline_log(fd: c_int) -> c_int {
+    match tansiv_client::dump_deadline_log(fd) {
+        Ok(_) => 0,
+        Err(e) => match e.raw_os_error() {
+            Some(errno) => errno,
+            None => // Unknown error, fallback to EIO
+                libc::EIO,
+        },
+    }
+}
+
+/// Picks the next message in the receive queue, stores its payload in `msg[0..*msglen]` and
+/// optionnally returns sender and destination addresses in `*psrc` and `*pdst` respectively.
+/// `*msglen` initially contains the size of the buffer pointed to by `msg`. When `vsg_recv`
+/// returns with success, `*msglen` contains the actual length of the received payload.
+///
+/// # Safety
+///
+/// * `context` should point to a valid context, as previously returned by [`vsg_init`].
+///
+/// * `psrc` and `pdst` can be `NULL`, in which case the correponding addresses will not be returned.
+///
+/// * If `msglen` is not `NULL`, `msg` must point to a valid memory range of at least `*msglen`
+///   bytes. This memory range does not need to be initialized.
+///
+/// * If `msglen` is `NULL` or `*msglen` is `0`, only 0-length messages can be received. Note that
+///   in that case it is allowed that `msg` is `NULL` too.
+///
+/// # Error codes
+///
+/// * Fails with `libc::EINVAL` whenever the pointers in arguments do not respect the rules above.
+///
+/// * Fails with `libc::EAGAIN` if the receive queue was empty.
+///
+/// * Fails with `libc::EMSGSIZE` whenever the next message in the queue has a payload bigger than
+///   the provided buffer. The message is lost.
+///
+/// * Fails with `libc::EAFNOSUPPORT` whenever the next message in the queue has IPv6 addresses. Use
+///   [`vsg_recv6`] to receive such messages. The message stays in the queue.
+#[no_mangle]
+pub unsafe extern fn vsg_recv(context: *const Context, psrc: *mut libc::in_addr_t, pdst: *mut libc::in_addr_t, msglen: *mut u32, msg: *mut u8) -> c_int {
+    const_assert!(tansiv_client::MAX_PACKET_SIZE <= std::u32::MAX as usize);
+
+    if let Some(context) = context.as_ref() {
+        let len = if msglen.is_null() {
+            0
+        } else {
+            *msglen
+        };
+        // We can tolerate msg.is_null() if len == 0 but std::slice::from_raw_parts_mut() requires
+        // non null pointers.
+        let ptr = if len == 0 {
+            std::ptr::NonNull::dangling().as_ptr()
+        } else {
+            if msg.is_null() {
+                return libc::EINVAL;
+            };
+            msg
+        };
+        let payload = std::slice::from_raw_parts_mut(ptr, len as usize);
+
+        match (*context).recv(payload) {
+            Ok((src, dst, payload)) => {
+                if !psrc.is_null() {
+                    *psrc = src;
+                }
+                if !pdst.is_null() {
+                    *pdst = dst;
+                }
+                if !msglen.is_null() {
+                    *msglen = payload.len() as u32;
+                }
+                0
+            },
+            Err(e) => match e {
+                Error::InvalidAddress => libc::EAFNOSUPPORT,
+                Error::NoMessageAvailable => libc::EAGAIN,
+                Error::SizeTooBig => libc::EMSGSIZE,
+                _ => // Unknown error, fallback to EIO
+                    libc::EIO,
+            },
+        }
+    } else {
+        libc::EINVAL
+    }
+}
+
+/// Same as [`vsg_recv`] with IPv6 sender and destination addresses. IPv4 addresses are returned as
+/// IPv4-mapped addresses (`::ffff:a.b.c.d`).
+///
+/// # Safety
+///
+/// * Same as [`vsg_recv`].
+///
+/// # Error codes
+///
+/// * Same as [`vsg_recv`], except that messages with IPv4 addresses are accepted.
+#[no_mangle]
+pub unsafe extern "C" fn vsg_recv6(context: *const Context, psrc: *mut libc::in6_addr, pdst: *mut libc::in6_addr, msglen: *mut u32, msg: *mut u8) -> c_int {
+    if let Some(context) = context.as_ref() {
+        let len = if msglen.is_null() {
+            0
+        } else {
+            *msglen
+        };
+        let ptr = if len == 0 {
+            std::ptr::NonNull::dangling().as_ptr()
+        } else {
+            if msg.is_null() {
+                return libc::EINVAL;
+            };
+            msg
+        };
+        let payload = std::slice::from_raw_parts_mut(ptr, len as usize);
+
+        match (*context).recv6(payload) {
+            Ok((src, dst, payload)) => {
+                if let Some(psrc) = psrc.as_mut() {
+                    psrc.s6_addr = src;
+                }
+                if let Some(pdst) = pdst.as_mut() {
+                    pdst.s6_addr = dst;
+                }
+                if !msglen.is_null() {
+                    *msglen = payload.len() as u32;
+                }
+                0
+            },
+            Err(e) => match e {
+                Error::NoMessageAvailable => libc::EAGAIN,
+                Error::SizeTooBig => libc::EMSGSIZE,
+                _ => // Unknown error, fallback to EIO
+                    libc::EIO,
+            },
+        }
+    } else {
+        libc::EINVAL
+    }
+}
+
+/// Receives the next message if it was sent with [`vsg_send_synthetic`], and stores its size on
+/// the simulated wire in `*size`. Other messages are left in place, to be read with [`vsg_recv`]
+/// and friends.
+///
+/// # Safety
+///
+/// * `context` should point to a valid context, as previously returned by [`vsg_init`].
+///
+/// * `psrc`, `pdst` and `size` can be `NULL`, in which case the corresponding value will not be
+///   returned. IPv4 addresses are returned as IPv4-mapped addresses.
+///
+/// # Error codes
+///
+/// * Fails with `libc::EINVAL` whenever `context` is `NULL`.
+///
+/// * Fails with `libc::EAGAIN` if no message is available.
+///
+/// * Fails with `libc::EPROTOTYPE` if the next message is not synthetic.
+#[no_mangle]
+pub unsafe extern "C" fn vsg_recv_synthetic(context: *const Context, psrc: *mut libc::in6_addr, pdst: *mut libc::in6_addr, size: *mut u32) -> c_int {
+    if let Some(context) = context.as_ref() {
+        match context.recv_synthetic() {
+            Ok((src, dst, wire_size)) => {
+                if let Some(psrc) = psrc.as_mut() {
+                    psrc.s6_addr = src.ipv6();
+                }
+                if let Some(pdst) = pdst.as_mut() {
+                    pdst.s6_addr = dst.ipv6();
+                }
+                if let Some(size) = size.as_mut() {
+                    *size = wire_size;
+                }
+                0
+            },
+            Err(e) => match e {
+                Error::NoMessageAvailable => libc::EAGAIN,
+                Error::NotSynthetic => libc::EPROTOTYPE,
+                _ => // Unknown error, fallback to EIO
+                    libc::EIO,
+            },
+        }
+    } else {
+        libc::EINVAL
+    }
+}
+
+/// Same as [`vsg_recv6`] and also returns the extended metadata of the message in `*attr`.
+/// Messages sent without attributes have all-zero attributes.
+///
+/// # Safety
+///
+/// * Same as [`vsg_recv6`].
+///
+/// * `attr` can be `NULL`, in which case the attributes will not be returned.
+///
+/// # Error codes
+///
+/// * Same as [`vsg_recv6`].
+#[no_mangle]
+pub unsafe extern "C" fn vsg_recv_ex(context: *const Context, psrc: *mut libc::in6_addr, pdst: *mut libc::in6_addr, attr: *mut VsgPacketAttr, msglen: *mut u32, msg: *mut u8) -> c_int {
+    if let Some(context) = context.as_ref() {
+        let len = if msglen.is_null() {
+            0
+        } else {
+            *msglen
+        };
+        let ptr = if len == 0 {
+            std::ptr::NonNull::dangling().as_ptr()
+        } else {
+            if msg.is_null() {
+                return libc::EINVAL;
+            };
+            msg
+        };
+        let payload = std::slice::from_raw_parts_mut(ptr, len as usize);
+
+        match (*context).recv_ex(payload) {
+            Ok((src, dst, attributes, payload)) => {
+                if let Some(psrc) = psrc.as_mut() {
+                    psrc.s6_addr = src.ipv6();
+                }
+                if let Some(pdst) = pdst.as_mut() {
+                    pdst.s6_addr = dst.ipv6();
+                }
+                if let Some(attr) = attr.as_mut() {
+                    *attr = attributes.into();
+                }
+                if !msglen.is_null() {
+                    *msglen = payload.len() as u32;
+                }
+                0
+            },
+            Err(e) => match e {
+                Error::NoMessageAvailable => libc::EAGAIN,
+                Error::SizeTooBig => libc::EMSGSIZE,
+                _ => // Unknown error, fallback to EIO
+                    libc::EIO,
+            },
+        }
+    } else {
+        libc::EINVAL
+    }
+}
+
+/// Tells that the application will not send any message during the next `*delay` of simulated
+/// time, unless it receives a message first. This is typically the delay until the next armed
+/// timer. The simulator can use this hint to skip idle time slices. A `NULL` delay withdraws the
+/// hint.
+///
+/// # Safety
+///
+/// * `context` should point to a valid context, as previously returned by [`vsg_init`].
+///
+/// * `delay` can be `NULL`.
+///
+/// # Error codes
+///
+/// * Fails with `libc::EINVAL` whenever `context` is NULL or `*delay` is negative or not
+///   normalized.
+#[no_mangle]
+pub unsafe extern "C" fn vsg_set_idle_hint(context: *const Context, delay: *const libc::timespec) -> c_int {
+    if let Some(context) = context.as_ref() {
+        let delay = match delay.as_ref() {
+            Some(delay) => {
+                if delay.tv_sec < 0 || delay.tv_nsec < 0 || delay.tv_nsec >= 1_000_000_000 {
+                    return libc::EINVAL;
+                }
+                Some(std::time::Duration::new(delay.tv_sec as u64, delay.tv_nsec as u32))
+            },
+            None => None,
+        };
+        (*context).set_idle_hint(delay);
+        0
+    } else {
+        libc::EINVAL
+    }
+}
+
+/// Checks if a message can be read from the input queue. If `0` is returned a message can be read
+/// from the input queue using [`vsg_recv`].
+///
+/// # Safety
+///
+/// * `context` should point to a valid context, as previously returned by [`vsg_init`].
+///
+/// # Error codes
+///
+/// * Fails with `libc::EINVAL` whenever `context` is NULL.
+///
+/// * Fails with `libc::EAGAIN` if the receive queue was empty.
+#[no_mangle]
+pub unsafe extern fn vsg_poll(context: *const Context) -> c_int {
+    if let Some(context) = context.as_ref() {
+        match (*context).poll() {
+            Some(_) => 0,
+            None => libc::EAGAIN,
+        }
+    } else {
+        libc::EINVAL
+    }
+}
+
+fn map_group_error(e: Error) -> c_int {
+    match e {
+        Error::InvalidAddress => libc::EINVAL,
+        Error::NoMemoryAvailable => libc::ENOMEM,
+        _ => // Unknown error, fallback to EIO
+            libc::EIO,
+    }
+}
+
+/// Joins the multicast group `group`. Messages sent to a multicast group are only received by
+/// contexts that joined it. Membership changes take effect at the next deadline.
+///
+/// # Safety
+///
+/// * `context` should point to a valid context, as previously returned by [`vsg_init`].
+///
+/// # Error codes
+///
+/// * Fails with `libc::EINVAL` whenever `context` is NULL or `group` is not a multicast
+///   address.
+///
+/// * Fails with `libc::ENOMEM` if too many membership changes are pending.
+#[no_mangle]
+pub unsafe extern "C" fn vsg_join_group(context: *const Context, group: libc::in_addr_t) -> c_int {
+    if let Some(context) = context.as_ref() {
+        match (*context).join_group(group.into()) {
+            Ok(_) => 0,
+            Err(e) => map_group_error(e),
+        }
+    } else {
+        libc::EINVAL
+    }
+}
+
+/// Leaves the multicast group `group`, see [`vsg_join_group`].
+///
+/// # Safety
+///
+/// * Same as [`vsg_join_group`].
+///
+/// # Error codes
+///
+/// * Same as [`vsg_join_group`].
+#[no_mangle]
+pub unsafe extern "C" fn vsg_leave_group(context: *const Context, group: libc::in_addr_t) -> c_int {
+    if let Some(context) = context.as_ref() {
+        match (*context).leave_group(group.into()) {
+            Ok(_) => 0,
+            Err(e) => map_group_error(e),
+        }
+    } else {
+        libc::EINVAL
+    }
+}
+
+/// Same as [`vsg_join_group`] with an IPv6 multicast group `*group`.
+///
+/// # Safety
 ///
-/// * If `msglen` is not `NULL`, `msg` must point to a valid memory range of at least `*msglen`
-///   bytes. This memory range does not need to be initialized.
+/// * `context` should point to a valid context, as previously returned by [`vsg_init`].
 ///
-/// * If `msglen` is `NULL` or `*msglen` is `0`, only 0-length messages can be received. Note that
-///   in that case it is allowed that `msg` is `NULL` too.
+/// * `group` should point to a valid IPv6 address.
 ///
 /// # Error codes
 ///
-/// * Fails with `libc::EINVAL` whenever the pointers in arguments do not respect the rules above.
-///
-/// * Fails with `libc::EAGAIN` if the receive queue was empty.
+/// * Same as [`vsg_join_group`].
 ///
-/// * Fails with `libc::EMSGSIZE` whenever the next message in the queue has a payload bigger than
-///   the provided buffer. The message is lost.
+/// * Fails with `libc::EINVAL` whenever `group` is `NULL`.
 #[no_mangle]
-pub unsafe extern fn vsg_recv(context: *const Context, psrc: *mut libc::in_addr_t, pdst: *mut libc::in_addr_t, msglen: *mut u32, msg: *mut u8) -> c_int {
-    const_assert!(tansiv_client::MAX_PACKET_SIZE <= std::u32::MAX as usize);
-
-    if let Some(context) = context.as_ref() {
-        let len = if msglen.is_null() {
-            0
-        } else {
-            *msglen
-        };
-        // We can tolerate msg.is_null() if len == 0 but std::slice::from_raw_parts_mut() requires
-        // non null pointers.
-        let ptr = if len == 0 {
-            std::ptr::NonNull::dangling().as_ptr()
-        } else {
-            if msg.is_null() {
-                return libc::EINVAL;
-            };
-            msg
-        };
-        let payload = std::slice::from_raw_parts_mut(ptr, len as usize);
-
-        match (*context).recv(payload) {
-            Ok((src, dst, payload)) => {
-                if !psrc.is_null() {
-                    *psrc = src;
-                }
-                if !pdst.is_null() {
-                    *pdst = dst;
-                }
-                if !msglen.is_null() {
-                    *msglen = payload.len() as u32;
-                }
-                0
-            },
-            Err(e) => match e {
-                Error::NoMessageAvailable => libc::EAGAIN,
-                Error::SizeTooBig => libc::EMSGSIZE,
-                _ => // Unknown error, fallback to EIO
-                    libc::EIO,
-            },
+pub unsafe extern "C" fn vsg_join_group6(context: *const Context, group: *const libc::in6_addr) -> c_int {
+    if let (Some(context), Some(group)) = (context.as_ref(), group.as_ref()) {
+        match (*context).join_group(VsgAddress::from_ipv6(group.s6_addr)) {
+            Ok(_) => 0,
+            Err(e) => map_group_error(e),
         }
     } else {
         libc::EINVAL
     }
 }
 
-/// Checks if a message can be read from the input queue. If `0` is returned a message can be read
-/// from the input queue using [`vsg_recv`].
+/// Same as [`vsg_leave_group`] with an IPv6 multicast group `*group`.
 ///
 /// # Safety
 ///
-/// * `context` should point to a valid context, as previously returned by [`vsg_init`].
+/// * Same as [`vsg_join_group6`].
 ///
 /// # Error codes
 ///
-/// * Fails with `libc::EINVAL` whenever `context` is NULL.
-///
-/// * Fails with `libc::EAGAIN` if the receive queue was empty.
+/// * Same as [`vsg_join_group6`].
 #[no_mangle]
-pub unsafe extern fn vsg_poll(context: *const Context) -> c_int {
-    if let Some(context) = context.as_ref() {
-        match (*context).poll() {
-            Some(_) => 0,
-            None => libc::EAGAIN,
+pub unsafe extern "C" fn vsg_leave_group6(context: *const Context, group: *const libc::in6_addr) -> c_int {
+    if let (Some(context), Some(group)) = (context.as_ref(), group.as_ref()) {
+        match (*context).leave_group(VsgAddress::from_ipv6(group.s6_addr)) {
+            Ok(_) => 0,
+            Err(e) => map_group_error(e),
         }
     } else {
         libc::EINVAL
@@ -793,6 +1939,319 @@ mod test {
         drop(actor);
     }
 
+    #[test]
+    fn recv6() {
+        init();
+
+        const EXPECTED_MSG: &[u8] = b"Foo msg";
+        let mut buffer = [0u8; EXPECTED_MSG.len()];
+
+        let actor = TestActorDesc::new("titi", |actor| send_one_msg_actor(actor, EXPECTED_MSG));
+
+        let args = valid_args!();
+        let recv_notifier = RecvNotifier::new();
+        let recv_notifier = recv_notifier.pin();
+        let context = unsafe { vsg_init(args.argc(), args.argv(), std::ptr::null_mut(), RecvNotifier::callback, RecvNotifier::get_callback_arg(&recv_notifier), dummy_deadline_callback, 0) };
+        assert!(!context.is_null());
+
+        let res: c_int = unsafe { vsg_start(context, std::ptr::null_mut()) };
+        assert_eq!(0, res);
+
+        recv_notifier.wait(1000);
+
+        let mut src = libc::in6_addr { s6_addr: [0; 16] };
+        let mut dst = libc::in6_addr { s6_addr: [0; 16] };
+        let mut buffer_len: u32 = buffer.len() as u32;
+        let res: c_int = unsafe { vsg_recv6(context, &mut src, &mut dst, &mut buffer_len, buffer.as_mut().as_mut_ptr()) };
+        assert_eq!(0, res);
+
+        // IPv4 addresses are returned as IPv4-mapped addresses
+        assert_eq!(src.s6_addr, mapped(local_vsg_address!()));
+        assert_eq!(dst.s6_addr, mapped(remote_vsg_address!()));
+        assert_eq!(buffer_len, EXPECTED_MSG.len() as u32);
+        assert_eq!(buffer, EXPECTED_MSG);
+
+        let res: c_int = unsafe { vsg_stop(context) };
+        assert_eq!(0, res);
+
+        unsafe { vsg_cleanup(context) };
+        drop(actor);
+    }
+
+    // Entry points that only check their context, not covered by the tests below
+    #[test]
+    fn no_context() {
+        init();
+
+        let buffer = b"Foo msg";
+        let addr = libc::in6_addr { s6_addr: [0; 16] };
+        let res: c_int = unsafe { vsg_send6(std::ptr::null(), &addr, buffer.len() as u32, buffer.as_ref().as_ptr()) };
+        assert_eq!(libc::EINVAL, res);
+        let res: c_int = unsafe { vsg_send_from(std::ptr::null(), local_vsg_address!(), remote_vsg_address!(), buffer.len() as u32, buffer.as_ref().as_ptr()) };
+        assert_eq!(libc::EINVAL, res);
+        let res: c_int = unsafe { vsg_send_ex(std::ptr::null(), std::ptr::null(), &addr, std::ptr::null(), buffer.len() as u32, buffer.as_ref().as_ptr(), std::ptr::null_mut()) };
+        assert_eq!(libc::EINVAL, res);
+        let res: c_int = unsafe { vsg_send_synthetic(std::ptr::null(), &addr, 1234) };
+        assert_eq!(libc::EINVAL, res);
+        let mut size = 0u32;
+        let res: c_int = unsafe { vsg_recv_synthetic(std::ptr::null(), std::ptr::null_mut(), std::ptr::null_mut(), &mut size) };
+        assert_eq!(libc::EINVAL, res);
+        let res: c_int = unsafe { vsg_inject(std::ptr::null(), &addr, buffer.len() as u32, buffer.as_ref().as_ptr()) };
+        assert_eq!(libc::EINVAL, res);
+        let res: c_int = unsafe { vsg_set_disk_callback(std::ptr::null(), None, 0) };
+        assert_eq!(libc::EINVAL, res);
+        let disk = b"disk0\0";
+        let res: c_int = unsafe { vsg_disk_submit(std::ptr::null(), disk.as_ptr() as *const c_char, VSG_DISK_READ, 4096, std::ptr::null_mut()) };
+        assert_eq!(libc::EINVAL, res);
+        let label = b"leader elected\0";
+        let res: c_int = unsafe { vsg_mark(std::ptr::null(), label.as_ptr() as *const c_char, 42) };
+        assert_eq!(libc::EINVAL, res);
+        let res: c_int = unsafe { vsg_execute(std::ptr::null(), 1e9) };
+        assert_eq!(libc::EINVAL, res);
+        let state = [0u8; 8];
+        let res: c_int = unsafe { vsg_load_state(std::ptr::null(), state.as_ptr(), state.len()) };
+        assert_eq!(libc::EINVAL, res);
+        let mut stats = VsgStats::default();
+        let res: c_int = unsafe { vsg_get_stats(std::ptr::null(), &mut stats) };
+        assert_eq!(libc::EINVAL, res);
+        let path = std::ffi::CString::new("/dev/null").unwrap();
+        let res: c_int = unsafe { vsg_dump_timing(std::ptr::null(), path.as_ptr()) };
+        assert_eq!(libc::EINVAL, res);
+        let res: c_int = unsafe { vsg_set_idle_hint(std::ptr::null(), std::ptr::null()) };
+        assert_eq!(libc::EINVAL, res);
+        let res: c_int = unsafe { vsg_join_group(std::ptr::null(), u32::from(std::net::Ipv4Addr::new(239, 0, 0, 1)).to_be()) };
+        assert_eq!(libc::EINVAL, res);
+        let res: c_int = unsafe { vsg_leave_group6(std::ptr::null(), std::ptr::null()) };
+        assert_eq!(libc::EINVAL, res);
+    }
+
+    #[test]
+    fn dump_deadline_log() {
+        init();
+
+        let fd = unsafe { libc::open(b"/dev/null\0".as_ptr() as *const c_char, libc::O_WRONLY) };
+        assert!(fd >= 0);
+        let res: c_int = vsg_dump_deadline_log(fd);
+        unsafe { libc::close(fd) };
+        assert_eq!(0, res);
+    }
+
+    fn mapped(address: libc::in_addr_t) -> [u8; 16] {
+        std::net::Ipv4Addr::from(u32::from_be(address)).to_ipv6_mapped().octets()
+    }
+
+    // Waits for a callback to record a value. The record_*_callback() functions below write to a
+    // SeqLock, which never blocks the deadline handler.
+    fn wait_record<T: Copy>(record: &SeqLock<Option<T>>) -> Option<T> {
+        for _ in 0..1000 {
+            if let Some(value) = record.read(|r| r) {
+                return Some(value);
+            }
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        None
+    }
+
+    unsafe extern "C" fn record_report_callback(arg: uintptr_t, report: *const VsgDeliveryReport) -> () {
+        let record = (arg as *const SeqLock<Option<VsgDeliveryReport>>).as_ref().unwrap();
+        let report = *report;
+        record.write(|_| Some(report));
+    }
+
+    #[test]
+    fn report_callback() {
+        init();
+
+        let actor = TestActorDesc::new("titi", report_actor);
+        let args = valid_args!();
+        let context = unsafe { vsg_init(args.argc(), args.argv(), std::ptr::null_mut(), dummy_recv_callback, 0, dummy_deadline_callback, 0) };
+        assert!(!context.is_null());
+
+        let report: SeqLock<Option<VsgDeliveryReport>> = SeqLock::new(None);
+        let res: c_int = unsafe { vsg_set_report_callback(context, Some(record_report_callback as CReportCallback), &report as *const _ as uintptr_t) };
+        assert_eq!(0, res);
+
+        let res: c_int = unsafe { vsg_start(context, std::ptr::null_mut()) };
+        assert_eq!(0, res);
+
+        let buffer = b"Foo msg";
+        let dst = libc::in6_addr { s6_addr: mapped(remote_vsg_address!()) };
+        let mut seq_id = 0u64;
+        let res: c_int = unsafe { vsg_send_ex(context, std::ptr::null(), &dst, std::ptr::null(), buffer.len() as u32, buffer.as_ref().as_ptr(), &mut seq_id) };
+        assert_eq!(0, res);
+        assert_ne!(0, seq_id);
+
+        let report = wait_record(&report).expect("No delivery report");
+        assert_eq!(seq_id, report.seq_id);
+        assert_eq!(VSG_DROP_LOSS, report.drop_reason);
+
+        let res: c_int = unsafe { vsg_stop(context) };
+        assert_eq!(0, res);
+
+        unsafe { vsg_cleanup(context) };
+        drop(actor);
+    }
+
+    unsafe extern "C" fn record_end_callback(arg: uintptr_t, reason: c_int) -> () {
+        let record = (arg as *const SeqLock<Option<c_int>>).as_ref().unwrap();
+        record.write(|_| Some(reason));
+    }
+
+    #[test]
+    fn end_callback() {
+        init();
+
+        let actor = TestActorDesc::new("titi", start_actor);
+        let args = valid_args!();
+        let context = unsafe { vsg_init(args.argc(), args.argv(), std::ptr::null_mut(), dummy_recv_callback, 0, dummy_deadline_callback, 0) };
+        assert!(!context.is_null());
+
+        let reason: SeqLock<Option<c_int>> = SeqLock::new(None);
+        let res: c_int = unsafe { vsg_set_end_callback(context, Some(record_end_callback as CEndCallback), &reason as *const _ as uintptr_t) };
+        assert_eq!(0, res);
+
+        let res: c_int = unsafe { vsg_start(context, std::ptr::null_mut()) };
+        assert_eq!(0, res);
+
+        assert_eq!(Some(VSG_END_ACTOR_REQUEST), wait_record(&reason));
+
+        let res: c_int = unsafe { vsg_stop(context) };
+        assert_eq!(0, res);
+
+        unsafe { vsg_cleanup(context) };
+        drop(actor);
+    }
+
+    unsafe extern "C" fn record_link_state_callback(arg: uintptr_t, address: *const libc::in6_addr, up: c_int) -> () {
+        let record = (arg as *const SeqLock<Option<([u8; 16], c_int)>>).as_ref().unwrap();
+        let address = (*address).s6_addr;
+        record.write(|_| Some((address, up)));
+    }
+
+    #[test]
+    fn link_state_callback() {
+        init();
+
+        let actor = TestActorDesc::new("titi", link_state_actor);
+        let args = valid_args!();
+        let context = unsafe { vsg_init(args.argc(), args.argv(), std::ptr::null_mut(), dummy_recv_callback, 0, dummy_deadline_callback, 0) };
+        assert!(!context.is_null());
+
+        let link_state: SeqLock<Option<([u8; 16], c_int)>> = SeqLock::new(None);
+        let res: c_int = unsafe { vsg_set_link_state_callback(context, Some(record_link_state_callback as CLinkStateCallback), &link_state as *const _ as uintptr_t) };
+        assert_eq!(0, res);
+
+        let res: c_int = unsafe { vsg_start(context, std::ptr::null_mut()) };
+        assert_eq!(0, res);
+
+        // IPv4 addresses are passed as IPv4-mapped addresses
+        assert_eq!(Some((mapped(local_vsg_address!()), 0)), wait_record(&link_state));
+
+        let res: c_int = unsafe { vsg_stop(context) };
+        assert_eq!(0, res);
+
+        unsafe { vsg_cleanup(context) };
+        drop(actor);
+    }
+
+    #[derive(Clone, Copy)]
+    struct TappedPacket {
+        src: [u8; 16],
+        dst: [u8; 16],
+        len: u32,
+    }
+
+    unsafe extern "C" fn record_tap_callback(arg: uintptr_t, _send_time: timespec, src: *const libc::in6_addr, dst: *const libc::in6_addr, msglen: u32, _msg: *const u8) -> () {
+        let record = (arg as *const SeqLock<Option<TappedPacket>>).as_ref().unwrap();
+        let packet = TappedPacket {
+            src: (*src).s6_addr,
+            dst: (*dst).s6_addr,
+            len: msglen,
+        };
+        record.write(|_| Some(packet));
+    }
+
+    // vsg_send_ex() sends from the default address of the context when src is NULL
+    #[test]
+    fn send_ex_no_src() {
+        init();
+
+        let actor = TestActorDesc::new("titi", recv_one_msg_actor);
+        let args = valid_args!();
+        let context = unsafe { vsg_init(args.argc(), args.argv(), std::ptr::null_mut(), dummy_recv_callback, 0, dummy_deadline_callback, 0) };
+        assert!(!context.is_null());
+
+        let tapped: SeqLock<Option<TappedPacket>> = SeqLock::new(None);
+        let res: c_int = unsafe { vsg_set_tap_callback(context, Some(record_tap_callback as CTapCallback), &tapped as *const _ as uintptr_t) };
+        assert_eq!(0, res);
+
+        let res: c_int = unsafe { vsg_start(context, std::ptr::null_mut()) };
+        assert_eq!(0, res);
+
+        let buffer = b"Foo msg";
+        let dst = libc::in6_addr { s6_addr: mapped(remote_vsg_address!()) };
+        let mut seq_id = 0u64;
+        let res: c_int = unsafe { vsg_send_ex(context, std::ptr::null(), &dst, std::ptr::null(), buffer.len() as u32, buffer.as_ref().as_ptr(), &mut seq_id) };
+        assert_eq!(0, res);
+        assert_ne!(0, seq_id);
+
+        // IPv4 addresses are passed as IPv4-mapped addresses
+        let packet = wait_record(&tapped).expect("Packet not tapped");
+        assert_eq!(mapped(local_vsg_address!()), packet.src);
+        assert_eq!(mapped(remote_vsg_address!()), packet.dst);
+        assert_eq!(buffer.len() as u32, packet.len);
+
+        let res: c_int = unsafe { vsg_stop(context) };
+        assert_eq!(0, res);
+
+        unsafe { vsg_cleanup(context) };
+        drop(actor);
+    }
+
+    #[test]
+    fn save_state() {
+        init();
+
+        let actor = TestActorDesc::new("titi", recv_one_msg_actor);
+        let args = valid_args!();
+        let context = unsafe { vsg_init(args.argc(), args.argv(), std::ptr::null_mut(), dummy_recv_callback, 0, dummy_deadline_callback, 0) };
+        assert!(!context.is_null());
+
+        let res: c_int = unsafe { vsg_start(context, std::ptr::null_mut()) };
+        assert_eq!(0, res);
+
+        let mut state_len = 0usize;
+        let res: c_int = unsafe { vsg_save_state(context, std::ptr::null_mut(), &mut state_len) };
+        assert_eq!(libc::EBUSY, res);
+
+        // Terminate gracefully
+        let buffer = b"Foo msg";
+        let res: c_int = unsafe { vsg_send(context, remote_vsg_address!(), buffer.len() as u32, buffer.as_ref().as_ptr()) };
+        assert_eq!(0, res);
+
+        let res: c_int = unsafe { vsg_stop(context) };
+        assert_eq!(0, res);
+
+        // A NULL state gives the needed length
+        let res: c_int = unsafe { vsg_save_state(context, std::ptr::null_mut(), &mut state_len) };
+        assert_eq!(libc::ENOBUFS, res);
+        assert!(state_len > 0);
+        let needed_len = state_len;
+
+        let mut state = vec![0u8; needed_len];
+        state_len = needed_len - 1;
+        let res: c_int = unsafe { vsg_save_state(context, state.as_mut_ptr(), &mut state_len) };
+        assert_eq!(libc::ENOBUFS, res);
+        assert_eq!(needed_len, state_len);
+
+        let res: c_int = unsafe { vsg_save_state(context, state.as_mut_ptr(), &mut state_len) };
+        assert_eq!(0, res);
+        assert_eq!(needed_len, state_len);
+
+        unsafe { vsg_cleanup(context) };
+        drop(actor);
+    }
+
     #[test]
     fn recv_no_src() {
         init();
@@ -1,6 +1,8 @@
 #ifndef __TANSIV_CLIENT_H__
 #define __TANSIV_CLIENT_H__
 
+#include <netinet/in.h>
+#include <stddef.h>
 #include <stdint.h>
 #include <sys/time.h>
 #include <time.h>
@@ -9,20 +11,143 @@
 
 struct vsg_context;
 
+/* Extended packet metadata. All-zero attributes mean no attributes. */
+struct vsg_packet_attr {
+    uint8_t protocol;  /* L4 protocol number, as in the IP header */
+    uint8_t priority;  /* DSCP value or other priority class */
+    uint16_t src_port;
+    uint16_t dst_port;
+    uint64_t flow_id;  /* User-defined flow identifier */
+    uint32_t wire_size;  /* Size on the simulated wire, 0 for the message length */
+};
+
+/* Outcome of a sent packet. time is the time of delivery, or of drop if drop_reason is not
+ * VSG_DELIVERED. Broadcast and multicast packets get one report per receiver. */
+#define VSG_DELIVERED 0
+#define VSG_DROP_UNKNOWN 1
+#define VSG_DROP_UNREACHABLE 2
+#define VSG_DROP_LOSS 3
+#define VSG_DROP_QUEUE_FULL 4
+
+struct vsg_delivery_report {
+    uint64_t seq_id;
+    struct timespec time;
+    int drop_reason;
+};
+
+/* Reasons passed to the end-of-simulation callback. Without this callback, the qemu and qemukvm backends abort
+ * the VM when the simulation ends. */
+#define VSG_END_ACTOR_REQUEST 1
+#define VSG_END_PROTOCOL_ERROR 2
+#define VSG_END_IO_ERROR 3
+#define VSG_END_DEADLINE_OVERRUN 4
+
+/* Counters of a context, since its creation. Fragments count as separate packets. */
+struct vsg_stats {
+    uint64_t packets_sent;
+    uint64_t bytes_sent;
+    uint64_t packets_received;
+    uint64_t bytes_received;
+    /* Packets not sent for lack of output buffer */
+    uint64_t drops_no_buffer;
+    /* Received packets dropped locally */
+    uint64_t drops_unjoined_group;
+    uint64_t drops_reassembly;
+    uint64_t drops_input_queue_full;
+    /* Sent packets dropped by the simulator, as told by delivery reports */
+    uint64_t drops_unknown;
+    uint64_t drops_unreachable;
+    uint64_t drops_loss;
+    uint64_t drops_queue_full;
+    uint64_t output_buffers_high_water;
+    uint64_t input_queue_high_water;
+    /* Deadline handler runs and host time spent in it, in nanoseconds */
+    uint64_t deadlines;
+    uint64_t deadline_handler_ns;
+    uint64_t deadline_handler_max_ns;
+    /* Messages time-stamped before the previous deadline and sent at it */
+    uint64_t late_timestamps;
+    /* Packets dropped, duplicated, corrupted or delayed by fault injection */
+    uint64_t faults;
+};
+
+/* Disk operations for vsg_disk_submit() */
+#define VSG_DISK_READ 0
+#define VSG_DISK_WRITE 1
+
 typedef void (*vsg_recv_cb)(uintptr_t recv_cb_arg);
 typedef void (*vsg_deadline_cb)(uintptr_t deadline_cb_arg, struct timespec deadline);
+typedef void (*vsg_report_cb)(uintptr_t report_cb_arg, const struct vsg_delivery_report* report);
+typedef void (*vsg_link_state_cb)(uintptr_t link_state_cb_arg, const struct in6_addr* address, int up);
+typedef void (*vsg_disk_cb)(uintptr_t disk_cb_arg, uint64_t request_id, struct timespec time);
+typedef void (*vsg_end_cb)(uintptr_t end_cb_arg, int reason);
+typedef void (*vsg_tap_cb)(uintptr_t tap_cb_arg, struct timespec send_time, const struct in6_addr* src,
+                           const struct in6_addr* dest, uint32_t msglen, const uint8_t* msg);
 
 struct vsg_context* vsg_init(int argc, const char* const argv[], int* next_arg_p,
                              vsg_recv_cb recv_cb, uintptr_t recv_cb_arg,
                              vsg_deadline_cb, uintptr_t deadline_cb_arg);
 void vsg_cleanup(struct vsg_context* context);
 
+int vsg_set_report_callback(const struct vsg_context* context, vsg_report_cb report_cb, uintptr_t report_cb_arg);
+int vsg_set_link_state_callback(const struct vsg_context* context, vsg_link_state_cb link_state_cb,
+                                uintptr_t link_state_cb_arg);
+int vsg_set_end_callback(const struct vsg_context* context, vsg_end_cb end_cb, uintptr_t end_cb_arg);
+int vsg_set_disk_callback(const struct vsg_context* context, vsg_disk_cb disk_cb, uintptr_t disk_cb_arg);
+int vsg_set_tap_callback(const struct vsg_context* context, vsg_tap_cb tap_cb, uintptr_t tap_cb_arg);
+
 int vsg_start(const struct vsg_context* context, struct timespec* offset);
 int vsg_stop(const struct vsg_context* context);
 
 int vsg_gettimeofday(const struct vsg_context* context, struct timeval* timeval, void* timezone);
 int vsg_send(const struct vsg_context* context, uint32_t dest, uint32_t msglen, const uint8_t* msg);
+/* Fails with EAFNOSUPPORT on IPv6 messages, which stay in the queue for vsg_recv6() */
 int vsg_recv(const struct vsg_context* context, uint32_t* src, uint32_t* dest, uint32_t* msglen, uint8_t* msg);
 int vsg_poll(const struct vsg_context* context);
+int vsg_set_idle_hint(const struct vsg_context* context, const struct timespec* delay);
+
+int vsg_send6(const struct vsg_context* context, const struct in6_addr* dest, uint32_t msglen, const uint8_t* msg);
+int vsg_recv6(const struct vsg_context* context, struct in6_addr* src, struct in6_addr* dest, uint32_t* msglen,
+              uint8_t* msg);
+
+int vsg_send_from(const struct vsg_context* context, uint32_t src, uint32_t dest, uint32_t msglen, const uint8_t* msg);
+int vsg_send_from6(const struct vsg_context* context, const struct in6_addr* src, const struct in6_addr* dest,
+                   uint32_t msglen, const uint8_t* msg);
+
+int vsg_send_ex(const struct vsg_context* context, const struct in6_addr* src, const struct in6_addr* dest,
+                const struct vsg_packet_attr* attr, uint32_t msglen, const uint8_t* msg, uint64_t* seq_id);
+int vsg_recv_ex(const struct vsg_context* context, struct in6_addr* src, struct in6_addr* dest,
+                struct vsg_packet_attr* attr, uint32_t* msglen, uint8_t* msg);
+
+/* Payload-less messages, for traffic generators */
+int vsg_send_synthetic(const struct vsg_context* context, const struct in6_addr* dest, uint32_t size);
+int vsg_recv_synthetic(const struct vsg_context* context, struct in6_addr* src, struct in6_addr* dest,
+                       uint32_t* size);
+
+int vsg_join_group(const struct vsg_context* context, uint32_t group);
+int vsg_leave_group(const struct vsg_context* context, uint32_t group);
+int vsg_join_group6(const struct vsg_context* context, const struct in6_addr* group);
+int vsg_leave_group6(const struct vsg_context* context, const struct in6_addr* group);
+
+int vsg_execute(const struct vsg_context* context, double flops);
+int vsg_mark(const struct vsg_context* context, const char* label, int64_t value);
+
+int vsg_save_state(const struct vsg_context* context, uint8_t* state, size_t* state_len);
+int vsg_load_state(const struct vsg_context* context, const uint8_t* state, size_t state_len);
+
+int vsg_get_stats(const struct vsg_context* context, struct vsg_stats* stats);
+int vsg_dump_timing(const struct vsg_context* context, const char* path);
+/* Async-signal-safe */
+int vsg_dump_deadline_log(int fd);
+
+/* Test harnesses */
+int vsg_inject(const struct vsg_context* context, const struct in6_addr* src, uint32_t msglen, const uint8_t* msg);
+
+int vsg_disk_submit(const struct vsg_context* context, const char* disk, int op, uint64_t size, uint64_t* request_id);
+
+/* qemukvm backend only, called by QEMU at each deadline. Returns the length of the next time slice in nanoseconds.
+ * Once the simulation has ended, the last slice length is returned again until the end callback shuts the VM
+ * down. */
+uint64_t deadline_handler(void* opaque, uint64_t guest_tsc);
 
 #endif /* __TANSIV_CLIENT_H__ */
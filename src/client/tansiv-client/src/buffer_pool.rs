@@ -1,7 +1,7 @@
 use self::Error::*;
 use std::fmt;
 use std::ops::{Deref, DerefMut};
-use std::sync::{Arc, atomic::{AtomicBool, Ordering}};
+use std::sync::{Arc, atomic::{AtomicBool, AtomicUsize, Ordering}};
 
 #[derive(Debug)]
 pub enum Error {
@@ -28,6 +28,9 @@ pub struct InnerBufferPool<T: InnerBuffer> {
     buffer_size: usize,
     buffers: T::Array,
     buffer_busy: Vec<AtomicBool>,
+    // Statistics only
+    num_busy: AtomicUsize,
+    max_busy: AtomicUsize,
 }
 
 impl<T: InnerBuffer> InnerBufferPool<T> {
@@ -65,6 +68,8 @@ impl<T: InnerBuffer> BufferPool<T> {
                 buffer_size,
                 buffers,
                 buffer_busy,
+                num_busy: AtomicUsize::new(0),
+                max_busy: AtomicUsize::new(0),
             })
         }
     }
@@ -74,6 +79,8 @@ impl<T: InnerBuffer> BufferPool<T> {
         if size <= pool.buffer_size {
             for (idx, slot) in pool.buffer_busy.iter().enumerate() {
                 if !slot.swap(true, Ordering::AcqRel) {
+                    let num_busy = pool.num_busy.fetch_add(1, Ordering::Relaxed) + 1;
+                    pool.max_busy.fetch_max(num_busy, Ordering::Relaxed);
                     let mut buffer = Buffer {
                         pool: (*self).clone(),
                         index: idx,
@@ -90,9 +97,19 @@ impl<T: InnerBuffer> BufferPool<T> {
         }
     }
 
+    pub fn in_use(&self) -> usize {
+        self.inner.num_busy.load(Ordering::Relaxed)
+    }
+
+    // Maximum number of buffers allocated at the same time so far
+    pub fn high_water_mark(&self) -> usize {
+        self.inner.max_busy.load(Ordering::Relaxed)
+    }
+
     // Safety:
     // - called only from Buffer<T>::drop
     fn free_buffer(&self, index: usize, _buffer: &mut T) {
+        self.inner.num_busy.fetch_sub(1, Ordering::Relaxed);
         self.inner.buffer_busy[index].store(false, Ordering::Release);
     }
 }
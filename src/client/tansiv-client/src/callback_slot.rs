@@ -0,0 +1,75 @@
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// Optional callback that application code can change at any time and that the deadline handler
+// calls
+//
+// The deadline handler only uses try_lock() to never wait for application code that it
+// interrupted. Calls happening while the callback is being changed are skipped.
+pub struct CallbackSlot<C> {
+    callback: Mutex<Option<C>>,
+    is_set: AtomicBool,
+}
+
+impl<C> CallbackSlot<C> {
+    pub fn new() -> CallbackSlot<C> {
+        CallbackSlot {
+            callback: Mutex::new(None),
+            is_set: AtomicBool::new(false),
+        }
+    }
+
+    pub fn set(&self, callback: Option<C>) {
+        let mut current = self.callback.lock().unwrap();
+        self.is_set.store(callback.is_some(), Ordering::Release);
+        *current = callback;
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.is_set.load(Ordering::Acquire)
+    }
+
+    // Returns false if the call was skipped because the callback was being changed
+    pub fn call<F>(&self, f: F) -> bool
+        where F: FnOnce(&C) {
+        match self.callback.try_lock() {
+            Ok(callback) => {
+                if let Some(callback) = callback.as_ref() {
+                    f(callback);
+                }
+                true
+            },
+            Err(_) => false,
+        }
+    }
+}
+
+impl<C> std::fmt::Debug for CallbackSlot<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "CallbackSlot {{ is_set: {} }}", self.is_set())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn set_call() {
+        let slot = CallbackSlot::<Box<dyn Fn(u32) -> u32>>::new();
+        let mut result = 0;
+
+        assert!(!slot.is_set());
+        assert!(slot.call(|cb| result = cb(1)));
+        assert_eq!(0, result);
+
+        slot.set(Some(Box::new(|x| x + 1)));
+        assert!(slot.is_set());
+        assert!(slot.call(|cb| result = cb(1)));
+        assert_eq!(2, result);
+
+        let _guard = slot.callback.lock().unwrap();
+        assert!(!slot.call(|cb| result = cb(2)));
+        assert_eq!(2, result);
+    }
+}
@@ -0,0 +1,244 @@
+use crate::waitfree_array_queue::WaitfreeArrayQueue;
+use log::debug;
+use nix::sys::eventfd::{eventfd, EfdFlags};
+use std::fmt;
+use std::io::{self, ErrorKind};
+use std::os::unix::io::RawFd;
+use std::sync::{Mutex, MutexGuard};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+const PENDING: u64 = std::u64::MAX;
+const ABORTED: u64 = std::u64::MAX - 1;
+
+#[derive(Debug)]
+pub enum Error {
+    SimulationEnded,
+    NoSlotAvailable,
+    IoError(io::Error),
+}
+
+type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::SimulationEnded => write!(f, "Simulation ended"),
+            Error::NoSlotAvailable => write!(f, "No slot available"),
+            Error::IoError(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+// Computation charged to the simulated host of the context
+//
+// time is the simulated time of submission. The simulator answers with an ExecuteCompletion
+// having the same request_id.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExecuteRequest {
+    pub request_id: u64,
+    pub time: Duration,
+    pub flops: f64,
+}
+
+// time is the simulated time at which the computation completed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExecuteCompletion {
+    pub request_id: u64,
+    pub time: Duration,
+}
+
+// Wakes up application code blocked in Context::execute(). notify() only calls write(), so the
+// deadline handler can call it from signal handler context. Notifications are counted, so none is
+// lost if notify() is called before wait().
+#[derive(Debug)]
+pub struct ExecuteEvent(RawFd);
+
+impl ExecuteEvent {
+    pub fn new() -> io::Result<ExecuteEvent> {
+        eventfd(0, EfdFlags::EFD_CLOEXEC)
+            .map(ExecuteEvent)
+            .map_err(|e| io::Error::new(ErrorKind::Other, e))
+    }
+
+    pub fn notify(&self) {
+        let one: u64 = 1;
+        // Safety: write() is async-signal-safe and only reads one. It cannot fail unless the
+        // counter overflows, which would still wake up the waiter.
+        unsafe { libc::write(self.0, &one as *const u64 as *const libc::c_void, std::mem::size_of::<u64>()) };
+    }
+
+    // Blocks until at least one notification is pending and consumes all pending notifications
+    pub fn wait(&self) -> io::Result<()> {
+        let mut count: u64 = 0;
+        loop {
+            // Safety: read() writes at most size_of::<u64>() bytes to count
+            let res = unsafe { libc::read(self.0, &mut count as *mut u64 as *mut libc::c_void, std::mem::size_of::<u64>()) };
+            if res >= 0 {
+                return Ok(());
+            }
+            let error = io::Error::last_os_error();
+            // The deadline handler may interrupt this thread
+            if error.kind() != ErrorKind::Interrupted {
+                return Err(error);
+            }
+        }
+    }
+}
+
+impl Drop for ExecuteEvent {
+    fn drop(&mut self) {
+        unsafe { libc::close(self.0) };
+    }
+}
+
+// Computation requested by application code, waiting for its completion
+//
+// Concurrency:
+// - lock serializes computations requested by application code,
+// - requests are popped from the queue by the deadline handler,
+// - done is written by the deadline handler, which then notifies event,
+// - application code waits for event and reads done.
+#[derive(Debug)]
+pub struct Executor {
+    lock: Mutex<()>,
+    requests: WaitfreeArrayQueue<ExecuteRequest>,
+    // Request id of the computation in progress, or 0
+    id: AtomicU64,
+    // Completion time in nanoseconds, or PENDING or ABORTED
+    done: AtomicU64,
+    event: ExecuteEvent,
+}
+
+impl Executor {
+    pub fn new() -> io::Result<Executor> {
+        Ok(Executor {
+            lock: Mutex::new(()),
+            requests: WaitfreeArrayQueue::new(1),
+            id: AtomicU64::new(0),
+            done: AtomicU64::new(PENDING),
+            event: ExecuteEvent::new()?,
+        })
+    }
+
+    // Serializes computations. The guard must be held from the checks preceding the request until
+    // ::run() returns.
+    pub fn lock(&self) -> MutexGuard<'_, ()> {
+        self.lock.lock().unwrap()
+    }
+
+    // Submits request and blocks until its completion, returning the simulated completion time.
+    // ended must load the termination state with SeqCst, which pairs with ::abort(), so that
+    // either the deadline handler sees the request and aborts it, or the end of the simulation is
+    // seen here. fast_forward is enabled while waiting.
+    pub fn run(&self, request: ExecuteRequest, ended: impl Fn() -> bool, fast_forward: impl Fn(bool)) -> Result<Duration> {
+        self.done.store(PENDING, Ordering::Relaxed);
+        self.id.store(request.request_id, Ordering::SeqCst);
+        if ended() {
+            self.id.store(0, Ordering::Release);
+            return Err(Error::SimulationEnded);
+        }
+        if self.requests.push(request).is_err() {
+            self.id.store(0, Ordering::Release);
+            return Err(Error::NoSlotAvailable);
+        }
+        debug!("new computation: time = {:?}, request_id = {}, flops = {}", request.time, request.request_id, request.flops);
+        fast_forward(true);
+
+        let done = loop {
+            let done = self.done.load(Ordering::Acquire);
+            if done != PENDING {
+                break done;
+            }
+            // Notifications left by a previous computation only cost one more iteration
+            if let Err(e) = self.event.wait() {
+                self.id.store(0, Ordering::Release);
+                fast_forward(false);
+                return Err(Error::IoError(e));
+            }
+        };
+        self.id.store(0, Ordering::Release);
+
+        if done == ABORTED {
+            Err(Error::SimulationEnded)
+        } else {
+            Ok(Duration::from_nanos(done))
+        }
+    }
+
+    // Request to send at the deadline, if any
+    pub fn pop_request(&self) -> Option<ExecuteRequest> {
+        self.requests.pop()
+    }
+
+    // Called by the deadline handler. resume is called before waking up the application. Returns
+    // false if completion is not the one of the computation in progress.
+    pub fn complete(&self, completion: &ExecuteCompletion, resume: impl FnOnce()) -> bool {
+        if completion.request_id != 0 && completion.request_id == self.id.load(Ordering::Acquire) {
+            resume();
+            self.done.store(completion.time.as_nanos() as u64, Ordering::Release);
+            self.event.notify();
+            true
+        } else {
+            false
+        }
+    }
+
+    // Called by the deadline handler once the simulation ended, after storing the termination
+    // state with SeqCst, so that the application is not left blocked in a computation
+    pub fn abort(&self, resume: impl FnOnce()) {
+        if self.id.load(Ordering::SeqCst) != 0 {
+            resume();
+            self.done.store(ABORTED, Ordering::Release);
+            self.event.notify();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use std::time::Duration;
+    use super::{Error, ExecuteCompletion, ExecuteEvent, ExecuteRequest, Executor};
+
+    #[test]
+    fn notify_before_wait() {
+        let event = ExecuteEvent::new().unwrap();
+        event.notify();
+        event.notify();
+        event.wait().unwrap();
+    }
+
+    #[test]
+    fn complete() {
+        let executor = Arc::new(Executor::new().unwrap());
+        let request = ExecuteRequest { request_id: 1, time: Duration::from_micros(10), flops: 1e6, };
+        let runner = {
+            let executor = Arc::clone(&executor);
+            std::thread::spawn(move || executor.run(request, || false, |_| ()))
+        };
+
+        let request = loop {
+            if let Some(request) = executor.pop_request() {
+                break request;
+            }
+            std::thread::yield_now();
+        };
+        assert!(!executor.complete(&ExecuteCompletion { request_id: 2, time: Duration::from_micros(20), }, || ()));
+        assert!(executor.complete(&ExecuteCompletion { request_id: request.request_id, time: Duration::from_micros(30), }, || ()));
+        assert_eq!(Duration::from_micros(30), runner.join().unwrap().unwrap());
+    }
+
+    #[test]
+    fn ended() {
+        let executor = Executor::new().unwrap();
+        let request = ExecuteRequest { request_id: 1, time: Duration::from_micros(10), flops: 1e6, };
+        match executor.run(request, || true, |_| panic!("Fast-forward while the simulation ended")) {
+            Err(Error::SimulationEnded) => (),
+            res => panic!("Unexpected result {:?}", res),
+        }
+        assert!(executor.pop_request().is_none());
+    }
+}
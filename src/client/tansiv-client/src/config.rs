@@ -1,6 +1,7 @@
 use chrono::naive::NaiveDateTime;
-use libc;
-use std::num::NonZeroUsize;
+use crate::fault::FaultRule;
+use crate::vsg_address::VsgAddress;
+use std::num::{NonZeroU64, NonZeroUsize};
 use std::str::FromStr;
 use structopt::StructOpt;
 
@@ -11,9 +12,14 @@ pub(crate) struct Config {
     #[structopt(short = "a", long = "actor", parse(from_os_str))]
     pub actor_socket: std::path::PathBuf,
 
-    /// Name (address) of this application in the network
+    /// Name (IPv4 or IPv6 address) of this application in the network
     #[structopt(short = "n", long = "name", parse(try_from_str = "crate::vsg_address::from_str"))]
-    pub address: libc::in_addr_t,
+    pub address: VsgAddress,
+
+    /// Additional addresses of this application, for instance for multiple network interfaces.
+    /// Can be repeated. Aliases are announced to the simulator at start.
+    #[structopt(long = "alias", parse(try_from_str = "crate::vsg_address::from_str"), raw(number_of_values = "1"))]
+    pub aliases: Vec<VsgAddress>,
 
     /// Initial time in the VM, formatted as %Y-%m-%dT%H:%M:%S%.f (%.f part is optional)
     #[structopt(short = "t", long = "initial_time", parse(try_from_str = "chrono::naive::NaiveDateTime::from_str"))]
@@ -22,6 +28,53 @@ pub(crate) struct Config {
     /// Number of packet buffers available for received packets, must not be 0
     #[structopt(short = "b", long = "num_buffers", default_value = "100")]
     pub num_buffers: NonZeroUsize,
+
+    /// Transparently split messages bigger than MAX_PACKET_SIZE in fragments and reassemble them
+    /// on reception. Must be enabled on all communicating nodes. A message may use at most a
+    /// quarter of num_buffers fragments.
+    #[structopt(long = "fragment")]
+    pub fragment: bool,
+
+    /// Time after which incomplete fragmented messages are dropped, in microseconds of simulated
+    /// time
+    #[structopt(long = "reassembly_timeout", default_value = "1000000")]
+    pub reassembly_timeout: u64,
+
+    /// Write a timeline of the deadlines, packets and callbacks of this application to the given
+    /// file, in Chrome trace-event JSON format as loaded by Perfetto
+    #[structopt(long = "trace", parse(from_os_str))]
+    pub trace: Option<std::path::PathBuf>,
+
+    /// Serve JSON status queries (status, stats, config), one per line, on a UNIX socket at the
+    /// given path
+    #[structopt(long = "control_socket", parse(from_os_str))]
+    pub control_socket: Option<std::path::PathBuf>,
+
+    /// Inject faults in sent and received packets, as
+    /// ACTION[:PROBABILITY][,dir=send|recv][,peer=ADDRESS][,from=US][,until=US][,by=US] with
+    /// ACTION among drop, duplicate, corrupt and delay (by US microseconds of simulated time).
+    /// Can be repeated.
+    #[structopt(long = "fault", raw(number_of_values = "1"))]
+    pub faults: Vec<FaultRule>,
+
+    /// Seed of the random draws of fault injection
+    #[structopt(long = "fault_seed", default_value = "0")]
+    pub fault_seed: u64,
+
+    /// Delay each sent packet by its serialization time on a NIC of the given rate, in bits per
+    /// second. Packets are sent at a later deadline if needed.
+    #[structopt(long = "nic_rate")]
+    pub nic_rate: Option<NonZeroU64>,
+
+    /// Bytes that the NIC can send back-to-back after an idle period without waiting for the
+    /// previous packets (token bucket size)
+    #[structopt(long = "nic_burst", default_value = "0")]
+    pub nic_burst: u64,
+
+    /// Stream sent packets to the actor as soon as they are time-stamped, from a helper thread,
+    /// instead of sending them all at the deadline
+    #[structopt(long = "stream")]
+    pub stream: bool,
 }
 
 #[cfg(test)]
@@ -38,9 +91,18 @@ mod test {
         let config = config.unwrap();
         assert_eq!("titi", config.actor_socket.to_str().unwrap());
         let vsg_addr = Into::<u32>::into(std::net::Ipv4Addr::new(10, 0, 0, 1)).to_be();
-        assert_eq!(vsg_addr, config.address);
+        assert_eq!(VsgAddress::V4(vsg_addr), config.address);
         assert_eq!(NaiveDateTime::from_timestamp(86400, 0), config.time_offset);
         assert_eq!(100, config.num_buffers.get());
+        assert!(!config.fragment);
+        assert!(config.aliases.is_empty());
+        assert!(config.trace.is_none());
+        assert!(config.control_socket.is_none());
+        assert!(config.faults.is_empty());
+        assert_eq!(0, config.fault_seed);
+        assert!(config.nic_rate.is_none());
+        assert_eq!(0, config.nic_burst);
+        assert!(!config.stream);
     }
 
     #[test]
@@ -52,7 +114,7 @@ mod test {
         let config = config.unwrap();
         assert_eq!("titi", config.actor_socket.to_str().unwrap());
         let vsg_addr = Into::<u32>::into(std::net::Ipv4Addr::new(10, 0, 0, 1)).to_be();
-        assert_eq!(vsg_addr, config.address);
+        assert_eq!(VsgAddress::V4(vsg_addr), config.address);
         assert_eq!(NaiveDateTime::from_timestamp(86400, 0), config.time_offset);
         assert_eq!(100, config.num_buffers.get());
     }
@@ -66,11 +128,100 @@ mod test {
         let config = config.unwrap();
         assert_eq!("titi", config.actor_socket.to_str().unwrap());
         let vsg_addr = Into::<u32>::into(std::net::Ipv4Addr::new(10, 0, 0, 1)).to_be();
-        assert_eq!(vsg_addr, config.address);
+        assert_eq!(VsgAddress::V4(vsg_addr), config.address);
         assert_eq!(NaiveDateTime::from_timestamp(86400, 0), config.time_offset);
         assert_eq!(1000, config.num_buffers.get());
     }
 
+    #[test]
+    // Correct args when enabling fragmentation
+    fn valid_args4() {
+        let config = Config::from_iter_safe(&["-atiti", "-n10.0.0.1", "-t1970-01-02T00:00:00", "--fragment", "--reassembly_timeout", "500"]);
+        assert!(config.is_ok());
+
+        let config = config.unwrap();
+        assert!(config.fragment);
+        assert_eq!(500, config.reassembly_timeout);
+    }
+
+    #[test]
+    // Correct args with an IPv6 address
+    fn valid_args5() {
+        let config = Config::from_iter_safe(&["-atiti", "-nfd00::1", "-t1970-01-02T00:00:00"]);
+        assert!(config.is_ok());
+
+        let config = config.unwrap();
+        let vsg_addr = std::net::Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 1).octets();
+        assert_eq!(VsgAddress::V6(vsg_addr), config.address);
+    }
+
+    #[test]
+    // Correct args with several addresses
+    fn valid_args6() {
+        let config = Config::from_iter_safe(&["-atiti", "-n10.0.0.1", "--alias", "10.0.1.1", "--alias", "fd00::1", "-t1970-01-02T00:00:00"]);
+        assert!(config.is_ok());
+
+        let config = config.unwrap();
+        let vsg_addr = Into::<u32>::into(std::net::Ipv4Addr::new(10, 0, 0, 1)).to_be();
+        assert_eq!(VsgAddress::V4(vsg_addr), config.address);
+        let alias4 = Into::<u32>::into(std::net::Ipv4Addr::new(10, 0, 1, 1)).to_be();
+        let alias6 = std::net::Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 1).octets();
+        assert_eq!(vec![VsgAddress::V4(alias4), VsgAddress::V6(alias6)], config.aliases);
+    }
+
+    #[test]
+    // Correct args when enabling tracing
+    fn valid_args7() {
+        let config = Config::from_iter_safe(&["-atiti", "-n10.0.0.1", "-t1970-01-02T00:00:00", "--trace", "trace.json"]);
+        assert!(config.is_ok());
+
+        let config = config.unwrap();
+        assert_eq!("trace.json", config.trace.unwrap().to_str().unwrap());
+    }
+
+    #[test]
+    // Correct args when enabling the control socket
+    fn valid_args8() {
+        let config = Config::from_iter_safe(&["-atiti", "-n10.0.0.1", "-t1970-01-02T00:00:00", "--control_socket", "titi-control"]);
+        assert!(config.is_ok());
+
+        let config = config.unwrap();
+        assert_eq!("titi-control", config.control_socket.unwrap().to_str().unwrap());
+    }
+
+    #[test]
+    // Correct args when injecting faults
+    fn valid_args9() {
+        let config = Config::from_iter_safe(&["-atiti", "-n10.0.0.1", "-t1970-01-02T00:00:00", "--fault", "drop:0.1,dir=send", "--fault", "delay,by=100", "--fault_seed", "42"]);
+        assert!(config.is_ok());
+
+        let config = config.unwrap();
+        assert_eq!(2, config.faults.len());
+        assert_eq!(crate::fault::FaultAction::Delay(std::time::Duration::from_micros(100)), config.faults[1].action);
+        assert_eq!(42, config.fault_seed);
+    }
+
+    #[test]
+    // Correct args when modeling the NIC
+    fn valid_args10() {
+        let config = Config::from_iter_safe(&["-atiti", "-n10.0.0.1", "-t1970-01-02T00:00:00", "--nic_rate", "1000000000", "--nic_burst", "3000"]);
+        assert!(config.is_ok());
+
+        let config = config.unwrap();
+        assert_eq!(1_000_000_000, config.nic_rate.unwrap().get());
+        assert_eq!(3000, config.nic_burst);
+    }
+
+    #[test]
+    // Correct args when streaming packets
+    fn valid_args11() {
+        let config = Config::from_iter_safe(&["-atiti", "-n10.0.0.1", "-t1970-01-02T00:00:00", "--stream"]);
+        assert!(config.is_ok());
+
+        let config = config.unwrap();
+        assert!(config.stream);
+    }
+
     #[test]
     // Missing socket value
     fn invalid_args1() {
@@ -113,6 +264,12 @@ mod test {
         assert!(Config::from_iter_safe(&["-atiti", "-n", "10.0.0.1.0", "-t1970-01-02T00:00"]).is_err());
     }
 
+    #[test]
+    // Invalid alias format
+    fn invalid_args10() {
+        assert!(Config::from_iter_safe(&["-atiti", "-n", "10.0.0.1", "--alias", "10.0.1", "-t1970-01-02T00:00:00"]).is_err());
+    }
+
     #[test]
     // Invalid time format
     fn invalid_args8() {
@@ -124,4 +281,16 @@ mod test {
     fn invalid_args9() {
         assert!(Config::from_iter_safe(&["-atiti", "-n", "10.0.0.1", "-t1970-01-02T00:00:00", "-b0"]).is_err());
     }
+
+    #[test]
+    // Invalid fault rule
+    fn invalid_args11() {
+        assert!(Config::from_iter_safe(&["-atiti", "-n", "10.0.0.1", "-t1970-01-02T00:00:00", "--fault", "drop:2"]).is_err());
+    }
+
+    #[test]
+    // NIC rate 0 is invalid
+    fn invalid_args12() {
+        assert!(Config::from_iter_safe(&["-atiti", "-n", "10.0.0.1", "-t1970-01-02T00:00:00", "--nic_rate", "0"]).is_err());
+    }
 }
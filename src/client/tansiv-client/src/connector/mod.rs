@@ -1,12 +1,20 @@
 use crate::buffer_pool::{Buffer, BufferPool};
 use crate::bytes_buffer::BytesBuffer;
+use crate::compute::{ExecuteCompletion, ExecuteRequest};
+use crate::disk::{DiskCompletion, DiskOp, DiskRequest};
 use crate::flatbuilder_buffer::*;
+use crate::groups::MembershipChange;
+use crate::marker::Marker;
+use crate::packet_attributes::PacketAttributes;
+use crate::report::{DeliveryReport, DropReason};
+use crate::vsg_address::VsgAddress;
 use flatbuffers::{FlatBufferBuilder, Vector, WIPOffset};
-use libc::in_addr_t;
-use std::convert::TryFrom;
+use std::convert::{TryFrom, TryInto};
 use std::cmp::Ordering;
 use std::fmt;
 use std::io::{Error, ErrorKind, Read, Result, Write};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU8, Ordering as AtomicOrdering};
 use std::time::Duration;
 
 pub(super) use unix::*;
@@ -21,6 +29,45 @@ pub(crate) trait Connector where Self: Sized {
     fn new(config: &super::Config) -> Result<Self>;
     fn recv(&mut self) -> Result<MsgIn>;
     fn send(&mut self, msg: MsgOut) -> Result<()>;
+    // Handle to the state of the connection, readable without locking the connector
+    fn connection_state(&self) -> ConnectionStateHandle;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    Connected,
+    // Lost for good
+    Disconnected,
+}
+
+impl ConnectionState {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ConnectionState::Connected => "connected",
+            ConnectionState::Disconnected => "disconnected",
+        }
+    }
+}
+
+// Written by the connector, read by anyone
+#[derive(Debug, Clone)]
+pub struct ConnectionStateHandle(Arc<AtomicU8>);
+
+impl ConnectionStateHandle {
+    pub fn new(state: ConnectionState) -> ConnectionStateHandle {
+        ConnectionStateHandle(Arc::new(AtomicU8::new(state as u8)))
+    }
+
+    pub fn get(&self) -> ConnectionState {
+        match self.0.load(AtomicOrdering::Acquire) {
+            0 => ConnectionState::Connected,
+            _ => ConnectionState::Disconnected,
+        }
+    }
+
+    pub fn set(&self, state: ConnectionState) {
+        self.0.store(state as u8, AtomicOrdering::Release)
+    }
 }
 
 // Crate-level interface
@@ -34,6 +81,37 @@ fn allocate_buffer(buffer_pool: &BufferPool<BytesBuffer>, size: usize) -> Result
 mod packets_generated;
 pub use packets_generated::*;
 
+// IPv4 source and destination use PacketMeta, any other combination uses PacketMeta6 with
+// IPv4-mapped addresses
+fn packet_meta(src: VsgAddress, dst: VsgAddress) -> (Option<tansiv::PacketMeta>, Option<tansiv::PacketMeta6>) {
+    match (src, dst) {
+        (VsgAddress::V4(src), VsgAddress::V4(dst)) => (Some(tansiv::PacketMeta::new(src, dst)), None),
+        (src, dst) => {
+            let (src_hi, src_lo) = src.ipv6_halves();
+            let (dst_hi, dst_lo) = dst.ipv6_halves();
+            let src = tansiv::Address6::new(src_hi, src_lo);
+            let dst = tansiv::Address6::new(dst_hi, dst_lo);
+            (None, Some(tansiv::PacketMeta6::new(&src, &dst)))
+        },
+    }
+}
+
+fn packet_addresses(metadata: Option<&tansiv::PacketMeta>, metadata6: Option<&tansiv::PacketMeta6>) -> Option<(VsgAddress, VsgAddress)> {
+    match (metadata, metadata6) {
+        (Some(m), _) => Some((VsgAddress::V4(m.src()), VsgAddress::V4(m.dst()))),
+        (None, Some(m)) => Some((VsgAddress::from_ipv6_halves(m.src().hi(), m.src().lo()),
+                                 VsgAddress::from_ipv6_halves(m.dst().hi(), m.dst().lo()))),
+        (None, None) => None,
+    }
+}
+
+fn parse_time(t: &tansiv::Time) -> Result<Duration> {
+    u32::try_from(t.nseconds()).ok()
+        .filter(|nsecs| *nsecs < 1_000_000_000)
+        .map(|nsecs| Duration::new(t.seconds(), nsecs))
+        .ok_or_else(|| Error::new(ErrorKind::InvalidData, "Time out of bounds"))
+}
+
 
 #[cfg(any(test, feature = "test-helpers"))]
 pub fn create_end_simulation(builder: &mut FlatBufferBuilder) -> () {
@@ -61,8 +139,80 @@ pub fn create_goto_deadline(builder: &mut FlatBufferBuilder, deadline: Duration)
     builder.finish_size_prefixed(msg, None);
 }
 
-pub fn create_at_deadline(builder: &mut FlatBufferBuilder) -> () {
-    let at_deadline = tansiv::AtDeadline::create(builder, &tansiv::AtDeadlineArgs{});
+#[cfg(any(test, feature = "test-helpers"))]
+pub fn create_delivery_report(builder: &mut FlatBufferBuilder, report: &DeliveryReport) {
+    let time = tansiv::Time::new(report.time.as_secs(), report.time.subsec_nanos() as u64);
+    let drop_reason = match report.drop_reason {
+        None => tansiv::DropReason::None,
+        Some(DropReason::Unknown) => tansiv::DropReason::Unknown,
+        Some(DropReason::Unreachable) => tansiv::DropReason::Unreachable,
+        Some(DropReason::Loss) => tansiv::DropReason::Loss,
+        Some(DropReason::QueueFull) => tansiv::DropReason::QueueFull,
+    };
+    let delivery_report = tansiv::DeliveryReport::create(builder, &tansiv::DeliveryReportArgs {
+        seq_id: report.seq_id,
+        time: Some(&time),
+        drop_reason,
+    });
+    let msg = tansiv::FromTansivMsg::create(builder, &tansiv::FromTansivMsgArgs{
+        content_type: tansiv::FromTansiv::DeliveryReport,
+        content: Some(delivery_report.as_union_value()),
+    });
+
+    builder.finish_size_prefixed(msg, None);
+}
+
+#[cfg(any(test, feature = "test-helpers"))]
+pub fn create_disk_completion(builder: &mut FlatBufferBuilder, completion: &DiskCompletion) {
+    let time = tansiv::Time::new(completion.time.as_secs(), completion.time.subsec_nanos() as u64);
+    let disk_completion = tansiv::DiskCompletion::create(builder, &tansiv::DiskCompletionArgs {
+        request_id: completion.request_id,
+        time: Some(&time),
+    });
+    let msg = tansiv::FromTansivMsg::create(builder, &tansiv::FromTansivMsgArgs{
+        content_type: tansiv::FromTansiv::DiskCompletion,
+        content: Some(disk_completion.as_union_value()),
+    });
+
+    builder.finish_size_prefixed(msg, None);
+}
+
+#[cfg(any(test, feature = "test-helpers"))]
+pub fn create_execute_completion(builder: &mut FlatBufferBuilder, completion: &ExecuteCompletion) {
+    let time = tansiv::Time::new(completion.time.as_secs(), completion.time.subsec_nanos() as u64);
+    let execute_completion = tansiv::ExecuteCompletion::create(builder, &tansiv::ExecuteCompletionArgs {
+        request_id: completion.request_id,
+        time: Some(&time),
+    });
+    let msg = tansiv::FromTansivMsg::create(builder, &tansiv::FromTansivMsgArgs{
+        content_type: tansiv::FromTansiv::ExecuteCompletion,
+        content: Some(execute_completion.as_union_value()),
+    });
+
+    builder.finish_size_prefixed(msg, None);
+}
+
+#[cfg(any(test, feature = "test-helpers"))]
+pub fn create_link_state(builder: &mut FlatBufferBuilder, address: VsgAddress, up: bool) {
+    let (hi, lo) = address.ipv6_halves();
+    let address = tansiv::Address6::new(hi, lo);
+    let link_state = tansiv::LinkState::create(builder, &tansiv::LinkStateArgs {
+        address: Some(&address),
+        up,
+    });
+    let msg = tansiv::FromTansivMsg::create(builder, &tansiv::FromTansivMsgArgs{
+        content_type: tansiv::FromTansiv::LinkState,
+        content: Some(link_state.as_union_value()),
+    });
+
+    builder.finish_size_prefixed(msg, None);
+}
+
+pub fn create_at_deadline(builder: &mut FlatBufferBuilder, idle_until: Option<Duration>) {
+    let idle_until = idle_until.map(|t| tansiv::Time::new(t.as_secs(), t.subsec_nanos() as u64));
+    let at_deadline = tansiv::AtDeadline::create(builder, &tansiv::AtDeadlineArgs{
+        idle_until: idle_until.as_ref(),
+    });
     let msg = tansiv::ToTansivMsg::create(builder, &tansiv::ToTansivMsgArgs{
         content_type: tansiv::ToTansiv::AtDeadline,
         content: Some(at_deadline.as_union_value()),
@@ -71,16 +221,45 @@ pub fn create_at_deadline(builder: &mut FlatBufferBuilder) -> () {
     builder.finish_size_prefixed(msg, None);
 }
 
+fn flow_meta(attributes: &PacketAttributes) -> Option<tansiv::FlowMeta> {
+    if !attributes.has_flow() {
+        None
+    } else {
+        Some(tansiv::FlowMeta::new(attributes.flow_id, attributes.src_port, attributes.dst_port, attributes.protocol, attributes.priority))
+    }
+}
+
+fn packet_attributes(flow: Option<&tansiv::FlowMeta>, wire_size: u32) -> PacketAttributes {
+    match flow {
+        Some(flow) => PacketAttributes {
+            protocol: flow.protocol(),
+            src_port: flow.src_port(),
+            dst_port: flow.dst_port(),
+            priority: flow.priority(),
+            flow_id: flow.flow_id(),
+            wire_size,
+        },
+        None => PacketAttributes {
+            wire_size,
+            ..Default::default()
+        },
+    }
+}
+
 #[cfg(any(test, feature = "test-helpers"))]
-fn prepare_deliver_packet<'a, 'b, 'c>(builder: &'a mut FlatBufferBuilder<'c>, src: u32, dst: u32, payload: &'b [u8]) -> (&'a mut FlatBufferBuilder<'c>, WIPOffset<tansiv::FromTansivMsg<'c>>) {
-    let fb_packet_meta = tansiv::PacketMeta::new(src, dst);
+fn prepare_deliver_packet<'a, 'c>(builder: &'a mut FlatBufferBuilder<'c>, src: VsgAddress, dst: VsgAddress, attributes: &PacketAttributes, payload: &[u8]) -> (&'a mut FlatBufferBuilder<'c>, WIPOffset<tansiv::FromTansivMsg<'c>>) {
+    let (fb_packet_meta, fb_packet_meta6) = packet_meta(src, dst);
+    let fb_flow_meta = flow_meta(attributes);
     let fb_payload = builder.create_vector(payload);
 
     let deliver_packet = tansiv::DeliverPacket::create(
         builder,
         &tansiv::DeliverPacketArgs {
-            metadata: Some(&fb_packet_meta),
+            metadata: fb_packet_meta.as_ref(),
             payload: Some(fb_payload),
+            metadata6: fb_packet_meta6.as_ref(),
+            flow: fb_flow_meta.as_ref(),
+            wire_size: attributes.wire_size,
     });
     let msg = tansiv::FromTansivMsg::create(builder, &tansiv::FromTansivMsgArgs{
         content_type: tansiv::FromTansiv::DeliverPacket,
@@ -92,15 +271,14 @@ fn prepare_deliver_packet<'a, 'b, 'c>(builder: &'a mut FlatBufferBuilder<'c>, sr
 }
 
 #[cfg(any(test, feature = "test-helpers"))]
-pub fn create_deliver_packet(builder: &mut FlatBufferBuilder, src: u32, dst: u32, payload: &[u8]) {
-    let (builder, msg) = prepare_deliver_packet(builder, src, dst, payload);
-    builder.finish_size_prefixed(msg, None);
+pub fn create_deliver_packet(builder: &mut FlatBufferBuilder, src: impl Into<VsgAddress>, dst: impl Into<VsgAddress>, payload: &[u8]) {
+    create_deliver_packet_with_attributes(builder, src, dst, &PacketAttributes::default(), payload)
 }
 
 #[cfg(any(test, feature = "test-helpers"))]
-pub fn create_deliver_packet_unprefixed(builder: &mut FlatBufferBuilder, src: u32, dst: u32, payload: &[u8]) {
-    let (builder, msg) = prepare_deliver_packet(builder, src, dst, payload);
-    builder.finish(msg, None);
+pub fn create_deliver_packet_with_attributes(builder: &mut FlatBufferBuilder, src: impl Into<VsgAddress>, dst: impl Into<VsgAddress>, attributes: &PacketAttributes, payload: &[u8]) {
+    let (builder, msg) = prepare_deliver_packet(builder, src.into(), dst.into(), attributes, payload);
+    builder.finish_size_prefixed(msg, None);
 }
 
 pub fn create_send_packet_from_payload(builder: &mut FlatBufferBuilder, send_time: Duration, src: u32, dst: u32, payload: &[u8]) -> () {
@@ -112,6 +290,7 @@ pub fn create_send_packet_from_payload(builder: &mut FlatBufferBuilder, send_tim
         time: Some(&time),
         // FIXME(msimonin) unwrap
         payload: Some(fb_payload),
+        ..Default::default()
     });
     let msg = tansiv::ToTansivMsg::create(builder, &tansiv::ToTansivMsgArgs{
         content_type: tansiv::ToTansiv::SendPacket,
@@ -122,6 +301,100 @@ pub fn create_send_packet_from_payload(builder: &mut FlatBufferBuilder, send_tim
     builder.finish_size_prefixed(msg, None);
 }
 
+pub fn create_group_membership(builder: &mut FlatBufferBuilder, change: MembershipChange) {
+    let (group, join) = match change {
+        MembershipChange::Join(group) => (group, true),
+        MembershipChange::Leave(group) => (group, false),
+    };
+    let (hi, lo) = group.ipv6_halves();
+    let group = tansiv::Address6::new(hi, lo);
+    let group_membership = tansiv::GroupMembership::create(builder, &tansiv::GroupMembershipArgs {
+        group: Some(&group),
+        join,
+    });
+    let msg = tansiv::ToTansivMsg::create(builder, &tansiv::ToTansivMsgArgs{
+        content_type: tansiv::ToTansiv::GroupMembership,
+        content: Some(group_membership.as_union_value()),
+    });
+    builder.finish_size_prefixed(msg, None);
+}
+
+pub fn create_disk_request(builder: &mut FlatBufferBuilder, request: &DiskRequest) {
+    let time = tansiv::Time::new(request.time.as_secs(), request.time.subsec_nanos() as u64);
+    let op = match request.op {
+        DiskOp::Read => tansiv::DiskOp::Read,
+        DiskOp::Write => tansiv::DiskOp::Write,
+    };
+    let disk = builder.create_string(request.disk());
+    let disk_request = tansiv::DiskRequest::create(builder, &tansiv::DiskRequestArgs {
+        request_id: request.request_id,
+        time: Some(&time),
+        op,
+        length: request.size,
+        disk: Some(disk),
+    });
+    let msg = tansiv::ToTansivMsg::create(builder, &tansiv::ToTansivMsgArgs{
+        content_type: tansiv::ToTansiv::DiskRequest,
+        content: Some(disk_request.as_union_value()),
+    });
+    builder.finish_size_prefixed(msg, None);
+}
+
+pub fn create_execute(builder: &mut FlatBufferBuilder, request: &ExecuteRequest) {
+    let time = tansiv::Time::new(request.time.as_secs(), request.time.subsec_nanos() as u64);
+    let execute = tansiv::Execute::create(builder, &tansiv::ExecuteArgs {
+        request_id: request.request_id,
+        time: Some(&time),
+        flops: request.flops,
+    });
+    let msg = tansiv::ToTansivMsg::create(builder, &tansiv::ToTansivMsgArgs{
+        content_type: tansiv::ToTansiv::Execute,
+        content: Some(execute.as_union_value()),
+    });
+    builder.finish_size_prefixed(msg, None);
+}
+
+pub fn create_marker(builder: &mut FlatBufferBuilder, marker: &Marker) {
+    let time = tansiv::Time::new(marker.time.as_secs(), marker.time.subsec_nanos() as u64);
+    let label = builder.create_string(marker.label());
+    let marker = tansiv::Marker::create(builder, &tansiv::MarkerArgs {
+        time: Some(&time),
+        label: Some(label),
+        value: marker.value,
+    });
+    let msg = tansiv::ToTansivMsg::create(builder, &tansiv::ToTansivMsgArgs{
+        content_type: tansiv::ToTansiv::Marker,
+        content: Some(marker.as_union_value()),
+    });
+    builder.finish_size_prefixed(msg, None);
+}
+
+pub fn create_addresses(builder: &mut FlatBufferBuilder, addresses: &[VsgAddress]) {
+    let addresses: Vec<tansiv::Address6> = addresses.iter().map(|address| {
+        let (hi, lo) = address.ipv6_halves();
+        tansiv::Address6::new(hi, lo)
+    }).collect();
+    let addresses = builder.create_vector(&addresses);
+    let addresses = tansiv::Addresses::create(builder, &tansiv::AddressesArgs {
+        addresses: Some(addresses),
+    });
+    let msg = tansiv::ToTansivMsg::create(builder, &tansiv::ToTansivMsgArgs{
+        content_type: tansiv::ToTansiv::Addresses,
+        content: Some(addresses.as_union_value()),
+    });
+    builder.finish_size_prefixed(msg, None);
+}
+
+fn delivery(dst: VsgAddress) -> tansiv::Delivery {
+    if dst.is_broadcast() {
+        tansiv::Delivery::Broadcast
+    } else if dst.is_multicast() {
+        tansiv::Delivery::Multicast
+    } else {
+        tansiv::Delivery::Unicast
+    }
+}
+
 // Read the actual size of a flatbuffer message
 // allocate a scratch buffer on the stack so that
 // it's usable from a signal handler
@@ -133,6 +406,21 @@ pub fn read_prefixed_size(reader: &mut impl Read) -> Result<usize> {
     }
 }
 
+// Size of the buffers receiving messages. Messages are kept with their size prefix: the builder
+// aligns the prefixed message, so 8-byte fields of a message stripped from its prefix look
+// misaligned to the verifier.
+pub const INPUT_BUFFER_SIZE: usize = crate::MAX_PACKET_SIZE + flatbuffers::SIZE_SIZEPREFIX;
+
+// Reads a size-prefixed message in a buffer of buffer_pool, prefix included
+fn recv_prefixed(reader: &mut impl Read, buffer_pool: &BufferPool<BytesBuffer>) -> Result<Buffer<BytesBuffer>> {
+    let size = read_prefixed_size(reader)?;
+
+    let mut buffer = allocate_buffer(buffer_pool, flatbuffers::SIZE_SIZEPREFIX + size)?;
+    buffer[..flatbuffers::SIZE_SIZEPREFIX].copy_from_slice(&(size as u32).to_le_bytes());
+    reader.read_exact(&mut buffer[flatbuffers::SIZE_SIZEPREFIX..])?;
+    Ok(buffer)
+}
+
 #[derive(Debug)]
 pub struct MsgFbInitializer;
 
@@ -148,7 +436,7 @@ impl FbBuilderInitializer for MsgFbInitializer {
         create_send_packet_from_payload(&mut builder, send_time, src, dst, &payload);
         builder.reset();
 
-        create_at_deadline(&mut builder);
+        create_at_deadline(&mut builder, Some(send_time));
         builder.reset();
 
         builder
@@ -181,14 +469,22 @@ pub struct DeliverPacket {
 }
 
 impl DeliverPacket {
-    pub fn src(&self) -> u32 {
+    pub fn src(&self) -> VsgAddress {
+        self.addresses().0
+    }
+
+    pub fn dst(&self) -> VsgAddress {
+        self.addresses().1
+    }
+
+    fn addresses(&self) -> (VsgAddress, VsgAddress) {
         let msg = self.deserialize();
-        msg.metadata().unwrap().src()
+        packet_addresses(msg.metadata(), msg.metadata6()).unwrap()
     }
 
-    pub fn dst(&self) -> u32 {
+    pub fn attributes(&self) -> PacketAttributes {
         let msg = self.deserialize();
-        msg.metadata().unwrap().dst()
+        packet_attributes(msg.flow(), msg.wire_size())
     }
 
     pub fn payload(&self) -> &[u8] {
@@ -200,7 +496,7 @@ impl DeliverPacket {
         // - can we assume that it has been verified prior to this ?
         // (connector.recv is doing the check)
         // if yes we can cool root_unchecked
-        let msg = flatbuffers::root::<tansiv::FromTansivMsg>(&self.inner).unwrap();
+        let msg = flatbuffers::size_prefixed_root::<tansiv::FromTansivMsg>(&self.inner).unwrap();
         msg.content_as_deliver_packet().unwrap()
     }
 }
@@ -208,17 +504,23 @@ impl DeliverPacket {
 #[derive(Debug)]
 pub enum MsgIn {
     DeliverPacket(DeliverPacket),
+    DeliveryReport(DeliveryReport),
+    // Address whose link changed, and whether the link is up
+    LinkState(VsgAddress, bool),
+    DiskCompletion(DiskCompletion),
+    ExecuteCompletion(ExecuteCompletion),
     GoToDeadline(Duration),
     EndSimulation,
 }
 
 impl MsgIn {
+    // buffer holds a size-prefixed message
     pub fn new_deliver_packet(buffer: Buffer<BytesBuffer>) -> Result<MsgIn> {
         // checking that we're dealing with the right message
-        let msg = flatbuffers::root::<tansiv::FromTansivMsg>(&buffer).unwrap();
-        let msg = msg.content_as_deliver_packet().ok_or(new_format_error())?;
+        let msg = flatbuffers::size_prefixed_root::<tansiv::FromTansivMsg>(&buffer).unwrap();
+        let msg = msg.content_as_deliver_packet().ok_or_else(new_format_error)?;
         // we don't trust fbb, so we check all the field
-        if msg.metadata().and(msg.payload()).is_none() {
+        if packet_addresses(msg.metadata(), msg.metadata6()).and(msg.payload()).is_none() {
             return Err(new_format_error());
         }
 
@@ -232,11 +534,10 @@ impl MsgIn {
 
 impl fmt::Display for MsgIn {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        use crate::vsg_address::to_ipv4addr;
         match self {
             // FIXME(msimonin) // real payload
             MsgIn::DeliverPacket(d) => {
-                write!(f, "DeliverPacket(src = {}, dst = {}, len = {}", to_ipv4addr(d.src()), to_ipv4addr(d.dst()), d.payload().len())
+                write!(f, "DeliverPacket(src = {}, dst = {}, len = {}", d.src(), d.dst(), d.payload().len())
             },
             _ => fmt::Debug::fmt(self, f),
         }
@@ -245,33 +546,58 @@ impl fmt::Display for MsgIn {
 
 impl MsgIn {
     fn recv<'a, 'b>(reader: &mut impl Read, buffer_pool: &'b BufferPool<BytesBuffer>) -> Result<MsgIn> {
-        let size = read_prefixed_size(reader)?;
+        let buffer = recv_prefixed(reader, buffer_pool)?;
 
-        let mut  buffer = allocate_buffer(buffer_pool, size)?;
-        reader.read_exact(&mut buffer)?;
-
-        let msg = flatbuffers::root::<tansiv::FromTansivMsg>(&buffer)
+        let msg = flatbuffers::size_prefixed_root::<tansiv::FromTansivMsg>(&buffer)
             .map_err(|_| {new_format_error()})?;
         match msg.content_type() {
             tansiv::FromTansiv::DeliverPacket => MsgIn::new_deliver_packet(buffer),
             tansiv::FromTansiv::GotoDeadline => {
-                let deadline = msg.content_as_goto_deadline().ok_or(new_format_error())?;
-                let time = deadline.time().ok_or(new_format_error())?;
-                if let (Ok(seconds), Ok(nseconds)) = (
-                    u64::try_from(time.seconds()),
-                    u32::try_from(time.nseconds()).map_err(|_| ()).and_then(|nsecs|
-                                                        if nsecs < 1_000_000_000 {
-                                                            Ok(nsecs)
-                                                        } else {
-                                                            Err(())
-                                                        })
-                ) {
-                    Ok(MsgIn::GoToDeadline(Duration::new(seconds, nseconds)))
-                } else {
-                    Err(Error::new(ErrorKind::InvalidData, "Time out of bounds"))
-                }
+                let deadline = msg.content_as_goto_deadline().ok_or_else(new_format_error)?;
+                let time = deadline.time().ok_or_else(new_format_error)?;
+                Ok(MsgIn::GoToDeadline(parse_time(time)?))
             },
             tansiv::FromTansiv::EndSimulation => Ok(MsgIn::EndSimulation),
+            tansiv::FromTansiv::DeliveryReport => {
+                let report = msg.content_as_delivery_report().ok_or_else(new_format_error)?;
+                let time = report.time().ok_or_else(new_format_error)?;
+                let time = parse_time(time)?;
+                let drop_reason = match report.drop_reason() {
+                    tansiv::DropReason::None => None,
+                    tansiv::DropReason::Unreachable => Some(DropReason::Unreachable),
+                    tansiv::DropReason::Loss => Some(DropReason::Loss),
+                    tansiv::DropReason::QueueFull => Some(DropReason::QueueFull),
+                    _ => Some(DropReason::Unknown),
+                };
+                Ok(MsgIn::DeliveryReport(DeliveryReport {
+                    seq_id: report.seq_id(),
+                    time,
+                    drop_reason,
+                }))
+            },
+            tansiv::FromTansiv::LinkState => {
+                let link_state = msg.content_as_link_state().ok_or_else(new_format_error)?;
+                let address = link_state.address().ok_or_else(new_format_error)?;
+                Ok(MsgIn::LinkState(VsgAddress::from_ipv6_halves(address.hi(), address.lo()), link_state.up()))
+            },
+            tansiv::FromTansiv::DiskCompletion => {
+                let completion = msg.content_as_disk_completion().ok_or_else(new_format_error)?;
+                let time = completion.time().ok_or_else(new_format_error)?;
+                let time = parse_time(time)?;
+                Ok(MsgIn::DiskCompletion(DiskCompletion {
+                    request_id: completion.request_id(),
+                    time,
+                }))
+            },
+            tansiv::FromTansiv::ExecuteCompletion => {
+                let completion = msg.content_as_execute_completion().ok_or_else(new_format_error)?;
+                let time = completion.time().ok_or_else(new_format_error)?;
+                let time = parse_time(time)?;
+                Ok(MsgIn::ExecuteCompletion(ExecuteCompletion {
+                    request_id: completion.request_id(),
+                    time,
+                }))
+            },
             _ => Err(Error::new(ErrorKind::InvalidData, "Message content is missing")),
         }
     }
@@ -279,9 +605,6 @@ impl MsgIn {
     #[cfg(any(test, feature = "test-helpers"))]
     fn send<'b>(self, writer: &mut impl Write, fb_buffer_pool: &BufferPool<FbBuffer>) -> Result<()> {
         let mut fb_buffer = fb_buffer_pool.allocate_buffer(1).unwrap();
-        // at this point d is unprefixed !
-        // intial implem:
-        // - we deserialize the buffer to reserialize it with a size prefixed
         match self {
             MsgIn::GoToDeadline(deadline) => {
                 create_goto_deadline(&mut fb_buffer, deadline);
@@ -291,32 +614,51 @@ impl MsgIn {
                 create_end_simulation(&mut fb_buffer);
                 writer.write_all(fb_buffer.finished_data())
             }
-            MsgIn::DeliverPacket(d) => {
-                // NOTE(msimonin): the byte buffer here correspond to an unprefixed deliverPacket on the wire
-                // the intend here is to craft a prefixed message and send it over the wire
-                create_deliver_packet(&mut fb_buffer, d.src(), d.dst(), d.payload());
+            MsgIn::DeliveryReport(report) => {
+                create_delivery_report(&mut fb_buffer, &report);
+                writer.write_all(fb_buffer.finished_data())
+            }
+            MsgIn::LinkState(address, up) => {
+                create_link_state(&mut fb_buffer, address, up);
+                writer.write_all(fb_buffer.finished_data())
+            }
+            MsgIn::DiskCompletion(completion) => {
+                create_disk_completion(&mut fb_buffer, &completion);
                 writer.write_all(fb_buffer.finished_data())
             }
+            MsgIn::ExecuteCompletion(completion) => {
+                create_execute_completion(&mut fb_buffer, &completion);
+                writer.write_all(fb_buffer.finished_data())
+            }
+            MsgIn::DeliverPacket(d) => {
+                // the byte buffer here is the prefixed DeliverPacket as on the wire
+                writer.write_all(&d.inner)
+            }
         }
     }
 }
 
 // Use for representing a partially built buffer
 pub struct SendPacketBuilder {
-    src: in_addr_t,
-    dst: in_addr_t,
+    src: VsgAddress,
+    dst: VsgAddress,
+    attributes: PacketAttributes,
+    // 0 if no delivery report is requested
+    seq_id: u64,
     send_time: Duration,
     payload: Buffer<FbBuffer>,
     payload_offset: WIPOffset<Vector<'static, u8>>,
 }
 
 impl SendPacketBuilder {
-    pub fn new(src: in_addr_t, dst: in_addr_t, send_time: Duration, payload: &[u8], mut buffer: Buffer<FbBuffer>) -> Result<SendPacketBuilder> {
+    pub fn new(src: VsgAddress, dst: VsgAddress, attributes: PacketAttributes, seq_id: u64, send_time: Duration, payload: &[u8], mut buffer: Buffer<FbBuffer>) -> Result<SendPacketBuilder> {
 
         let payload_offset = buffer.create_vector(payload);
         Ok(SendPacketBuilder {
             src,
             dst,
+            attributes,
+            seq_id,
             send_time,
             payload: buffer,
             payload_offset,
@@ -325,12 +667,18 @@ impl SendPacketBuilder {
 
     pub fn finish(self, send_time: Duration) -> SendPacket {
         let time = tansiv::Time::new(send_time.as_secs(), send_time.subsec_nanos() as u64);
-        let packet_meta = tansiv::PacketMeta::new(self.src, self.dst);
+        let (packet_meta, packet_meta6) = packet_meta(self.src, self.dst);
+        let flow_meta = flow_meta(&self.attributes);
         let mut p = self.payload;
         let send_packet = tansiv::SendPacket::create(&mut p, &tansiv::SendPacketArgs {
-            metadata: Some(&packet_meta),
+            metadata: packet_meta.as_ref(),
             time: Some(&time),
             payload: Some(self.payload_offset),
+            metadata6: packet_meta6.as_ref(),
+            delivery: delivery(self.dst),
+            flow: flow_meta.as_ref(),
+            seq_id: self.seq_id,
+            wire_size: self.attributes.wire_size,
         });
         let msg = tansiv::ToTansivMsg::create(&mut p, &tansiv::ToTansivMsgArgs{
             content_type: tansiv::ToTansiv::SendPacket,
@@ -343,16 +691,36 @@ impl SendPacketBuilder {
         }
     }
 
-    pub fn src(&self) -> in_addr_t {
+    pub fn src(&self) -> VsgAddress {
         self.src
     }
-    pub fn dst(&self) -> in_addr_t{
+    pub fn dst(&self) -> VsgAddress {
         self.dst
     }
 
+    pub fn attributes(&self) -> PacketAttributes {
+        self.attributes
+    }
+
+    pub fn seq_id(&self) -> u64 {
+        self.seq_id
+    }
+
     pub fn send_time(&self) -> Duration {
         self.send_time
     }
+
+    pub fn set_send_time(&mut self, send_time: Duration) {
+        self.send_time = send_time;
+    }
+
+    pub fn payload(&self) -> &[u8] {
+        // The payload vector is the only object built so far, at payload_offset from the end
+        let data = self.payload.unfinished_data();
+        let start = data.len() - self.payload_offset.value() as usize;
+        let len = u32::from_le_bytes(data[start..(start + 4)].try_into().unwrap()) as usize;
+        &data[(start + 4)..(start + 4 + len)]
+    }
 }
 
 impl Ord for SendPacketBuilder {
@@ -391,59 +759,123 @@ impl std::ops::Deref for SendPacket {
 
 #[derive(Debug)]
 pub enum MsgOut {
-    AtDeadline,
+    // Optional idle hint, see ::at_deadline()
+    AtDeadline(Option<Duration>),
     SendPacket(SendPacket),
+    GroupMembership(MembershipChange),
+    DiskRequest(DiskRequest),
+    Execute(ExecuteRequest),
+    Marker(Marker),
+    // All the addresses of the context, sent once at start
+    Addresses(Vec<VsgAddress>),
 }
 
 impl MsgOut {
     fn send(mut self, writer: &mut impl Write, scratch_builder: &mut FlatBufferBuilder<'static>) -> Result<()> {
         let fbb = match self {
-            MsgOut::AtDeadline => {
-                create_at_deadline(scratch_builder);
+            MsgOut::AtDeadline(idle_until) => {
+                create_at_deadline(scratch_builder, idle_until);
                 scratch_builder
             },
             MsgOut::SendPacket(SendPacket { ref mut inner }) => inner,
+            MsgOut::GroupMembership(change) => {
+                create_group_membership(scratch_builder, change);
+                scratch_builder
+            },
+            MsgOut::DiskRequest(request) => {
+                create_disk_request(scratch_builder, &request);
+                scratch_builder
+            },
+            MsgOut::Execute(request) => {
+                create_execute(scratch_builder, &request);
+                scratch_builder
+            },
+            MsgOut::Marker(marker) => {
+                create_marker(scratch_builder, &marker);
+                scratch_builder
+            },
+            MsgOut::Addresses(addresses) => {
+                create_addresses(scratch_builder, &addresses);
+                scratch_builder
+            },
         };
         writer.write_all(fbb.finished_data())
     }
 
     #[cfg(any(test, feature = "test-helpers"))]
     fn recv<'a, 'b>(reader: &mut impl Read, buffer_pool: &'b BufferPool<BytesBuffer>, fb_buffer_pool: &BufferPool<FbBuffer>) -> Result<MsgOut> {
-        let size = read_prefixed_size(reader)?;
-
-        let mut buffer = allocate_buffer(buffer_pool, size)?;
-        reader.read_exact(&mut buffer)?;
-        let msg = flatbuffers::root::<tansiv::ToTansivMsg>(&buffer).unwrap();
+        let buffer = recv_prefixed(reader, buffer_pool)?;
+        let msg = flatbuffers::size_prefixed_root::<tansiv::ToTansivMsg>(&buffer).unwrap();
 
         let fb_buffer = fb_buffer_pool.allocate_buffer(0).unwrap();
         match msg.content_type() {
-            tansiv::ToTansiv::AtDeadline =>  Ok(MsgOut::AtDeadline),
+            tansiv::ToTansiv::AtDeadline => {
+                let at_deadline = msg.content_as_at_deadline().ok_or(new_format_error())?;
+                let idle_until = at_deadline.idle_until().map(parse_time).transpose()?;
+                Ok(MsgOut::AtDeadline(idle_until))
+            },
             tansiv::ToTansiv::SendPacket => {
                 let send_packet = msg.content_as_send_packet().ok_or(new_format_error())?;
                 let time = send_packet.time().ok_or(new_format_error())?;
-                let metadata = send_packet.metadata().ok_or(new_format_error())?;
-                if let (Ok(seconds), Ok(nseconds)) = (
-                    u64::try_from(time.seconds()),
-                    u32::try_from(time.nseconds()).map_err(|_| ()).and_then(|nsecs|
-                                                        if nsecs < 1_000_000_000 {
-                                                            Ok(nsecs)
-                                                        } else {
-                                                            Err(())
-                                                        })
-                ){
-                    let send_time = Duration::new(seconds, nseconds);
-                    let send_packet_builder = SendPacketBuilder::new(
-                        metadata.src(),
-                        metadata.dst(),
-                        send_time,
-                        send_packet.payload().unwrap(),
-                        fb_buffer,
-                    )?;
-                    Ok(MsgOut::SendPacket(send_packet_builder.finish(send_time)))
+                let (src, dst) = packet_addresses(send_packet.metadata(), send_packet.metadata6()).ok_or(new_format_error())?;
+                let send_time = parse_time(time)?;
+                let send_packet_builder = SendPacketBuilder::new(
+                    src,
+                    dst,
+                    packet_attributes(send_packet.flow(), send_packet.wire_size()),
+                    send_packet.seq_id(),
+                    send_time,
+                    send_packet.payload().unwrap(),
+                    fb_buffer,
+                )?;
+                Ok(MsgOut::SendPacket(send_packet_builder.finish(send_time)))
+            },
+            tansiv::ToTansiv::GroupMembership => {
+                let group_membership = msg.content_as_group_membership().ok_or(new_format_error())?;
+                let group = group_membership.group().ok_or(new_format_error())?;
+                let group = VsgAddress::from_ipv6_halves(group.hi(), group.lo());
+                if group_membership.join() {
+                    Ok(MsgOut::GroupMembership(MembershipChange::Join(group)))
                 } else {
-                    Err(Error::new(ErrorKind::InvalidData, "Time out of bounds"))
+                    Ok(MsgOut::GroupMembership(MembershipChange::Leave(group)))
                 }
             },
+            tansiv::ToTansiv::DiskRequest => {
+                let disk_request = msg.content_as_disk_request().ok_or(new_format_error())?;
+                let time = disk_request.time().ok_or(new_format_error())?;
+                let time = parse_time(time)?;
+                let op = match disk_request.op() {
+                    tansiv::DiskOp::Write => DiskOp::Write,
+                    _ => DiskOp::Read,
+                };
+                let disk = disk_request.disk().ok_or(new_format_error())?;
+                let request = DiskRequest::new(disk_request.request_id(), time, op, disk_request.length(), disk)
+                    .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+                Ok(MsgOut::DiskRequest(request))
+            },
+            tansiv::ToTansiv::Execute => {
+                let execute = msg.content_as_execute().ok_or(new_format_error())?;
+                let time = execute.time().ok_or(new_format_error())?;
+                Ok(MsgOut::Execute(ExecuteRequest {
+                    request_id: execute.request_id(),
+                    time: parse_time(time)?,
+                    flops: execute.flops(),
+                }))
+            },
+            tansiv::ToTansiv::Marker => {
+                let marker = msg.content_as_marker().ok_or(new_format_error())?;
+                let time = marker.time().ok_or(new_format_error())?;
+                let time = parse_time(time)?;
+                let label = marker.label().ok_or(new_format_error())?;
+                let marker = Marker::new(time, label, marker.value())
+                    .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+                Ok(MsgOut::Marker(marker))
+            },
+            tansiv::ToTansiv::Addresses => {
+                let addresses = msg.content_as_addresses().ok_or(new_format_error())?;
+                let addresses = addresses.addresses().ok_or(new_format_error())?;
+                Ok(MsgOut::Addresses(addresses.iter().map(|a| VsgAddress::from_ipv6_halves(a.hi(), a.lo())).collect()))
+            },
             _ => Err(Error::new(ErrorKind::InvalidData, "Message content is missing")),
         }
     }
@@ -506,7 +938,7 @@ mod test {
             // at deadline
             assert_no_alloc!(A, {
                 let mut fb: Buffer<FbBuffer> = fb_pool.allocate_buffer(0).unwrap();
-                create_at_deadline(&mut fb);
+                create_at_deadline(&mut fb, None);
             });
         }
 
@@ -523,7 +955,7 @@ mod test {
             // send packet
             assert_no_alloc!(A, {
                 let fb: Buffer<FbBuffer> = fb_pool.allocate_buffer(0).unwrap();
-                let send_packet_builder = SendPacketBuilder::new(1u32, 1u32, d, payload, fb).unwrap();
+                let send_packet_builder = SendPacketBuilder::new(1u32.into(), 1u32.into(), PacketAttributes::default(), 0, d, payload, fb).unwrap();
                 send_packet_builder.finish(d);
             });
         }
@@ -532,7 +964,7 @@ mod test {
             // send packet
             assert_no_alloc!(A, {
                 let fb: Buffer<FbBuffer> = fb_pool.allocate_buffer(0).unwrap();
-                let send_packet_builder = SendPacketBuilder::new(0u32, 1u32, d, payload, fb).unwrap();
+                let send_packet_builder = SendPacketBuilder::new(0u32.into(), 1u32.into(), PacketAttributes::default(), 0, d, payload, fb).unwrap();
                 let _ = MsgOut::SendPacket(send_packet_builder.finish(d));
             });
         }
@@ -548,6 +980,38 @@ mod test {
 
     }
 
+    #[test]
+    fn deliver_packet_attributes() {
+        let attributes = PacketAttributes {
+            protocol: 17,
+            src_port: 1234,
+            dst_port: 53,
+            priority: 46,
+            flow_id: 42,
+            wire_size: 0,
+        };
+        let wire_size_only = PacketAttributes {
+            wire_size: 1500,
+            ..Default::default()
+        };
+        let mut builder = FlatBufferBuilder::new();
+
+        for expected in &[PacketAttributes::default(), attributes, PacketAttributes { wire_size: 1500, ..attributes }, wire_size_only] {
+            let (b, msg) = prepare_deliver_packet(&mut builder, 0u32.into(), 1u32.into(), expected, b"Foo msg");
+            b.finish_size_prefixed(msg, None);
+            let fb = builder.finished_data();
+            let buffer_pool = BufferPool::<BytesBuffer>::new(fb.len(), 1);
+            let mut buffer = buffer_pool.allocate_buffer(fb.len()).unwrap();
+            buffer.copy_from_slice(fb);
+
+            match MsgIn::new_deliver_packet(buffer).unwrap() {
+                MsgIn::DeliverPacket(d) => assert_eq!(*expected, d.attributes()),
+                _ => assert!(false),
+            }
+            builder.reset();
+        }
+    }
+
     #[test]
     fn alloc_fb_receive() {
 
@@ -575,4 +1039,56 @@ mod test {
             });
         }
     }
+    #[test]
+    fn alloc_reassembly() {
+        use crate::fragment::{Fragmenter, Reassembler, FRAGMENT_HEADER_SIZE};
+
+        let pool = BufferPool::<BytesBuffer>::new(super::INPUT_BUFFER_SIZE, 10);
+        let fragmenter = Fragmenter::new(4);
+        let reassembler = Reassembler::new(2, 4, Duration::from_micros(100));
+        let msg = vec![1u8; 3 * crate::MAX_PACKET_SIZE];
+        let mut out = vec![0u8; msg.len()];
+
+        let make_fragments = || -> Vec<DeliverPacket> {
+            fragmenter.split(&msg).unwrap().map(|(header, chunk)| {
+                let mut payload = vec![0u8; FRAGMENT_HEADER_SIZE + chunk.len()];
+                header.write(&mut payload);
+                payload[FRAGMENT_HEADER_SIZE..].copy_from_slice(chunk);
+                let mut builder = FlatBufferBuilder::new();
+                create_deliver_packet(&mut builder, 1u32, 2u32, &payload);
+                let fb = builder.finished_data();
+                let mut buffer = pool.allocate_buffer(fb.len()).unwrap();
+                buffer.copy_from_slice(fb);
+                match MsgIn::new_deliver_packet(buffer).unwrap() {
+                    MsgIn::DeliverPacket(d) => d,
+                    _ => unreachable!(),
+                }
+            }).collect()
+        };
+
+        {
+            // complete message
+            let fragments = make_fragments();
+            assert_no_alloc!(A, {
+                let mut reassembled = None;
+                for fragment in fragments {
+                    reassembled = reassembler.push(fragment, Duration::from_micros(0)).unwrap().msg;
+                }
+                reassembled.unwrap().copy_to(&mut out);
+            });
+            assert_eq!(msg, out);
+        }
+
+        {
+            // incomplete message expiring at a deadline
+            let mut fragments = make_fragments();
+            fragments.pop();
+            assert_no_alloc!(A, {
+                for fragment in fragments {
+                    assert!(reassembler.push(fragment, Duration::from_micros(0)).unwrap().msg.is_none());
+                }
+                assert_eq!(1, reassembler.expire(Duration::from_micros(200)));
+            });
+        }
+    }
 }
@@ -5,9 +5,11 @@ use crate::flatbuilder_buffer::FbBuilderInitializer;
 use flatbuffers::FlatBufferBuilder;
 use std::io::Result;
 use std::os::unix::net::UnixStream;
-use super::{Connector, MsgIn, MsgOut};
+use super::{ConnectionState, ConnectionStateHandle, Connector, MsgIn, MsgOut};
 
 
+// Losing the connection to the actor is fatal: the actor cannot resume the simulation of a node
+// after a restart, so any I/O error is returned to the caller, which ends the simulation.
 #[derive(Debug)]
 pub(crate) struct UnixConnector {
     // No concurrency
@@ -19,24 +21,37 @@ pub(crate) struct UnixConnector {
     input_buffer_pool: BufferPool<BytesBuffer>,
     // No concurrency
     scratch_builder: FlatBufferBuilder<'static>,
+    // Concurrency: written when sending to and receiving from the actor, read by anyone
+    state: ConnectionStateHandle,
+}
+
+impl UnixConnector {
+    fn check<T>(&self, res: Result<T>) -> Result<T> {
+        if res.is_err() {
+            self.state.set(ConnectionState::Disconnected);
+        }
+        res
+    }
 }
 
 impl Connector for UnixConnector {
     fn new(config: &crate::Config) -> Result<UnixConnector> {
         let actor_stream = UnixStream::connect(&config.actor_socket)?;
 
-        let input_buffer_pool = BufferPool::new(crate::MAX_PACKET_SIZE, config.num_buffers.get());
+        let input_buffer_pool = BufferPool::new(super::INPUT_BUFFER_SIZE, config.num_buffers.get());
         Ok(UnixConnector {
             actor: actor_stream,
             input_buffer_pool: input_buffer_pool,
-            scratch_builder: MsgFbInitializer::init(crate::MAX_PACKET_SIZE)
+            scratch_builder: MsgFbInitializer::init(crate::MAX_PACKET_SIZE),
+            state: ConnectionStateHandle::new(ConnectionState::Connected),
         })
     }
 
     fn recv(&mut self) -> Result<MsgIn> {
         let stream = &mut self.actor;
         let buffer_pool = &self.input_buffer_pool;
-        MsgIn::recv(stream, buffer_pool)
+        let res = MsgIn::recv(stream, buffer_pool);
+        self.check(res)
     }
 
     fn send(&mut self, msg: MsgOut) -> Result<()> {
@@ -44,7 +59,12 @@ impl Connector for UnixConnector {
         let scratch_builder = &mut self.scratch_builder;
         // TODO(msimonin): test that we reset the buffer correctly when sending several messages in a row:w
         scratch_builder.reset();
-        msg.send(stream, scratch_builder)
+        let res = msg.send(stream, scratch_builder);
+        self.check(res)
+    }
+
+    fn connection_state(&self) -> ConnectionStateHandle {
+        self.state.clone()
     }
 }
 
@@ -188,7 +208,7 @@ pub mod test_helpers {
             TestActor {
                 client: client,
                 // TODO: Do not hardcode a limit of 100 buffers
-                input_buffer_pool: BufferPool::new(crate::MAX_PACKET_SIZE, 100),
+                input_buffer_pool: BufferPool::new(crate::connector::INPUT_BUFFER_SIZE, 100),
                 input_fb_buffer_pool: BufferPool::<FbBuffer>::new(crate::MAX_PACKET_SIZE, 100),
             }
         }
@@ -494,6 +514,24 @@ mod test {
         recv_deliver_packet_actor)
     }
 
+    fn recv_link_state_actor(actor: &mut TestActor) -> TestResult<()> {
+        actor.send(MsgIn::LinkState(DELIVER_PACKET_DST.into(), false))
+    }
+
+    #[test]
+    fn recv_link_state() {
+        run_client_and_actor(|mut connector| {
+            match connector.recv().expect("recv failed") {
+                MsgIn::LinkState(address, up) => {
+                    assert_eq!(crate::vsg_address::VsgAddress::V4(DELIVER_PACKET_DST), address);
+                    assert!(!up);
+                },
+                _ => assert!(false),
+            }
+        },
+        recv_link_state_actor)
+    }
+
     // fn recv_msg_out_type(client: &mut UnixStream, expected_type: MsgOutType) -> TestResult<MsgOutType> {
     //     let mut buffer = vec!(0; MsgOutType::NUM_BYTES);
     //     let buffer = buffer.as_mut_slice();
@@ -528,7 +566,7 @@ mod test {
         // FIXME(msimonin): This is basically a duplication of MsgOut::recv
         let msg: MsgOut = actor.recv()?;
         TestActor::check(match msg {
-            MsgOut::AtDeadline => Ok(()),
+            MsgOut::AtDeadline(_) => Ok(()),
              _ => Err(std::io::Error::new(ErrorKind::InvalidData, "Wrong message type"))
         }, "Received wrong message type")
     }
@@ -536,7 +574,7 @@ mod test {
     #[test]
     fn send_at_deadline() {
         run_client_and_actor(|mut connector| {
-            connector.send(MsgOut::AtDeadline).expect("Failed to send at_deadline")
+            connector.send(MsgOut::AtDeadline(None)).expect("Failed to send at_deadline")
         },
         recv_at_deadline)
     }
@@ -545,8 +583,8 @@ mod test {
     fn send_at_deadline_twice() {
         // Test that we correctly reset the flatbuffer
         run_client_and_actor(|mut connector| {
-            connector.send(MsgOut::AtDeadline).expect("Failed to send at_deadline");
-            connector.send(MsgOut::AtDeadline).expect("Failed to send at_deadline")
+            connector.send(MsgOut::AtDeadline(None)).expect("Failed to send at_deadline");
+            connector.send(MsgOut::AtDeadline(None)).expect("Failed to send at_deadline")
         },
         recv_at_deadline)
     }
@@ -570,7 +608,7 @@ mod test {
         let buffer = buffer_pool.allocate_buffer(msg.len()).expect("allocate_buffer failed");
 
         let send_time = Duration::new(3, 200);
-        let send_packet_builder = SendPacketBuilder::new(0u32, 1u32, send_time, msg, buffer).unwrap();
+        let send_packet_builder = SendPacketBuilder::new(0u32.into(), 1u32.into(), crate::packet_attributes::PacketAttributes::default(), 0, send_time, msg, buffer).unwrap();
         MsgOut::SendPacket(send_packet_builder.finish(send_time))
     }
 
@@ -605,4 +643,28 @@ mod test {
         },
         send_send_packet_actor)
     }
+
+    fn connection_lost_actor(actor: &mut TestActor) -> TestResult<()> {
+        actor.send(MsgIn::GoToDeadline(Duration::new(GO_TO_DEADLINE_SECONDS, GO_TO_DEADLINE_NSECONDS as u32)))
+    }
+
+    #[test]
+    fn connection_lost() {
+        init();
+
+        let mut actor = TestActorDesc::new("titi", connection_lost_actor);
+        let config = Config::from_iter_safe(&["-atiti", "-n10.0.0.1", "-t1970-01-02T00:00:00"]).unwrap();
+        let mut connector = UnixConnector::new(&config).unwrap();
+        let state = connector.connection_state();
+        assert_eq!(ConnectionState::Connected, state.get());
+
+        connector.recv().expect("recv failed");
+        // The actor closed the connection, there is no way back
+        assert!(connector.recv().is_err());
+        assert_eq!(ConnectionState::Disconnected, state.get());
+
+        drop(connector);
+        let status = actor.wait().unwrap();
+        assert_eq!(0, status, "Actor process reported an error");
+    }
 }
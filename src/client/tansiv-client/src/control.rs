@@ -0,0 +1,189 @@
+use crate::{Config, Context, Stats};
+use log::error;
+use std::io::{BufRead, BufReader, ErrorKind, Result, Write};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::sync::Weak;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::{Receiver, Sender};
+use std::time::Duration;
+
+// Period at which the control thread checks that the context is still alive
+const ACCEPT_PERIOD: Duration = Duration::from_millis(100);
+// Idle clients are disconnected after this delay
+const READ_TIMEOUT: Duration = Duration::from_secs(1);
+
+// Starts the control socket of a context. Clients send one query per line, among status, stats
+// and config, and get one JSON object per line in return.
+//
+// Queries are served by helper threads that only read atomic state of the context, so they never
+// block the deadline handler. Each client has its own thread, so that an idle client does not
+// delay the others. The accepting thread exits once the context is gone.
+pub(crate) fn start(config: &Config) -> Result<Option<Sender<Weak<Context>>>> {
+    let path = match &config.control_socket {
+        Some(path) => path.clone(),
+        None => return Ok(None),
+    };
+    // Remove the socket of a previous run, but nothing else
+    if let Ok(metadata) = std::fs::symlink_metadata(&path) {
+        if metadata.file_type().is_socket() {
+            std::fs::remove_file(&path)?;
+        }
+    }
+    let listener = UnixListener::bind(&path)?;
+    listener.set_nonblocking(true)?;
+    let config_json = config_json(config);
+
+    let (sender, receiver) = std::sync::mpsc::channel();
+    std::thread::Builder::new()
+        .name("tansiv-control".to_string())
+        .spawn(move || control_main(listener, path, config_json, receiver))?;
+    Ok(Some(sender))
+}
+
+fn control_main(listener: UnixListener, path: PathBuf, config: String, context: Receiver<Weak<Context>>) {
+    // The deadline handler must not interrupt queries while they hold locks, e.g. in malloc()
+    if let Err(e) = crate::timer::block_deadline_signal() {
+        error!("Control socket failed to block the deadline signal: {}", e);
+        let _ = std::fs::remove_file(&path);
+        return;
+    }
+
+    if let Ok(context) = context.recv() {
+        loop {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    let (context, config) = (context.clone(), config.clone());
+                    // Inherits the signal mask, with the deadline signal blocked
+                    let res = std::thread::Builder::new()
+                        .name("tansiv-ctl-conn".to_string())
+                        .spawn(move || if let Err(e) = serve(&context, &config, stream) {
+                            if e.kind() != ErrorKind::WouldBlock && e.kind() != ErrorKind::TimedOut {
+                                error!("Control socket client failed: {}", e);
+                            }
+                        });
+                    if let Err(e) = res {
+                        error!("Control socket failed to start a client thread: {}", e);
+                    }
+                },
+                Err(ref e) if e.kind() == ErrorKind::WouldBlock => {
+                    if context.strong_count() == 0 {
+                        break;
+                    }
+                    std::thread::sleep(ACCEPT_PERIOD);
+                },
+                Err(e) => {
+                    error!("Control socket failed: {}", e);
+                    break;
+                },
+            }
+        }
+    }
+    let _ = std::fs::remove_file(&path);
+}
+
+fn serve(context: &Weak<Context>, config: &str, stream: UnixStream) -> Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut writer = stream.try_clone()?;
+
+    for query in BufReader::new(stream).lines() {
+        let query = query?;
+        // Do not keep the context alive between queries
+        let context = match context.upgrade() {
+            Some(context) => context,
+            None => break,
+        };
+        let response = match query.trim() {
+            "" => continue,
+            "status" => status_json(&context),
+            "stats" => stats_json(&context.stats()),
+            "config" => config.to_string(),
+            query => format!("{{\"error\":\"unknown query\",\"query\":{}}}", json_string(query)),
+        };
+        writeln!(writer, "{}", response)?;
+    }
+    Ok(())
+}
+
+fn json_string(s: &str) -> String {
+    let mut json = String::with_capacity(s.len() + 2);
+    json.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+fn status_json(context: &Context) -> String {
+    let termination_reason = match context.termination_reason() {
+        Some(reason) => json_string(&format!("{:?}", reason)),
+        None => "null".to_string(),
+    };
+    format!("{{\"address\":{},\"started\":{},\"termination_reason\":{},\"connection\":\"{}\",\"last_deadline_ns\":{},\"next_deadline_ns\":{},\"input_queue_len\":{},\"output_buffers_in_use\":{}}}",
+        json_string(&context.addresses[0].to_string()),
+        context.start_once.is_completed(),
+        termination_reason,
+        context.connection_state.get().name(),
+        context.last_deadline.load(Ordering::Acquire),
+        context.next_deadline.load(Ordering::Acquire),
+        context.input_queue.len(),
+        context.output_buffer_pool.in_use())
+}
+
+fn stats_json(stats: &Stats) -> String {
+    format!("{{\"packets_sent\":{},\"bytes_sent\":{},\"packets_received\":{},\"bytes_received\":{},\
+             \"drops_no_buffer\":{},\"drops_unjoined_group\":{},\"drops_reassembly\":{},\"drops_input_queue_full\":{},\
+             \"drops_unknown\":{},\"drops_unreachable\":{},\"drops_loss\":{},\"drops_queue_full\":{},\
+             \"output_buffers_high_water\":{},\"input_queue_high_water\":{},\
+             \"deadlines\":{},\"deadline_handler_ns\":{},\"deadline_handler_max_ns\":{},\"late_timestamps\":{},\"faults\":{}}}",
+        stats.packets_sent, stats.bytes_sent, stats.packets_received, stats.bytes_received,
+        stats.drops_no_buffer, stats.drops_unjoined_group, stats.drops_reassembly, stats.drops_input_queue_full,
+        stats.drops_unknown, stats.drops_unreachable, stats.drops_loss, stats.drops_queue_full,
+        stats.output_buffers_high_water, stats.input_queue_high_water,
+        stats.deadlines, stats.deadline_handler_time.as_nanos(), stats.deadline_handler_max_time.as_nanos(), stats.late_timestamps,
+        stats.faults)
+}
+
+fn config_json(config: &Config) -> String {
+    let mut addresses = vec![json_string(&config.address.to_string())];
+    addresses.extend(config.aliases.iter().map(|alias| json_string(&alias.to_string())));
+    let faults: Vec<String> = config.faults.iter().map(|rule| json_string(&rule.to_string())).collect();
+    let path_json = |path: &Option<PathBuf>| match path {
+        Some(path) => json_string(&path.to_string_lossy()),
+        None => "null".to_string(),
+    };
+    format!("{{\"addresses\":[{}],\"actor_socket\":{},\"initial_time\":{},\"num_buffers\":{},\"fragment\":{},\
+             \"reassembly_timeout_us\":{},\"trace\":{},\"control_socket\":{},\
+             \"faults\":[{}],\"fault_seed\":{},\"nic_rate\":{},\"nic_burst\":{},\"stream\":{}}}",
+        addresses.join(","),
+        json_string(&config.actor_socket.to_string_lossy()),
+        json_string(&config.time_offset.to_string()),
+        config.num_buffers,
+        config.fragment,
+        config.reassembly_timeout,
+        path_json(&config.trace),
+        path_json(&config.control_socket),
+        faults.join(","),
+        config.fault_seed,
+        config.nic_rate.map_or("null".to_string(), |rate| rate.to_string()),
+        config.nic_burst,
+        config.stream)
+}
+
+#[cfg(test)]
+mod test {
+    use super::json_string;
+
+    #[test]
+    fn escape() {
+        assert_eq!("\"a\\\"b\\\\c\\u000a\"", json_string("a\"b\\c\n"));
+    }
+}
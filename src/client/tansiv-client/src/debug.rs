@@ -1,7 +1,14 @@
+// Logging from the deadline handler, which may run in signal handler context. Records go to the
+// preallocated log_ring::LOG_RING without locking nor allocating and a helper thread forwards them
+// to the log crate, so that they are available in production runs and can be dumped on a crash.
+// The deadline-handler-debug feature restores direct calls to log::debug!(), which are not
+// async-signal-safe.
 macro_rules! deadline_handler_debug {
     ($($arg:tt)*) => {
         if cfg!(feature = "deadline-handler-debug") {
             ::log::debug!($($arg)*)
+        } else {
+            crate::log_ring::LOG_RING.record(::log::Level::Debug, format_args!($($arg)*))
         }
     }
 }
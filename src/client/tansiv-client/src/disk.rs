@@ -0,0 +1,81 @@
+use crate::short_str::{ShortStr, MAX_SHORT_STR_LEN};
+use std::fmt;
+use std::time::Duration;
+
+#[derive(Debug)]
+pub enum Error {
+    NameTooLong,
+}
+
+type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let msg = match self {
+            Error::NameTooLong => "Disk name too long",
+        };
+        write!(f, "{}", msg)
+    }
+}
+
+impl std::error::Error for Error {}
+
+pub const MAX_DISK_NAME_LEN: usize = MAX_SHORT_STR_LEN;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiskOp {
+    Read,
+    Write,
+}
+
+// Block I/O request on a disk of the simulated platform
+//
+// time is the simulated time of submission. The simulator answers with a DiskCompletion having
+// the same request_id.
+#[derive(Debug, Clone, Copy)]
+pub struct DiskRequest {
+    pub request_id: u64,
+    pub time: Duration,
+    pub op: DiskOp,
+    pub size: u64,
+    disk: ShortStr,
+}
+
+impl DiskRequest {
+    pub fn new(request_id: u64, time: Duration, op: DiskOp, size: u64, disk: &str) -> Result<DiskRequest> {
+        Ok(DiskRequest {
+            request_id,
+            time,
+            op,
+            size,
+            disk: ShortStr::new(disk).map_err(|_| Error::NameTooLong)?,
+        })
+    }
+
+    pub fn disk(&self) -> &str {
+        self.disk.as_str()
+    }
+}
+
+// time is the simulated time at which the request completed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiskCompletion {
+    pub request_id: u64,
+    pub time: Duration,
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+    use super::{DiskOp, DiskRequest, Error, MAX_DISK_NAME_LEN};
+
+    #[test]
+    fn disk_name() {
+        let request = DiskRequest::new(1, Duration::from_micros(1), DiskOp::Write, 4096, "disk0")
+            .expect("DiskRequest::new failed");
+        assert_eq!("disk0", request.disk());
+
+        let name = "d".repeat(MAX_DISK_NAME_LEN + 1);
+        assert!(matches!(DiskRequest::new(1, Duration::from_micros(1), DiskOp::Read, 4096, &name), Err(Error::NameTooLong)));
+    }
+}
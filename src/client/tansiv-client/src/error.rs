@@ -2,10 +2,18 @@ use std::{error, fmt, io};
 
 #[derive(Debug)]
 pub enum Error {
+    AlreadyLoaded,
     AlreadyStarted,
+    InvalidAddress,
+    InvalidSize,
+    InvalidState,
     NoMemoryAvailable,
     NoMessageAvailable,
+    NotStarted,
+    NotSynthetic,
     ProtocolViolation,
+    SimulationEnded,
+    SimulationRunning,
     SizeTooBig,
     IoError(io::Error),
 }
@@ -22,10 +30,18 @@ impl fmt::Display for Error {
             Error::IoError(e) => e.fmt(f),
             simple => {
                 let msg = match simple {
+                    Error::AlreadyLoaded => "Already loaded",
                     Error::AlreadyStarted => "Already Started",
+                    Error::InvalidAddress => "Invalid address",
+                    Error::InvalidSize => "Invalid size",
+                    Error::InvalidState => "Invalid saved state",
                     Error::NoMemoryAvailable => "No memory available",
                     Error::NoMessageAvailable => "No message available",
+                    Error::NotStarted => "Not started",
+                    Error::NotSynthetic => "Not a synthetic message",
                     Error::ProtocolViolation => "Protocol violation",
+                    Error::SimulationEnded => "Simulation ended",
+                    Error::SimulationRunning => "Simulation running",
                     Error::SizeTooBig => "Size too big",
                     Error::IoError(_) => unimplemented!(),
                 };
@@ -0,0 +1,525 @@
+use crate::buffer_pool::BufferPool;
+use crate::bytes_buffer::BytesBuffer;
+use crate::connector::FbBuffer;
+use crate::input_msg::{CopiedMsg, InputMsg};
+use crate::output_msg_set::OutputMsg;
+use crate::stats::{Counters, LocalDrop};
+use crate::vsg_address::{self, VsgAddress};
+use log::info;
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    UnknownAction(String),
+    InvalidProbability(String),
+    InvalidParameter(String),
+    MissingDelay,
+}
+
+type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::UnknownAction(action) => write!(f, "Unknown fault action: {}", action),
+            Error::InvalidProbability(probability) => write!(f, "Invalid fault probability: {}", probability),
+            Error::InvalidParameter(parameter) => write!(f, "Invalid fault parameter: {}", parameter),
+            Error::MissingDelay => write!(f, "Delay faults need a delay (by=...)"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FaultAction {
+    Drop,
+    Duplicate,
+    // Flips one bit of the payload
+    Corrupt,
+    // Extra delay, in simulated time
+    Delay(Duration),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Send,
+    Recv,
+    Both,
+}
+
+// Fault applied with some probability to the packets matching a direction, a peer address and a
+// window of simulated time
+//
+// Syntax: ACTION[:PROBABILITY][,dir=send|recv][,peer=ADDRESS][,from=US][,until=US][,by=US]
+//
+// - ACTION is drop, duplicate, corrupt or delay,
+// - PROBABILITY is between 0 and 1, 1 by default,
+// - peer is the destination of sent packets and the source of received packets,
+// - from (included) and until (excluded) bound the window, in microseconds of simulated time,
+// - by is the extra delay of delay faults, in microseconds of simulated time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FaultRule {
+    pub action: FaultAction,
+    pub probability: f64,
+    pub direction: Direction,
+    pub peer: Option<VsgAddress>,
+    pub from: Option<Duration>,
+    pub until: Option<Duration>,
+}
+
+impl FaultRule {
+    fn matches(&self, direction: Direction, peer: VsgAddress, time: Duration) -> bool {
+        (self.direction == Direction::Both || self.direction == direction) &&
+            self.peer.map_or(true, |p| p == peer) &&
+            self.from.map_or(true, |from| time >= from) &&
+            self.until.map_or(true, |until| time < until)
+    }
+}
+
+fn parse_micros(parameter: &str, value: &str) -> Result<Duration> {
+    u64::from_str(value).map(Duration::from_micros).map_err(|_| Error::InvalidParameter(parameter.to_string()))
+}
+
+impl FromStr for FaultRule {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<FaultRule> {
+        let mut parts = s.split(',');
+        // split() yields at least one item
+        let head = parts.next().unwrap();
+        let (action, probability) = match head.find(':') {
+            Some(pos) => (&head[..pos], Some(&head[(pos + 1)..])),
+            None => (head, None),
+        };
+        let probability = match probability {
+            Some(probability) => match f64::from_str(probability) {
+                Ok(p) if (0.0..=1.0).contains(&p) => p,
+                _ => return Err(Error::InvalidProbability(probability.to_string())),
+            },
+            None => 1.0,
+        };
+
+        let mut rule = FaultRule {
+            action: FaultAction::Drop,
+            probability,
+            direction: Direction::Both,
+            peer: None,
+            from: None,
+            until: None,
+        };
+        let mut delay = None;
+        for parameter in parts {
+            let (key, value) = match parameter.find('=') {
+                Some(pos) => (&parameter[..pos], &parameter[(pos + 1)..]),
+                None => return Err(Error::InvalidParameter(parameter.to_string())),
+            };
+            match key {
+                "dir" => rule.direction = match value {
+                    "send" => Direction::Send,
+                    "recv" => Direction::Recv,
+                    _ => return Err(Error::InvalidParameter(parameter.to_string())),
+                },
+                "peer" => rule.peer = Some(vsg_address::from_str(value).map_err(|_| Error::InvalidParameter(parameter.to_string()))?),
+                "from" => rule.from = Some(parse_micros(parameter, value)?),
+                "until" => rule.until = Some(parse_micros(parameter, value)?),
+                "by" => delay = Some(parse_micros(parameter, value)?),
+                _ => return Err(Error::InvalidParameter(parameter.to_string())),
+            }
+        }
+
+        rule.action = match action {
+            "drop" => FaultAction::Drop,
+            "duplicate" => FaultAction::Duplicate,
+            "corrupt" => FaultAction::Corrupt,
+            "delay" => FaultAction::Delay(delay.ok_or(Error::MissingDelay)?),
+            _ => return Err(Error::UnknownAction(action.to_string())),
+        };
+        Ok(rule)
+    }
+}
+
+impl fmt::Display for FaultRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let action = match self.action {
+            FaultAction::Drop => "drop",
+            FaultAction::Duplicate => "duplicate",
+            FaultAction::Corrupt => "corrupt",
+            FaultAction::Delay(_) => "delay",
+        };
+        write!(f, "{}:{}", action, self.probability)?;
+        match self.direction {
+            Direction::Send => write!(f, ",dir=send")?,
+            Direction::Recv => write!(f, ",dir=recv")?,
+            Direction::Both => (),
+        }
+        if let Some(peer) = self.peer {
+            write!(f, ",peer={}", peer)?;
+        }
+        if let Some(from) = self.from {
+            write!(f, ",from={}", from.as_micros())?;
+        }
+        if let Some(until) = self.until {
+            write!(f, ",until={}", until.as_micros())?;
+        }
+        if let FaultAction::Delay(delay) = self.action {
+            write!(f, ",by={}", delay.as_micros())?;
+        }
+        Ok(())
+    }
+}
+
+// Faults to apply to a packet
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Faults {
+    pub drop: bool,
+    pub duplicate: bool,
+    pub corrupt: bool,
+    pub delay: Duration,
+}
+
+impl Faults {
+    pub fn is_empty(&self) -> bool {
+        *self == Faults::default()
+    }
+}
+
+// SplitMix64, lock-free and async-signal-safe
+#[derive(Debug)]
+struct Rng {
+    state: AtomicU64,
+}
+
+const RNG_GAMMA: u64 = 0x9e37_79b9_7f4a_7c15;
+
+impl Rng {
+    fn new(seed: u64) -> Rng {
+        Rng {
+            state: AtomicU64::new(seed),
+        }
+    }
+
+    fn next_u64(&self) -> u64 {
+        let mut z = self.state.fetch_add(RNG_GAMMA, Ordering::Relaxed).wrapping_add(RNG_GAMMA);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    // Uniform in [0, 1)
+    fn next_f64(&self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+// Client-side fault injection, between the context and the connector
+//
+// Random draws come from a seeded generator and only depend on the sequence of packets, so runs
+// with the same seed and the same traffic get the same faults.
+#[derive(Debug)]
+pub struct FaultInjector {
+    rules: Vec<FaultRule>,
+    rng: Rng,
+}
+
+impl FaultInjector {
+    pub fn new(rules: &[FaultRule], seed: u64) -> FaultInjector {
+        FaultInjector {
+            rules: rules.to_vec(),
+            rng: Rng::new(seed),
+        }
+    }
+
+    // dst is the destination of the packet and time its send time
+    pub fn on_send(&self, dst: VsgAddress, time: Duration) -> Faults {
+        self.faults(Direction::Send, dst, time)
+    }
+
+    // src is the source of the packet and time the deadline at which it is delivered
+    pub fn on_recv(&self, src: VsgAddress, time: Duration) -> Faults {
+        self.faults(Direction::Recv, src, time)
+    }
+
+    // Rules are cumulative. Delays add up.
+    fn faults(&self, direction: Direction, peer: VsgAddress, time: Duration) -> Faults {
+        let mut faults = Faults::default();
+        for rule in self.rules.iter().filter(|rule| rule.matches(direction, peer, time)) {
+            if self.rng.next_f64() < rule.probability {
+                match rule.action {
+                    FaultAction::Drop => faults.drop = true,
+                    FaultAction::Duplicate => faults.duplicate = true,
+                    FaultAction::Corrupt => faults.corrupt = true,
+                    FaultAction::Delay(delay) => faults.delay += delay,
+                }
+            }
+        }
+        faults
+    }
+
+    pub fn corrupt(&self, payload: &mut [u8]) {
+        if !payload.is_empty() {
+            let bit = self.rng.next_u64() % (payload.len() as u64 * 8);
+            payload[(bit / 8) as usize] ^= 1 << (bit % 8);
+        }
+    }
+}
+
+// Fault injection on the send and receive paths of a context, used by the deadline handler only
+#[derive(Debug)]
+pub struct FaultLayer {
+    injector: FaultInjector,
+    // Length of the header left intact by corruption, so that corrupted fragments are still
+    // reassembled
+    header_len: usize,
+    // Copies of received messages corrupted or duplicated
+    // Concurrency: buffers are allocated by the deadline handler and freed by application code.
+    // BufferPool is thread-safe.
+    buffer_pool: BufferPool<BytesBuffer>,
+    // Received messages delayed, with the deadline from which they can be delivered, in
+    // increasing order. Its capacity is allocated once and never grows.
+    // No concurrency: (mut) accessed only by the deadline handler
+    delayed_input: Mutex<VecDeque<(Duration, InputMsg)>>,
+}
+
+impl FaultLayer {
+    // Received messages of up to max_msg_size bytes can be copied, and up to num_buffers
+    // messages can be copied or delayed at the same time
+    pub fn new(rules: &[FaultRule], seed: u64, header_len: usize, max_msg_size: usize, num_buffers: usize) -> FaultLayer {
+        FaultLayer {
+            injector: FaultInjector::new(rules, seed),
+            header_len,
+            buffer_pool: BufferPool::<BytesBuffer>::new(max_msg_size, num_buffers),
+            delayed_input: Mutex::new(VecDeque::with_capacity(num_buffers)),
+        }
+    }
+
+    // Sends a packet time-stamped send_time through fault injection. send is called with each
+    // copy of the packet left and its new send time. No delivery report is received for dropped
+    // packets.
+    pub fn send<F>(&self, msg: OutputMsg, send_time: Duration, output_buffer_pool: &BufferPool<FbBuffer>, counters: &Counters, mut send: F) -> std::io::Result<()>
+        where F: FnMut(OutputMsg, Duration) -> std::io::Result<()> {
+        let fault = self.injector.on_send(msg.dst(), send_time);
+        if fault.is_empty() {
+            return send(msg, send_time);
+        }
+        deadline_handler_debug!("FaultLayer::send() packet to {} at {:?}: {:?}", msg.dst(), send_time, fault);
+        counters.count_fault();
+        if fault.drop {
+            return Ok(());
+        }
+
+        let msg = if fault.corrupt {
+            match self.corrupt_output_msg(msg, output_buffer_pool) {
+                Ok(msg) => msg,
+                Err(e) => {
+                    info!("Dropping corrupted packet: {:?}", e);
+                    return Ok(());
+                },
+            }
+        } else {
+            msg
+        };
+        if fault.duplicate {
+            match copy_output_msg(&msg, output_buffer_pool) {
+                Ok(copy) => send(copy, send_time)?,
+                Err(e) => info!("Cannot duplicate packet to {}: {:?}", msg.dst(), e),
+            }
+        }
+        send(msg, send_time + fault.delay)
+    }
+
+    // Output buffers cannot be modified in place, so the payload is corrupted in a copy
+    fn corrupt_output_msg(&self, msg: OutputMsg, output_buffer_pool: &BufferPool<FbBuffer>) -> crate::Result<OutputMsg> {
+        let mut payload = [0u8; crate::MAX_PACKET_SIZE];
+        let len = msg.payload().len();
+        payload[..len].copy_from_slice(msg.payload());
+        let start = std::cmp::min(self.header_len, len);
+        self.injector.corrupt(&mut payload[start..len]);
+        let (src, dst, attributes, seq_id, send_time) = (msg.src(), msg.dst(), msg.attributes(), msg.seq_id(), msg.send_time());
+        // Free the buffer before allocating the copy
+        drop(msg);
+        let buffer = output_buffer_pool.allocate_buffer(len)?;
+        Ok(OutputMsg::new(src, dst, attributes, seq_id, send_time, &payload[..len], buffer)?)
+    }
+
+    // Delivers a message received at deadline now through fault injection, applied after
+    // reassembly. deliver is called with each copy of the message delivered now.
+    pub fn deliver<F>(&self, input_msg: InputMsg, now: Duration, counters: &Counters, mut deliver: F)
+        where F: FnMut(InputMsg) {
+        let fault = self.injector.on_recv(input_msg.src(), now);
+        if fault.is_empty() {
+            return deliver(input_msg);
+        }
+        deadline_handler_debug!("FaultLayer::deliver() packet from {} at {:?}: {:?}", input_msg.src(), now, fault);
+        counters.count_fault();
+        if fault.drop {
+            return;
+        }
+
+        // Copies are made in preallocated buffers, the deadline handler must not allocate
+        let input_msg = if fault.corrupt {
+            match CopiedMsg::new(&input_msg, &self.buffer_pool) {
+                Ok(mut copy) => {
+                    self.injector.corrupt(copy.payload_mut());
+                    InputMsg::Copied(copy)
+                },
+                Err(e) => {
+                    info!("Dropping corrupted packet from {}: {}", input_msg.src(), e);
+                    return;
+                },
+            }
+        } else {
+            input_msg
+        };
+        if fault.duplicate {
+            match CopiedMsg::new(&input_msg, &self.buffer_pool) {
+                Ok(copy) => deliver(InputMsg::Copied(copy)),
+                Err(e) => info!("Cannot duplicate packet from {}: {}", input_msg.src(), e),
+            }
+        }
+        if fault.delay > Duration::from_nanos(0) {
+            let due = now + fault.delay;
+            let mut delayed_input = self.delayed_input.lock().unwrap();
+            // Do not let the queue grow
+            if delayed_input.len() == delayed_input.capacity() {
+                info!("Dropping delayed packet from {} of {} bytes", input_msg.src(), input_msg.payload_len());
+                counters.count_local_drop(LocalDrop::InputQueueFull);
+                return;
+            }
+            let pos = delayed_input.iter().position(|(time, _)| *time > due).unwrap_or(delayed_input.len());
+            delayed_input.insert(pos, (due, input_msg));
+        } else {
+            deliver(input_msg);
+        }
+    }
+
+    // Calls deliver with the delayed messages due at deadline, in order
+    pub fn deliver_delayed(&self, deadline: Duration, mut deliver: impl FnMut(InputMsg)) {
+        let mut delayed_input = self.delayed_input.lock().unwrap();
+        while delayed_input.front().map_or(false, |(due, _)| *due <= deadline) {
+            let (_, input_msg) = delayed_input.pop_front().unwrap();
+            deliver(input_msg);
+        }
+    }
+
+    pub fn has_delayed_input(&self) -> bool {
+        !self.delayed_input.lock().unwrap().is_empty()
+    }
+}
+
+fn copy_output_msg(msg: &OutputMsg, output_buffer_pool: &BufferPool<FbBuffer>) -> crate::Result<OutputMsg> {
+    let buffer = output_buffer_pool.allocate_buffer(msg.payload().len())?;
+    Ok(OutputMsg::new(msg.src(), msg.dst(), msg.attributes(), msg.seq_id(), msg.send_time(), msg.payload(), buffer)?)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::input_msg::InputMsg;
+    use crate::packet_attributes::PacketAttributes;
+    use crate::state::SavedInput;
+    use crate::stats::Counters;
+    use crate::vsg_address::VsgAddress;
+    use std::str::FromStr;
+    use std::time::Duration;
+    use super::{Direction, Error, FaultAction, FaultInjector, FaultLayer, FaultRule, Faults};
+
+    #[test]
+    fn parse() {
+        let rule = FaultRule::from_str("delay:0.25,dir=recv,peer=10.0.1.1,from=1000,until=2000,by=500").unwrap();
+        assert_eq!(FaultRule {
+            action: FaultAction::Delay(Duration::from_micros(500)),
+            probability: 0.25,
+            direction: Direction::Recv,
+            peer: Some(VsgAddress::V4(u32::from(std::net::Ipv4Addr::new(10, 0, 1, 1)).to_be())),
+            from: Some(Duration::from_micros(1000)),
+            until: Some(Duration::from_micros(2000)),
+        }, rule);
+        assert_eq!(rule, FaultRule::from_str(&rule.to_string()).unwrap());
+
+        let rule = FaultRule::from_str("drop").unwrap();
+        assert_eq!(FaultAction::Drop, rule.action);
+        assert_eq!(1.0, rule.probability);
+        assert_eq!(Direction::Both, rule.direction);
+
+        assert_eq!(Err(Error::UnknownAction("reorder".to_string())), FaultRule::from_str("reorder:0.1"));
+        assert_eq!(Err(Error::InvalidProbability("1.5".to_string())), FaultRule::from_str("drop:1.5"));
+        assert_eq!(Err(Error::InvalidParameter("dir=both".to_string())), FaultRule::from_str("drop,dir=both"));
+        assert_eq!(Err(Error::MissingDelay), FaultRule::from_str("delay:0.1"));
+    }
+
+    #[test]
+    fn deterministic() {
+        let rules = [FaultRule::from_str("drop:0.5,dir=send").unwrap()];
+        let peer = VsgAddress::V4(0);
+        let draw = |injector: &FaultInjector| -> Vec<bool> {
+            (0..100).map(|i| injector.on_send(peer, Duration::from_micros(i)).drop).collect()
+        };
+
+        let drops = draw(&FaultInjector::new(&rules, 42));
+        assert_eq!(drops, draw(&FaultInjector::new(&rules, 42)));
+        assert_ne!(drops, draw(&FaultInjector::new(&rules, 43)));
+        let num_drops = drops.iter().filter(|drop| **drop).count();
+        assert!((26..75).contains(&num_drops));
+
+        // Wrong direction
+        let injector = FaultInjector::new(&rules, 42);
+        assert_eq!(Faults::default(), injector.on_recv(peer, Duration::from_micros(0)));
+    }
+
+    #[test]
+    fn window() {
+        let rules = [
+            FaultRule::from_str("delay,from=100,until=200,by=10").unwrap(),
+            FaultRule::from_str("delay,peer=10.0.0.1,by=5").unwrap(),
+        ];
+        let injector = FaultInjector::new(&rules, 0);
+        let peer = VsgAddress::V4(u32::from(std::net::Ipv4Addr::new(10, 0, 0, 1)).to_be());
+        let other = VsgAddress::V4(0);
+
+        assert_eq!(Duration::from_micros(0), injector.on_send(other, Duration::from_micros(99)).delay);
+        assert_eq!(Duration::from_micros(10), injector.on_send(other, Duration::from_micros(100)).delay);
+        assert_eq!(Duration::from_micros(15), injector.on_recv(peer, Duration::from_micros(199)).delay);
+        assert_eq!(Duration::from_micros(5), injector.on_recv(peer, Duration::from_micros(200)).delay);
+    }
+
+    #[test]
+    fn corrupt() {
+        let injector = FaultInjector::new(&[], 0);
+        let mut payload = [0u8; 16];
+        injector.corrupt(&mut payload);
+        assert_eq!(1, payload.iter().map(|b| b.count_ones()).sum::<u32>());
+        injector.corrupt(&mut []);
+    }
+
+    #[test]
+    fn delayed_delivery() {
+        let rules = [FaultRule::from_str("delay,dir=recv,by=10").unwrap()];
+        let faults = FaultLayer::new(&rules, 0, 0, 64, 4);
+        let counters = Counters::new();
+        let input = |payload: &[u8]| InputMsg::Injected(SavedInput {
+            src: VsgAddress::V4(1),
+            dst: VsgAddress::V4(2),
+            attributes: PacketAttributes::default(),
+            payload: payload.to_vec(),
+        });
+
+        let mut delivered = vec![];
+        faults.deliver(input(b"first"), Duration::from_micros(100), &counters, |msg| delivered.push(msg));
+        faults.deliver(input(b"second msg"), Duration::from_micros(105), &counters, |msg| delivered.push(msg));
+        assert!(delivered.is_empty());
+        assert!(faults.has_delayed_input());
+
+        faults.deliver_delayed(Duration::from_micros(109), |msg| delivered.push(msg));
+        assert!(delivered.is_empty());
+        faults.deliver_delayed(Duration::from_micros(110), |msg| delivered.push(msg));
+        assert_eq!(vec![5], delivered.iter().map(|msg| msg.payload_len()).collect::<Vec<usize>>());
+        faults.deliver_delayed(Duration::from_micros(115), |msg| delivered.push(msg));
+        assert_eq!(vec![5, 10], delivered.iter().map(|msg| msg.payload_len()).collect::<Vec<usize>>());
+        assert!(!faults.has_delayed_input());
+    }
+}
@@ -0,0 +1,60 @@
+use crate::Context;
+use log::error;
+use std::io::Result;
+use std::sync::Weak;
+use std::sync::mpsc::{Receiver, Sender};
+use std::thread::Thread;
+use std::time::Duration;
+
+// Period at which the flusher checks that the context is still alive. Application code wakes the
+// flusher up for each packet, so this only bounds the latency of missed wake-ups.
+const FLUSH_PERIOD: Duration = Duration::from_millis(10);
+
+// Streams the outgoing packets of a context to the actor as soon as they are time-stamped, instead
+// of leaving them all to the deadline handler. The actor can then simulate the network while the
+// application runs, and long time slices do not end with bursts of packets.
+//
+// The flusher is a helper thread, so it may lock and allocate. It shares the connector with the
+// deadline handler, which still sends AtDeadline at the end of each time slice, and packets
+// time-stamped after the next deadline are left to the deadline handler. The thread exits once
+// the context is gone or the simulation has ended.
+pub(crate) struct Flusher {
+    thread: Thread,
+}
+
+impl Flusher {
+    pub(crate) fn start() -> Result<(Flusher, Sender<Weak<Context>>)> {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let handle = std::thread::Builder::new()
+            .name("tansiv-flusher".to_string())
+            .spawn(move || flusher_main(receiver))?;
+        Ok((Flusher { thread: handle.thread().clone() }, sender))
+    }
+
+    // Called by application code after queuing a packet
+    pub(crate) fn wake(&self) {
+        self.thread.unpark()
+    }
+}
+
+fn flusher_main(context: Receiver<Weak<Context>>) {
+    // The deadline handler must not interrupt the flusher while it holds the connector lock
+    if let Err(e) = crate::timer::block_deadline_signal() {
+        error!("Flusher failed to block the deadline signal: {}", e);
+        return;
+    }
+
+    if let Ok(context) = context.recv() {
+        loop {
+            std::thread::park_timeout(FLUSH_PERIOD);
+            // Do not keep the context alive between flushes
+            let context = match context.upgrade() {
+                Some(context) => context,
+                None => break,
+            };
+            if !context.flush() {
+                break;
+            }
+        }
+    }
+}
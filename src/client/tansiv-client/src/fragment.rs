@@ -0,0 +1,644 @@
+use crate::buffer_pool::{Buffer, BufferPool};
+use crate::bytes_buffer::BytesBuffer;
+use crate::connector::DeliverPacket;
+use crate::packet_attributes::PacketAttributes;
+use crate::vsg_address::VsgAddress;
+use log::info;
+use std::fmt;
+use std::ops::Range;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+
+// When fragmentation is enabled, every payload exchanged with the actor starts with this header,
+// even when the message fits in a single fragment. Both ends of a communication must therefore
+// enable fragmentation.
+//
+// Wire format (network byte order):
+// - msg_id: u32, unique per sender,
+// - index: u16, index of the fragment in the message,
+// - count: u16, number of fragments in the message,
+// - total_len: u32, length of the reassembled message.
+pub const FRAGMENT_HEADER_SIZE: usize = 12;
+// Fragments must fit in messages of MAX_PACKET_SIZE bytes (size prefix excluded), the limit of
+// the actor and of input buffers, together with the flatbuffers framing of SendPacket and
+// DeliverPacket. With all optional fields set (IPv6 metadata, flow metadata, time, seq_id and
+// wire_size) the framing takes less than 160 bytes, as checked by the fragment_fits test. The
+// rest is left for new fields.
+const MAX_FRAMING_SIZE: usize = 256;
+pub const MAX_FRAGMENT_SIZE: usize = crate::MAX_PACKET_SIZE - MAX_FRAMING_SIZE;
+const MAX_FRAGMENT_PAYLOAD: usize = MAX_FRAGMENT_SIZE - FRAGMENT_HEADER_SIZE;
+
+#[derive(Debug)]
+pub enum Error {
+    InvalidHeader,
+    NoSpace,
+    SizeTooBig,
+}
+
+type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let msg = match self {
+            Error::InvalidHeader => "Invalid fragment header",
+            Error::NoSpace => "No space left for reassembly",
+            Error::SizeTooBig => "Size too big",
+        };
+        write!(f, "{}", msg)
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FragmentHeader {
+    pub msg_id: u32,
+    pub index: u16,
+    pub count: u16,
+    pub total_len: u32,
+}
+
+impl FragmentHeader {
+    pub fn write(&self, buf: &mut [u8]) {
+        buf[0..4].copy_from_slice(&self.msg_id.to_be_bytes());
+        buf[4..6].copy_from_slice(&self.index.to_be_bytes());
+        buf[6..8].copy_from_slice(&self.count.to_be_bytes());
+        buf[8..12].copy_from_slice(&self.total_len.to_be_bytes());
+    }
+
+    pub fn read(buf: &[u8]) -> Result<FragmentHeader> {
+        use std::convert::TryInto;
+
+        if buf.len() < FRAGMENT_HEADER_SIZE {
+            return Err(Error::InvalidHeader);
+        }
+        let header = FragmentHeader {
+            msg_id: u32::from_be_bytes(buf[0..4].try_into().unwrap()),
+            index: u16::from_be_bytes(buf[4..6].try_into().unwrap()),
+            count: u16::from_be_bytes(buf[6..8].try_into().unwrap()),
+            total_len: u32::from_be_bytes(buf[8..12].try_into().unwrap()),
+        };
+        if header.count == 0 || header.index >= header.count {
+            Err(Error::InvalidHeader)
+        } else {
+            Ok(header)
+        }
+    }
+
+    // Share of the wire size of the message carried by this fragment, in proportion to its
+    // payload. Shares add up to wire_size, except that a share is never 0, which would stand for
+    // the length of the fragment.
+    pub fn wire_size(&self, wire_size: u32) -> u32 {
+        let total_len = self.total_len as u64;
+        if wire_size == 0 || total_len == 0 {
+            return wire_size;
+        }
+        let start = self.index as u64 * MAX_FRAGMENT_PAYLOAD as u64;
+        let end = std::cmp::min(start + MAX_FRAGMENT_PAYLOAD as u64, total_len);
+        let offset = |len: u64| wire_size as u64 * len / total_len;
+        std::cmp::max(1, offset(end) - offset(start)) as u32
+    }
+}
+
+// Splits messages in fragments on the send path
+// Concurrency: msg ids are allocated atomically by application code.
+#[derive(Debug)]
+pub struct Fragmenter {
+    next_msg_id: AtomicU32,
+    max_fragments: usize,
+}
+
+impl Fragmenter {
+    pub fn new(max_fragments: usize) -> Fragmenter {
+        Fragmenter {
+            next_msg_id: AtomicU32::new(0),
+            max_fragments: max_fragments.min(u16::MAX as usize),
+        }
+    }
+
+    pub fn max_message_size(&self) -> usize {
+        self.max_fragments * MAX_FRAGMENT_PAYLOAD
+    }
+
+    pub fn split<'a>(&self, msg: &'a [u8]) -> Result<impl Iterator<Item = (FragmentHeader, &'a [u8])> + 'a> {
+        if msg.len() > self.max_message_size() {
+            return Err(Error::SizeTooBig);
+        }
+
+        // Empty messages still need one fragment
+        let count = std::cmp::max(1, (msg.len() + MAX_FRAGMENT_PAYLOAD - 1) / MAX_FRAGMENT_PAYLOAD) as u16;
+        let msg_id = self.next_msg_id.fetch_add(1, Ordering::Relaxed);
+        let total_len = msg.len() as u32;
+
+        Ok((0..count).map(move |index| {
+            let start = index as usize * MAX_FRAGMENT_PAYLOAD;
+            let end = std::cmp::min(start + MAX_FRAGMENT_PAYLOAD, msg.len());
+            (FragmentHeader { msg_id, index, count, total_len, }, &msg[start..end])
+        }))
+    }
+
+    // Calls send with the attributes and the payload of each fragment of msg, in order. If send
+    // fails in the middle of a message, the fragments already sent are dropped on reception by
+    // the reassembly timeout.
+    pub fn send<E, F>(&self, msg: &[u8], attributes: &PacketAttributes, mut send: F) -> std::result::Result<(), E>
+        where E: From<Error>, F: FnMut(&PacketAttributes, &[u8]) -> std::result::Result<(), E> {
+        let mut fragment = [0u8; MAX_FRAGMENT_SIZE];
+        for (header, chunk) in self.split(msg)? {
+            let len = FRAGMENT_HEADER_SIZE + chunk.len();
+            header.write(&mut fragment);
+            fragment[FRAGMENT_HEADER_SIZE..len].copy_from_slice(chunk);
+            let attributes = PacketAttributes {
+                wire_size: header.wire_size(attributes.wire_size),
+                ..*attributes
+            };
+            send(&attributes, &fragment[..len])?;
+        }
+        Ok(())
+    }
+}
+
+// Fragmentation layer of a context having num_buffers buffers. A message may take at most a
+// quarter of the output buffers, and as many messages can be in reassembly at the same time.
+pub fn new_layer(num_buffers: usize, reassembly_timeout: Duration) -> (Fragmenter, Reassembler) {
+    let max_fragments = std::cmp::max(1, num_buffers / 4);
+    (Fragmenter::new(max_fragments), Reassembler::new(max_fragments, max_fragments, reassembly_timeout))
+}
+
+// A message made of all its fragments
+#[derive(Debug)]
+enum Content {
+    // Message sent in a single fragment, kept in its input buffer
+    Single(DeliverPacket),
+    // Payloads of the fragments copied in order
+    Assembled(Buffer<BytesBuffer>),
+}
+
+#[derive(Debug)]
+pub struct ReassembledMsg {
+    src: VsgAddress,
+    dst: VsgAddress,
+    attributes: PacketAttributes,
+    content: Content,
+}
+
+impl ReassembledMsg {
+    pub fn src(&self) -> VsgAddress {
+        self.src
+    }
+
+    pub fn dst(&self) -> VsgAddress {
+        self.dst
+    }
+
+    // All fragments are sent with the same attributes, but for their share of wire_size
+    pub fn attributes(&self) -> PacketAttributes {
+        self.attributes
+    }
+
+    fn payload(&self) -> &[u8] {
+        match &self.content {
+            Content::Single(packet) => &packet.payload()[FRAGMENT_HEADER_SIZE..],
+            Content::Assembled(buffer) => buffer,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.payload().len()
+    }
+
+    // Caller must make sure that buf is exactly ::len() bytes long
+    pub fn copy_to(&self, buf: &mut [u8]) {
+        buf.copy_from_slice(self.payload())
+    }
+}
+
+// Checks that the fragment described by header can be part of a message of at most max_fragments
+// fragments, and returns the position of its chunk in the message
+fn chunk_range(header: &FragmentHeader, chunk_len: usize, max_fragments: usize) -> Result<Range<usize>> {
+    let total_len = header.total_len as usize;
+    let count = std::cmp::max(1, (total_len + MAX_FRAGMENT_PAYLOAD - 1) / MAX_FRAGMENT_PAYLOAD);
+    if count > max_fragments {
+        return Err(Error::SizeTooBig);
+    }
+    if header.count as usize != count || header.index >= header.count {
+        return Err(Error::InvalidHeader);
+    }
+    let start = header.index as usize * MAX_FRAGMENT_PAYLOAD;
+    let end = std::cmp::min(start + MAX_FRAGMENT_PAYLOAD, total_len);
+    if chunk_len != end - start {
+        return Err(Error::InvalidHeader);
+    }
+    Ok(start..end)
+}
+
+#[derive(Debug)]
+struct ReassemblySlot {
+    // None if the slot is free
+    payload: Option<Buffer<BytesBuffer>>,
+    src: VsgAddress,
+    dst: VsgAddress,
+    attributes: PacketAttributes,
+    msg_id: u32,
+    count: u16,
+    received: u16,
+    // Arrival time of the first fragment, in global simulation time
+    first_seen: Duration,
+    // Indexed by fragment index, allocated once for the maximum number of fragments
+    fragments_seen: Vec<bool>,
+}
+
+impl ReassemblySlot {
+    fn new(max_fragments: usize) -> ReassemblySlot {
+        let mut fragments_seen = Vec::with_capacity(max_fragments);
+        fragments_seen.resize(max_fragments, false);
+
+        ReassemblySlot {
+            payload: None,
+            src: VsgAddress::V4(0),
+            dst: VsgAddress::V4(0),
+            attributes: PacketAttributes::default(),
+            msg_id: 0,
+            count: 0,
+            received: 0,
+            first_seen: Duration::new(0, 0),
+            fragments_seen,
+        }
+    }
+
+    fn is_free(&self) -> bool {
+        self.payload.is_none()
+    }
+
+    fn matches(&self, src: VsgAddress, header: &FragmentHeader) -> bool {
+        !self.is_free() && self.src == src && self.msg_id == header.msg_id
+    }
+
+    fn start(&mut self, packet: &DeliverPacket, header: &FragmentHeader, payload: Buffer<BytesBuffer>, now: Duration) {
+        self.payload = Some(payload);
+        self.src = packet.src();
+        self.dst = packet.dst();
+        self.attributes = PacketAttributes {
+            wire_size: 0,
+            ..packet.attributes()
+        };
+        self.msg_id = header.msg_id;
+        self.count = header.count;
+        self.received = 0;
+        self.first_seen = now;
+        self.fragments_seen[..header.count as usize].fill(false);
+    }
+
+    // Frees the slot
+    fn take_msg(&mut self) -> ReassembledMsg {
+        ReassembledMsg {
+            src: self.src,
+            dst: self.dst,
+            attributes: self.attributes,
+            content: Content::Assembled(self.payload.take().unwrap()),
+        }
+    }
+}
+
+// Outcome of Reassembler::push()
+#[derive(Debug)]
+pub struct Pushed {
+    // The complete message, if the packet was its last missing fragment
+    pub msg: Option<ReassembledMsg>,
+    // Incomplete messages dropped to make room for the message of the packet, 0 or 1
+    pub evicted: usize,
+}
+
+// Reassembles fragments on the receive path
+// No concurrency: (mut) accessed only by the deadline handler
+// Mutex is used to show interior mutability despite sharing.
+//
+// Fragments are copied in a buffer of the message as they arrive, so that incomplete messages do
+// not hold input buffers. All the memory is allocated by ::new(): max_slots messages of at most
+// max_fragments fragments can be in reassembly or waiting to be received by the application.
+//
+// Times are the global simulation times at which the actor delivers fragments, that is the
+// current deadline of the deadline handler.
+#[derive(Debug)]
+pub struct Reassembler {
+    slots: Mutex<Vec<ReassemblySlot>>,
+    buffer_pool: BufferPool<BytesBuffer>,
+    max_fragments: usize,
+    timeout: Duration,
+}
+
+impl Reassembler {
+    pub fn new(max_slots: usize, max_fragments: usize, timeout: Duration) -> Reassembler {
+        let mut slots = Vec::with_capacity(max_slots);
+        slots.resize_with(max_slots, || ReassemblySlot::new(max_fragments));
+
+        Reassembler {
+            slots: Mutex::new(slots),
+            buffer_pool: BufferPool::new(max_fragments * MAX_FRAGMENT_PAYLOAD, max_slots),
+            max_fragments,
+            timeout,
+        }
+    }
+
+    // Returns the complete message if packet was its last missing fragment. When all the slots
+    // are in use, the oldest incomplete message is dropped and counted in the result. Fragments
+    // whose header does not match their length or the other fragments of their message are
+    // rejected. now is the current global simulation time.
+    pub fn push(&self, packet: DeliverPacket, now: Duration) -> Result<Pushed> {
+        let header = FragmentHeader::read(packet.payload())?;
+        let chunk = &packet.payload()[FRAGMENT_HEADER_SIZE..];
+        let range = chunk_range(&header, chunk.len(), self.max_fragments)?;
+        let src = packet.src();
+
+        if header.count == 1 {
+            return Ok(Pushed {
+                msg: Some(ReassembledMsg {
+                    src,
+                    dst: packet.dst(),
+                    attributes: packet.attributes(),
+                    content: Content::Single(packet),
+                }),
+                evicted: 0,
+            });
+        }
+
+        let mut evicted = 0;
+        let mut slots = self.slots.lock().unwrap();
+        let slot_index = match slots.iter().position(|s| s.matches(src, &header)) {
+            Some(index) => index,
+            None => {
+                let index = match slots.iter().position(|s| s.is_free()) {
+                    Some(index) => index,
+                    None => {
+                        // Make room by dropping the oldest incomplete message
+                        let oldest = (0..slots.len()).min_by_key(|&i| slots[i].first_seen).ok_or(Error::NoSpace)?;
+                        let dropped = slots[oldest].take_msg();
+                        info!("Dropping incomplete message {} from {}: too many messages in reassembly", slots[oldest].msg_id, dropped.src);
+                        evicted += 1;
+                        oldest
+                    },
+                };
+                // Buffers of complete messages are released by the application
+                let payload = self.buffer_pool.allocate_buffer(header.total_len as usize).map_err(|_| Error::NoSpace)?;
+                slots[index].start(&packet, &header, payload, now);
+                index
+            },
+        };
+
+        let slot = &mut slots[slot_index];
+        if header.count != slot.count || header.total_len as usize != slot.payload.as_ref().unwrap().len() {
+            return Err(Error::InvalidHeader);
+        }
+        if !slot.fragments_seen[header.index as usize] {
+            slot.fragments_seen[header.index as usize] = true;
+            slot.received += 1;
+            slot.payload.as_mut().unwrap()[range].copy_from_slice(chunk);
+            slot.attributes.wire_size = slot.attributes.wire_size.saturating_add(packet.attributes().wire_size);
+        }
+
+        let msg = if slot.received == slot.count {
+            Some(slot.take_msg())
+        } else {
+            None
+        };
+        Ok(Pushed { msg, evicted })
+    }
+
+    // Drops incomplete messages whose first fragment arrived more than timeout ago, and returns
+    // how many were dropped
+    pub fn expire(&self, now: Duration) -> usize {
+        let timeout = self.timeout;
+        let mut dropped = 0;
+        for slot in self.slots.lock().unwrap().iter_mut() {
+            if !slot.is_free() && now.checked_sub(slot.first_seen).map_or(false, |elapsed| elapsed > timeout) {
+                info!("Dropping incomplete message {} from {}: reassembly timeout", slot.msg_id, slot.src);
+                slot.take_msg();
+                dropped += 1;
+            }
+        }
+        dropped
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::buffer_pool::BufferPool;
+    use crate::bytes_buffer::BytesBuffer;
+    use crate::connector::{MsgIn, create_deliver_packet};
+    use super::*;
+
+    fn make_packet(pool: &BufferPool<BytesBuffer>, src: u32, dst: u32, payload: &[u8]) -> DeliverPacket {
+        let mut builder = flatbuffers::FlatBufferBuilder::new();
+        create_deliver_packet(&mut builder, src, dst, payload);
+        let fb = builder.finished_data();
+        let mut buffer = pool.allocate_buffer(fb.len()).unwrap();
+        buffer.copy_from_slice(fb);
+        match MsgIn::new_deliver_packet(buffer).unwrap() {
+            MsgIn::DeliverPacket(d) => d,
+            _ => unreachable!(),
+        }
+    }
+
+    fn make_fragments(pool: &BufferPool<BytesBuffer>, fragmenter: &Fragmenter, msg: &[u8]) -> Vec<DeliverPacket> {
+        fragmenter.split(msg).unwrap().map(|(header, chunk)| {
+            let mut payload = vec![0u8; FRAGMENT_HEADER_SIZE + chunk.len()];
+            header.write(&mut payload);
+            payload[FRAGMENT_HEADER_SIZE..].copy_from_slice(chunk);
+            make_packet(pool, 1, 2, &payload)
+        }).collect()
+    }
+
+    #[test]
+    fn header_round_trip() {
+        let header = FragmentHeader { msg_id: 0xdeadbeef, index: 3, count: 5, total_len: 10000, };
+        let mut buf = [0u8; FRAGMENT_HEADER_SIZE];
+        header.write(&mut buf);
+        assert_eq!(header, FragmentHeader::read(&buf).unwrap());
+    }
+
+    #[test]
+    fn header_invalid() {
+        let header = FragmentHeader { msg_id: 0, index: 5, count: 5, total_len: 10000, };
+        let mut buf = [0u8; FRAGMENT_HEADER_SIZE];
+        header.write(&mut buf);
+        assert!(FragmentHeader::read(&buf).is_err());
+        assert!(FragmentHeader::read(&buf[..FRAGMENT_HEADER_SIZE - 1]).is_err());
+    }
+
+    #[test]
+    fn split_too_big() {
+        let fragmenter = Fragmenter::new(2);
+        let msg = vec![0u8; fragmenter.max_message_size() + 1];
+        assert!(fragmenter.split(&msg).is_err());
+    }
+
+    #[test]
+    fn send_fragments() {
+        let fragmenter = Fragmenter::new(10);
+        let msg: Vec<u8> = (0..(MAX_FRAGMENT_PAYLOAD + 10)).map(|i| i as u8).collect();
+        let attributes = PacketAttributes {
+            wire_size: 100_000,
+            ..Default::default()
+        };
+        let mut fragments = vec![];
+        fragmenter.send(&msg, &attributes, |attributes, fragment| -> Result<()> {
+            fragments.push((*attributes, fragment.to_vec()));
+            Ok(())
+        }).unwrap();
+
+        assert_eq!(2, fragments.len());
+        assert_eq!(attributes.wire_size, fragments.iter().map(|(attributes, _)| attributes.wire_size).sum());
+        let payload: Vec<u8> = fragments.iter().flat_map(|(_, fragment)| fragment[FRAGMENT_HEADER_SIZE..].to_vec()).collect();
+        assert_eq!(msg, payload);
+
+        let msg = vec![0u8; fragmenter.max_message_size() + 1];
+        assert!(fragmenter.send(&msg, &attributes, |_, _| -> Result<()> { Ok(()) }).is_err());
+    }
+
+    #[test]
+    fn wire_size_shares() {
+        let fragmenter = Fragmenter::new(10);
+        let msg = vec![0u8; 2 * MAX_FRAGMENT_PAYLOAD + 10];
+        let headers: Vec<FragmentHeader> = fragmenter.split(&msg).unwrap().map(|(header, _)| header).collect();
+
+        assert!(headers.iter().all(|header| header.wire_size(0) == 0));
+        let wire_size = 100_000;
+        let shares: Vec<u32> = headers.iter().map(|header| header.wire_size(wire_size)).collect();
+        assert_eq!(wire_size, shares.iter().sum());
+        assert!(shares[0] > 10 * shares[2]);
+        // Never 0
+        assert!(headers.iter().all(|header| header.wire_size(1) == 1));
+    }
+
+    #[test]
+    fn reassemble_out_of_order() {
+        let pool = BufferPool::<BytesBuffer>::new(crate::connector::INPUT_BUFFER_SIZE, 10);
+        let fragmenter = Fragmenter::new(10);
+        let reassembler = Reassembler::new(2, 10, Duration::from_secs(1));
+        let msg: Vec<u8> = (0..(3 * MAX_FRAGMENT_PAYLOAD + 10)).map(|i| i as u8).collect();
+
+        let mut fragments = make_fragments(&pool, &fragmenter, &msg);
+        assert_eq!(4, fragments.len());
+        fragments.reverse();
+
+        let last = fragments.pop().unwrap();
+        for fragment in fragments {
+            assert!(reassembler.push(fragment, Duration::from_micros(100)).unwrap().msg.is_none());
+        }
+        let reassembled = reassembler.push(last, Duration::from_micros(200)).unwrap().msg.unwrap();
+
+        assert_eq!(VsgAddress::V4(1), reassembled.src());
+        assert_eq!(msg.len(), reassembled.len());
+        let mut buf = vec![0u8; reassembled.len()];
+        reassembled.copy_to(&mut buf);
+        assert_eq!(msg, buf);
+    }
+
+    #[test]
+    fn reassembly_timeout() {
+        let pool = BufferPool::<BytesBuffer>::new(crate::connector::INPUT_BUFFER_SIZE, 10);
+        let fragmenter = Fragmenter::new(10);
+        let reassembler = Reassembler::new(2, 10, Duration::from_micros(100));
+        let msg = vec![1u8; 2 * MAX_FRAGMENT_PAYLOAD];
+
+        let mut fragments = make_fragments(&pool, &fragmenter, &msg);
+        let last = fragments.pop().unwrap();
+        assert!(reassembler.push(fragments.pop().unwrap(), Duration::from_micros(100)).unwrap().msg.is_none());
+
+        assert_eq!(1, reassembler.expire(Duration::from_micros(201)));
+        // The first fragment is gone, so this one starts a new message
+        assert!(reassembler.push(last, Duration::from_micros(300)).unwrap().msg.is_none());
+    }
+
+    #[test]
+    fn reassembly_eviction() {
+        let pool = BufferPool::<BytesBuffer>::new(crate::connector::INPUT_BUFFER_SIZE, 10);
+        let fragmenter = Fragmenter::new(10);
+        let reassembler = Reassembler::new(1, 10, Duration::from_secs(1));
+        let msg = vec![1u8; 2 * MAX_FRAGMENT_PAYLOAD];
+
+        let mut first = make_fragments(&pool, &fragmenter, &msg);
+        let mut second = make_fragments(&pool, &fragmenter, &msg);
+        let pushed = reassembler.push(first.remove(0), Duration::from_micros(100)).unwrap();
+        assert_eq!((true, 0), (pushed.msg.is_none(), pushed.evicted));
+        // The first message is dropped to make room for the second one
+        let pushed = reassembler.push(second.remove(0), Duration::from_micros(200)).unwrap();
+        assert_eq!((true, 1), (pushed.msg.is_none(), pushed.evicted));
+        let pushed = reassembler.push(second.remove(0), Duration::from_micros(200)).unwrap();
+        assert_eq!((false, 0), (pushed.msg.is_none(), pushed.evicted));
+    }
+
+    #[test]
+    fn reassembly_rejects_corrupt_header() {
+        let pool = BufferPool::<BytesBuffer>::new(crate::connector::INPUT_BUFFER_SIZE, 10);
+        let fragmenter = Fragmenter::new(10);
+        let reassembler = Reassembler::new(2, 10, Duration::from_secs(1));
+        let msg = vec![1u8; 2 * MAX_FRAGMENT_PAYLOAD + 10];
+        let now = Duration::from_micros(100);
+
+        let corrupt = |fragment: &DeliverPacket, f: &dyn Fn(&mut FragmentHeader)| {
+            let mut payload = fragment.payload().to_vec();
+            let mut header = FragmentHeader::read(&payload).unwrap();
+            f(&mut header);
+            header.write(&mut payload);
+            make_packet(&pool, 1, 2, &payload)
+        };
+        let fragments = make_fragments(&pool, &fragmenter, &msg);
+        // Chunks not adding up to total_len
+        let packet = corrupt(&fragments[2], &|h| h.total_len += 1);
+        assert!(reassembler.push(packet, now).is_err());
+        let packet = corrupt(&fragments[2], &|h| h.total_len = 100);
+        assert!(reassembler.push(packet, now).is_err());
+        // Index out of the message
+        let packet = corrupt(&fragments[1], &|h| h.count = 2);
+        assert!(reassembler.push(packet, now).is_err());
+        let packet = corrupt(&fragments[1], &|h| h.index = 3);
+        assert!(reassembler.push(packet, now).is_err());
+        // Too many fragments
+        let packet = corrupt(&fragments[0], &|h| { h.count = 11; h.total_len = 11 * MAX_FRAGMENT_PAYLOAD as u32; });
+        assert!(reassembler.push(packet, now).is_err());
+
+        // Not matching the first fragment
+        let mut fragments = fragments.into_iter();
+        assert!(reassembler.push(fragments.next().unwrap(), now).unwrap().msg.is_none());
+        let other = make_fragments(&pool, &Fragmenter::new(10), &msg[..MAX_FRAGMENT_PAYLOAD + 1]);
+        assert!(reassembler.push(other.into_iter().nth(1).unwrap(), now).is_err());
+
+        // The message is still complete with its genuine fragments
+        assert!(reassembler.push(fragments.next().unwrap(), now).unwrap().msg.is_none());
+        let reassembled = reassembler.push(fragments.next().unwrap(), now).unwrap().msg.unwrap();
+        let mut buf = vec![0u8; reassembled.len()];
+        reassembled.copy_to(&mut buf);
+        assert_eq!(msg, buf);
+    }
+
+    #[test]
+    fn fragment_fits() {
+        use crate::buffer_pool::BufferPool;
+        use crate::connector::{FbBuffer, SendPacketBuilder};
+
+        let src = VsgAddress::from_ipv6([0xfe; 16]);
+        let dst = VsgAddress::from_ipv6([0xff; 16]);
+        let attributes = PacketAttributes {
+            protocol: 17,
+            src_port: 1234,
+            dst_port: 53,
+            priority: 46,
+            flow_id: 42,
+            wire_size: std::u32::MAX,
+        };
+        let fragment = [0u8; MAX_FRAGMENT_SIZE];
+
+        let fb_pool = BufferPool::<FbBuffer>::new(crate::MAX_PACKET_SIZE, 1);
+        let buffer = fb_pool.allocate_buffer(0).unwrap();
+        let time = Duration::new(std::u64::MAX, 999_999_999);
+        let send_packet = SendPacketBuilder::new(src, dst, attributes, std::u64::MAX, time, &fragment, buffer).unwrap().finish(time);
+        let framing = send_packet.finished_data().len() - flatbuffers::SIZE_SIZEPREFIX - MAX_FRAGMENT_SIZE;
+        assert!(framing < 160, "SendPacket framing is {} bytes", framing);
+
+        let mut builder = flatbuffers::FlatBufferBuilder::new();
+        crate::connector::create_deliver_packet_with_attributes(&mut builder, src, dst, &attributes, &fragment);
+        let framing = builder.finished_data().len() - flatbuffers::SIZE_SIZEPREFIX - MAX_FRAGMENT_SIZE;
+        assert!(framing < 160, "DeliverPacket framing is {} bytes", framing);
+    }
+}
@@ -0,0 +1,146 @@
+use crate::vsg_address::VsgAddress;
+use crate::waitfree_array_queue::WaitfreeArrayQueue;
+use std::fmt;
+use std::sync::Mutex;
+
+#[derive(Debug)]
+pub enum Error {
+    NotMulticast,
+    NoSlotAvailable,
+}
+
+type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let msg = match self {
+            Error::NotMulticast => "Not a multicast address",
+            Error::NoSlotAvailable => "No slot available",
+        };
+        write!(f, "{}", msg)
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MembershipChange {
+    Join(VsgAddress),
+    Leave(VsgAddress),
+}
+
+// Multicast groups joined by a context
+//
+// Membership changes requested by application code only take effect at the next deadline, when
+// they are also reported to the actor. This keeps the deadline handler the only user of the
+// current membership.
+#[derive(Debug)]
+pub struct GroupSet {
+    // Concurrency: Changes are:
+    // - pushed to the queue by application code,
+    // - popped from the queue by the deadline handler.
+    // Concurrent read-write support is provided by interior mutability.
+    pending: WaitfreeArrayQueue<MembershipChange>,
+    // No concurrency: (mut) accessed only by the deadline handler
+    // Mutex is used to show interior mutability despite sharing.
+    joined: Mutex<Vec<VsgAddress>>,
+}
+
+impl GroupSet {
+    pub fn new(max_pending: usize) -> GroupSet {
+        GroupSet {
+            pending: WaitfreeArrayQueue::new(max_pending),
+            joined: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn join(&self, group: VsgAddress) -> Result<()> {
+        self.request(MembershipChange::Join(group), group)
+    }
+
+    pub fn leave(&self, group: VsgAddress) -> Result<()> {
+        self.request(MembershipChange::Leave(group), group)
+    }
+
+    fn request(&self, change: MembershipChange, group: VsgAddress) -> Result<()> {
+        if !group.is_multicast() {
+            return Err(Error::NotMulticast);
+        }
+        self.pending.push(change).or(Err(Error::NoSlotAvailable))
+    }
+
+    // Applies pending changes and calls f for each change that modified the membership
+    pub fn apply_pending<F>(&self, mut f: F)
+        where F: FnMut(MembershipChange) {
+        let mut joined = self.joined.lock().unwrap();
+        while let Some(change) = self.pending.pop() {
+            let changed = match change {
+                MembershipChange::Join(group) => {
+                    if joined.contains(&group) {
+                        false
+                    } else {
+                        joined.push(group);
+                        true
+                    }
+                },
+                MembershipChange::Leave(group) => {
+                    let len = joined.len();
+                    joined.retain(|g| *g != group);
+                    joined.len() != len
+                },
+            };
+            if changed {
+                f(change);
+            }
+        }
+    }
+
+    // Whether a packet sent to dst must be delivered to this context
+    pub fn accepts(&self, dst: VsgAddress) -> bool {
+        if dst.is_multicast() {
+            self.joined.lock().unwrap().contains(&dst)
+        } else {
+            true
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::Ipv4Addr;
+    use super::*;
+
+    fn ipv4(a: u8, b: u8, c: u8, d: u8) -> VsgAddress {
+        VsgAddress::V4(u32::from(Ipv4Addr::new(a, b, c, d)).to_be())
+    }
+
+    #[test]
+    fn join_not_multicast() {
+        let groups = GroupSet::new(10);
+        assert!(groups.join(ipv4(10, 0, 0, 1)).is_err());
+        assert!(groups.join(ipv4(255, 255, 255, 255)).is_err());
+    }
+
+    #[test]
+    fn join_leave() {
+        let groups = GroupSet::new(10);
+        let group = ipv4(239, 0, 0, 1);
+        let mut changes = Vec::new();
+
+        groups.join(group).unwrap();
+        groups.join(group).unwrap();
+        assert!(!groups.accepts(group));
+        groups.apply_pending(|c| changes.push(c));
+        assert_eq!(vec![MembershipChange::Join(group)], changes);
+        assert!(groups.accepts(group));
+        assert!(!groups.accepts(ipv4(239, 0, 0, 2)));
+        assert!(groups.accepts(ipv4(255, 255, 255, 255)));
+        assert!(groups.accepts(ipv4(10, 0, 0, 1)));
+
+        changes.clear();
+        groups.leave(group).unwrap();
+        groups.apply_pending(|c| changes.push(c));
+        assert_eq!(vec![MembershipChange::Leave(group)], changes);
+        assert!(!groups.accepts(group));
+    }
+}
@@ -0,0 +1,99 @@
+use crate::callback_slot::CallbackSlot;
+use crate::output_msg_set::OutputMsg;
+use crate::packet_attributes::PacketAttributes;
+use crate::state::SavedInput;
+use crate::vsg_address::VsgAddress;
+use crate::waitfree_array_queue::WaitfreeArrayQueue;
+use crate::TapCallback;
+use std::fmt;
+use std::time::Duration;
+
+#[derive(Debug)]
+pub enum Error {
+    NoSlotAvailable
+}
+
+type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let msg = match self {
+            Error::NoSlotAvailable => "No slot available",
+        };
+        write!(f, "{}", msg)
+    }
+}
+
+impl std::error::Error for Error {}
+
+// Hooks for test harnesses: packets injected as if the simulator had delivered them, and a tap on
+// outgoing packets
+//
+// Concurrency:
+// - injected packets are pushed by application code and popped by the deadline handler,
+// - the tap callback is set by application code and called by the deadline handler.
+// WaitfreeArrayQueue and CallbackSlot use interior mutability.
+#[derive(Debug)]
+pub struct Harness {
+    injected: WaitfreeArrayQueue<SavedInput>,
+    tap_callback: CallbackSlot<TapCallback>,
+}
+
+impl Harness {
+    pub fn new(num_slots: usize) -> Harness {
+        Harness {
+            injected: WaitfreeArrayQueue::new(num_slots),
+            tap_callback: CallbackSlot::new(),
+        }
+    }
+
+    pub fn inject(&self, src: VsgAddress, dst: VsgAddress, payload: &[u8]) -> Result<()> {
+        let input = SavedInput {
+            src,
+            dst,
+            attributes: PacketAttributes::default(),
+            payload: payload.to_vec(),
+        };
+        self.injected.push(input).map_err(|_| Error::NoSlotAvailable)
+    }
+
+    // Called by the deadline handler with the injected packets, in order
+    pub fn drain_injected(&self, mut deliver: impl FnMut(SavedInput)) {
+        while let Some(input) = self.injected.pop() {
+            deliver(input);
+        }
+    }
+
+    pub fn set_tap_callback(&self, tap_callback: Option<TapCallback>) {
+        self.tap_callback.set(tap_callback)
+    }
+
+    // Called by the deadline handler with each packet just before it is sent to the simulator
+    pub fn tap(&self, send_time: Duration, msg: &OutputMsg) {
+        if self.tap_callback.is_set() {
+            self.tap_callback.call(|tap_callback| tap_callback(send_time, msg.src(), msg.dst(), msg.payload()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::vsg_address::VsgAddress;
+    use super::Harness;
+
+    #[test]
+    fn inject() {
+        let harness = Harness::new(2);
+        let (src, dst) = (VsgAddress::V4(1), VsgAddress::V4(2));
+        harness.inject(src, dst, b"first").unwrap();
+        harness.inject(src, dst, b"second").unwrap();
+        assert!(harness.inject(src, dst, b"third").is_err());
+
+        let mut payloads = vec![];
+        harness.drain_injected(|input| {
+            assert_eq!((src, dst), (input.src, input.dst));
+            payloads.push(input.payload);
+        });
+        assert_eq!(vec![b"first".to_vec(), b"second".to_vec()], payloads);
+    }
+}
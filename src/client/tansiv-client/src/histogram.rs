@@ -0,0 +1,171 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+// Log-linear buckets, as in HDR histograms: values below SUB_BUCKETS have their own bucket, then
+// each power of two is split in SUB_BUCKETS / 2 buckets. The relative error is below 2 /
+// SUB_BUCKETS.
+const SUB_BUCKET_BITS: u32 = 5;
+const SUB_BUCKETS: u64 = 1 << SUB_BUCKET_BITS;
+const HALF_SUB_BUCKETS: u64 = SUB_BUCKETS / 2;
+const NUM_BUCKETS: usize = ((64 - SUB_BUCKET_BITS as u64 + 1) * HALF_SUB_BUCKETS + HALF_SUB_BUCKETS) as usize;
+
+fn bucket_index(value: u64) -> usize {
+    if value < SUB_BUCKETS {
+        value as usize
+    } else {
+        let shift = (64 - value.leading_zeros()) - SUB_BUCKET_BITS;
+        (shift as u64 * HALF_SUB_BUCKETS + (value >> shift)) as usize
+    }
+}
+
+// Lowest value and highest value (excluded) of a bucket. The highest value saturates.
+fn bucket_range(index: usize) -> (u64, u64) {
+    let index = index as u64;
+    if index < SUB_BUCKETS {
+        (index, index + 1)
+    } else {
+        let shift = index / HALF_SUB_BUCKETS - 1;
+        let top = index % HALF_SUB_BUCKETS + HALF_SUB_BUCKETS;
+        (top << shift, (top + 1).checked_shl(shift as u32).filter(|high| *high > top << shift).unwrap_or(u64::MAX))
+    }
+}
+
+// Histogram of durations in nanoseconds
+//
+// Recording is lock-free and async-signal-safe, so that the deadline handler can record values
+// while application code takes snapshots.
+pub struct Histogram {
+    buckets: Vec<AtomicU64>,
+    count: AtomicU64,
+    sum: AtomicU64,
+    min: AtomicU64,
+    max: AtomicU64,
+}
+
+impl std::fmt::Debug for Histogram {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Histogram {{ count: {:?} }}", self.count)
+    }
+}
+
+impl Histogram {
+    pub fn new() -> Histogram {
+        let mut buckets: Vec<AtomicU64> = Vec::with_capacity(NUM_BUCKETS);
+        buckets.resize_with(NUM_BUCKETS, Default::default);
+
+        Histogram {
+            buckets,
+            count: AtomicU64::new(0),
+            sum: AtomicU64::new(0),
+            min: AtomicU64::new(u64::MAX),
+            max: AtomicU64::new(0),
+        }
+    }
+
+    pub fn record(&self, value: Duration) {
+        let value = std::cmp::min(value.as_nanos(), u64::MAX as u128) as u64;
+        self.buckets[bucket_index(value)].fetch_add(1, Ordering::Relaxed);
+        self.sum.fetch_add(value, Ordering::Relaxed);
+        self.min.fetch_min(value, Ordering::Relaxed);
+        self.max.fetch_max(value, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // Values recorded concurrently may be partially accounted for
+    pub fn snapshot(&self) -> HistogramSnapshot {
+        let buckets = self.buckets.iter().enumerate().filter_map(|(index, bucket)| {
+            match bucket.load(Ordering::Relaxed) {
+                0 => None,
+                count => {
+                    let (low, high) = bucket_range(index);
+                    Some(HistogramBucket {
+                        low: Duration::from_nanos(low),
+                        high: Duration::from_nanos(high),
+                        count,
+                    })
+                },
+            }
+        }).collect();
+        let count = self.count.load(Ordering::Relaxed);
+
+        HistogramSnapshot {
+            count,
+            sum: Duration::from_nanos(self.sum.load(Ordering::Relaxed)),
+            min: if count > 0 { Duration::from_nanos(self.min.load(Ordering::Relaxed)) } else { Duration::from_nanos(0) },
+            max: Duration::from_nanos(self.max.load(Ordering::Relaxed)),
+            buckets,
+        }
+    }
+}
+
+// Values from low (included) to high (excluded)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HistogramBucket {
+    pub low: Duration,
+    pub high: Duration,
+    pub count: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HistogramSnapshot {
+    pub count: u64,
+    pub sum: Duration,
+    pub min: Duration,
+    pub max: Duration,
+    // Non-empty buckets only, in increasing order
+    pub buckets: Vec<HistogramBucket>,
+}
+
+impl HistogramSnapshot {
+    // Upper bound of the bucket containing the given quantile (0.0 to 1.0), capped by the maximum
+    // value. Returns 0 if no value was recorded.
+    pub fn quantile(&self, quantile: f64) -> Duration {
+        let rank = (quantile * self.count as f64).ceil() as u64;
+        let mut seen = 0;
+        for bucket in &self.buckets {
+            seen += bucket.count;
+            if seen >= rank {
+                return std::cmp::min(bucket.high, self.max);
+            }
+        }
+        self.max
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+    use super::{bucket_index, bucket_range, Histogram, NUM_BUCKETS};
+
+    #[test]
+    fn buckets() {
+        let mut prev_high = 0;
+        for index in 0..NUM_BUCKETS {
+            let (low, high) = bucket_range(index);
+            assert_eq!(prev_high, low);
+            assert_eq!(index, bucket_index(low));
+            assert_eq!(index, bucket_index(high - 1));
+            prev_high = high;
+        }
+        assert_eq!(u64::MAX, prev_high);
+        assert_eq!(NUM_BUCKETS - 1, bucket_index(u64::MAX));
+    }
+
+    #[test]
+    fn record() {
+        let histogram = Histogram::new();
+        for micros in 1..=100 {
+            histogram.record(Duration::from_micros(micros));
+        }
+
+        let snapshot = histogram.snapshot();
+        assert_eq!(100, snapshot.count);
+        assert_eq!(Duration::from_micros(5050), snapshot.sum);
+        assert_eq!(Duration::from_micros(1), snapshot.min);
+        assert_eq!(Duration::from_micros(100), snapshot.max);
+        assert_eq!(100, snapshot.buckets.iter().map(|bucket| bucket.count).sum::<u64>());
+        let median = snapshot.quantile(0.5);
+        assert!((Duration::from_micros(50)..=Duration::from_micros(53)).contains(&median));
+        assert_eq!(Duration::from_micros(100), snapshot.quantile(1.0));
+    }
+}
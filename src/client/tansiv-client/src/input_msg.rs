@@ -0,0 +1,95 @@
+use crate::buffer_pool::{self, Buffer, BufferPool};
+use crate::bytes_buffer::BytesBuffer;
+use crate::connector::DeliverPacket;
+use crate::fragment::ReassembledMsg;
+use crate::packet_attributes::PacketAttributes;
+use crate::state::SavedInput;
+use crate::vsg_address::VsgAddress;
+
+// Messages waiting in the input queue of a context
+#[derive(Debug)]
+pub enum InputMsg {
+    Packet(DeliverPacket),
+    Reassembled(ReassembledMsg),
+    // Restored by Context::load_state()
+    Restored(SavedInput),
+    // Injected by Context::inject_delivery()
+    Injected(SavedInput),
+    // Copied by fault injection
+    Copied(CopiedMsg),
+}
+
+impl InputMsg {
+    pub fn src(&self) -> VsgAddress {
+        match self {
+            InputMsg::Packet(p) => p.src(),
+            InputMsg::Reassembled(m) => m.src(),
+            InputMsg::Restored(m) | InputMsg::Injected(m) => m.src,
+            InputMsg::Copied(m) => m.src,
+        }
+    }
+
+    pub fn dst(&self) -> VsgAddress {
+        match self {
+            InputMsg::Packet(p) => p.dst(),
+            InputMsg::Reassembled(m) => m.dst(),
+            InputMsg::Restored(m) | InputMsg::Injected(m) => m.dst,
+            InputMsg::Copied(m) => m.dst,
+        }
+    }
+
+    pub fn attributes(&self) -> PacketAttributes {
+        match self {
+            InputMsg::Packet(p) => p.attributes(),
+            InputMsg::Reassembled(m) => m.attributes(),
+            InputMsg::Restored(m) | InputMsg::Injected(m) => m.attributes,
+            InputMsg::Copied(m) => m.attributes,
+        }
+    }
+
+    pub fn payload_len(&self) -> usize {
+        match self {
+            InputMsg::Packet(p) => p.payload().len(),
+            InputMsg::Reassembled(m) => m.len(),
+            InputMsg::Restored(m) | InputMsg::Injected(m) => m.payload.len(),
+            InputMsg::Copied(m) => m.payload.len(),
+        }
+    }
+
+    // Caller must make sure that buf is exactly ::payload_len() bytes long
+    pub fn copy_payload(&self, buf: &mut [u8]) {
+        match self {
+            InputMsg::Packet(p) => buf.copy_from_slice(p.payload()),
+            InputMsg::Reassembled(m) => m.copy_to(buf),
+            InputMsg::Restored(m) | InputMsg::Injected(m) => buf.copy_from_slice(&m.payload),
+            InputMsg::Copied(m) => buf.copy_from_slice(&m.payload),
+        }
+    }
+}
+
+// Copy of a received message in a preallocated buffer, so that the deadline handler can modify or
+// duplicate it without allocating
+#[derive(Debug)]
+pub struct CopiedMsg {
+    src: VsgAddress,
+    dst: VsgAddress,
+    attributes: PacketAttributes,
+    payload: Buffer<BytesBuffer>,
+}
+
+impl CopiedMsg {
+    pub fn new(msg: &InputMsg, buffer_pool: &BufferPool<BytesBuffer>) -> Result<CopiedMsg, buffer_pool::Error> {
+        let mut payload = buffer_pool.allocate_buffer(msg.payload_len())?;
+        msg.copy_payload(&mut payload);
+        Ok(CopiedMsg {
+            src: msg.src(),
+            dst: msg.dst(),
+            attributes: msg.attributes(),
+            payload,
+        })
+    }
+
+    pub fn payload_mut(&mut self) -> &mut [u8] {
+        &mut self.payload
+    }
+}
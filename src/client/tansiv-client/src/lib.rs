@@ -1,29 +1,77 @@
 use buffer_pool::BufferPool;
+use callback_slot::CallbackSlot;
+use compute::{ExecuteRequest, Executor};
 pub(crate) use config::Config;
-use connector::{Connector, ConnectorImpl, DeliverPacket, FbBuffer, MsgIn, MsgOut};
+use connector::{ConnectionStateHandle, Connector, ConnectorImpl, FbBuffer, MsgIn, MsgOut};
+use disk::DiskRequest;
+pub use disk::{DiskCompletion, DiskOp, MAX_DISK_NAME_LEN};
 pub use error::Error;
+use fault::FaultLayer;
+use flusher::Flusher;
+use fragment::{Fragmenter, Reassembler, FRAGMENT_HEADER_SIZE};
+use groups::GroupSet;
+use harness::Harness;
+pub use histogram::{HistogramBucket, HistogramSnapshot};
+use input_msg::InputMsg;
+use marker::Marker;
+use nic::Nic;
 use libc;
 #[allow(unused_imports)]
 use log::{debug, info, error};
 use output_msg_set::{OutputMsgSet, OutputMsg};
+pub use packet_attributes::PacketAttributes;
+pub use report::{DeliveryReport, DropReason};
+use stats::{Counters, LocalDrop};
+pub use stats::Stats;
+pub use termination::TerminationReason;
+use state::{SavedPacket, State};
 use std::collections::VecDeque;
 use std::sync::{Arc, Mutex, Once};
-use std::time::Duration;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering};
+use std::time::{Duration, Instant};
 use timer::TimerContext;
-use waitfree_array_queue::WaitfreeArrayQueue;
+use timing::DeadlineTiming;
+use trace::{TraceKind, Tracer};
+pub use timing::TimingReport;
+pub use vsg_address::VsgAddress;
+use waitfree_array_queue::{PeekableQueue, WaitfreeArrayQueue};
 
 pub const MAX_PACKET_SIZE: usize = 2048;
 
+const NO_IDLE_HINT: u64 = std::u64::MAX;
+
 mod buffer_pool;
 mod bytes_buffer;
+mod callback_slot;
+mod compute;
 mod config;
 mod connector;
+mod control;
 #[macro_use]
 mod debug;
+mod disk;
 pub mod error;
+mod fault;
 mod flatbuilder_buffer;
+mod flusher;
+mod fragment;
+mod groups;
+mod harness;
+mod histogram;
+mod input_msg;
+mod log_ring;
+mod marker;
+mod nic;
 mod output_msg_set;
+mod packet_attributes;
+mod report;
+mod short_str;
+mod state;
+mod stats;
+mod termination;
 mod timer;
+mod timing;
+mod trace;
 mod vsg_address;
 mod waitfree_array_queue;
 
@@ -36,6 +84,70 @@ impl From<buffer_pool::Error> for Error {
     }
 }
 
+impl From<compute::Error> for Error {
+    fn from(error: compute::Error) -> Error {
+        match error {
+            compute::Error::SimulationEnded => Error::SimulationEnded,
+            compute::Error::NoSlotAvailable => Error::NoMemoryAvailable,
+            compute::Error::IoError(e) => Error::IoError(e),
+        }
+    }
+}
+
+impl From<disk::Error> for Error {
+    fn from(error: disk::Error) -> Error {
+        match error {
+            disk::Error::NameTooLong => Error::SizeTooBig,
+        }
+    }
+}
+
+impl From<fragment::Error> for Error {
+    fn from(error: fragment::Error) -> Error {
+        match error {
+            fragment::Error::InvalidHeader => Error::ProtocolViolation,
+            fragment::Error::NoSpace => Error::NoMemoryAvailable,
+            fragment::Error::SizeTooBig => Error::SizeTooBig,
+        }
+    }
+}
+
+impl From<groups::Error> for Error {
+    fn from(error: groups::Error) -> Error {
+        match error {
+            groups::Error::NotMulticast => Error::InvalidAddress,
+            groups::Error::NoSlotAvailable => Error::NoMemoryAvailable,
+        }
+    }
+}
+
+impl From<short_str::Error> for Error {
+    fn from(error: short_str::Error) -> Error {
+        match error {
+            short_str::Error::TooLong => Error::SizeTooBig,
+        }
+    }
+}
+
+impl From<state::Error> for Error {
+    fn from(error: state::Error) -> Error {
+        match error {
+            state::Error::BadMagic => Error::InvalidState,
+            state::Error::UnsupportedVersion(_) => Error::InvalidState,
+            state::Error::Truncated => Error::InvalidState,
+            state::Error::InvalidTime => Error::InvalidState,
+        }
+    }
+}
+
+impl From<harness::Error> for Error {
+    fn from(error: harness::Error) -> Error {
+        match error {
+            harness::Error::NoSlotAvailable => Error::NoMemoryAvailable,
+        }
+    }
+}
+
 impl From<output_msg_set::Error> for Error {
     fn from(error: output_msg_set::Error) -> Error {
         match error {
@@ -48,21 +160,31 @@ pub type Result<T> = std::result::Result<T, Error>;
 
 pub type RecvCallback = Box<dyn Fn() -> () + Send + Sync>;
 pub type DeadlineCallback = Box<dyn Fn(Duration) -> () + Send + Sync>;
+pub type ReportCallback = Box<dyn Fn(DeliveryReport) + Send + Sync>;
+pub type LinkStateCallback = Box<dyn Fn(VsgAddress, bool) + Send + Sync>;
+pub type DiskCallback = Box<dyn Fn(DiskCompletion) + Send + Sync>;
+pub type EndCallback = Box<dyn Fn(TerminationReason) + Send + Sync>;
+// Send time, source, destination and payload of an outgoing packet
+pub type TapCallback = Box<dyn Fn(Duration, VsgAddress, VsgAddress, &[u8]) + Send + Sync>;
 
 // Context must be accessed concurrently from application code and the deadline handler. To
 // enable this, all fields are either read-only or implement thread and signal handler-safe
 // interior mutability.
 pub struct Context {
     // Read-only
-    address: libc::in_addr_t,
-    // No concurrency: (mut) accessed only by the deadline handler
-    // Mutex is used to show interior mutability despite sharing.
+    // The first address is the default source address
+    addresses: Vec<VsgAddress>,
+    // Concurrency: (mut) accessed by the deadline handler and the flusher
+    // The flusher runs with the deadline signal blocked, so the deadline handler cannot interrupt
+    // it while it holds the lock.
     connector: Mutex<ConnectorImpl>,
+    // Concurrency: written by the connector, read by anyone
+    connection_state: ConnectionStateHandle,
     // Concurrency: Messages are:
     // - pushed to the queue by the deadline handler,
     // - popped from the queue by application code.
     // Concurrent read-write support is provided by interior mutability.
-    input_queue: WaitfreeArrayQueue<DeliverPacket>,
+    input_queue: PeekableQueue<InputMsg>,
     // No concurrency, read-only: called only by the deadline handler
     recv_callback: RecvCallback,
     // No concurrency, read-only: called only by ::start() and the deadline handler
@@ -73,41 +195,163 @@ pub struct Context {
     timer_context: TimerContext,
     // Concurrency: Buffers are:
     // - allocated and added to the set by application code,
-    // - consumed and freed by the deadline handler.
+    // - consumed and freed by the deadline handler and the flusher, which hold connector.
     // BufferPool uses interior mutability for concurrent allocation and freeing of buffers.
     output_buffer_pool: BufferPool<FbBuffer>,
     outgoing_messages: OutputMsgSet,
     upcoming_messages: Mutex<VecDeque<OutputMsg>>,
+    // Optional fragmentation layer
+    // Concurrency:
+    // - fragmenter is used by application code only, with interior mutability,
+    // - reassembler is used by the deadline handler only, with interior mutability.
+    fragmenter: Option<Fragmenter>,
+    reassembler: Option<Reassembler>,
+    // Concurrency:
+    // - membership changes are requested by application code,
+    // - membership changes are applied and used by the deadline handler.
+    // GroupSet uses interior mutability.
+    groups: GroupSet,
+    // Sequence ids of packets and disk requests
+    // Concurrency: used by application code only, atomic
+    next_seq_id: AtomicU64,
+    // Concurrency: Requests are:
+    // - pushed to the queue by application code,
+    // - popped from the queue by the deadline handler.
+    disk_requests: WaitfreeArrayQueue<DiskRequest>,
+    // Concurrency: Markers are:
+    // - pushed to the queue by application code,
+    // - popped from the queue by the deadline handler.
+    markers: WaitfreeArrayQueue<Marker>,
+    // Concurrency: used by application code and the deadline handler. Harness uses interior
+    // mutability.
+    harness: Harness,
+    // Concurrency: computations are requested by application code and completed by the deadline
+    // handler. Executor uses interior mutability.
+    executor: Executor,
+    // Concurrency:
+    // - written by application code,
+    // - read by the deadline handler.
+    // Simulation time in nanoseconds, or NO_IDLE_HINT
+    idle_until: AtomicU64,
+    // Concurrency:
+    // - set by application code,
+    // - called by the deadline handler.
+    // CallbackSlot uses interior mutability.
+    report_callback: CallbackSlot<ReportCallback>,
+    link_state_callback: CallbackSlot<LinkStateCallback>,
+    disk_callback: CallbackSlot<DiskCallback>,
+    end_callback: CallbackSlot<EndCallback>,
+    // Concurrency:
+    // - written by the deadline handler,
+    // - read by application code.
+    // TerminationReason::to_raw(), or 0 while the simulation runs
+    termination_reason: AtomicU8,
+    // Concurrency:
+    // - written by ::start(), ::load_state() and the deadline handler,
+    // - read by the control socket thread and the flusher.
+    // Last deadline handled and next deadline, in nanoseconds of simulation time
+    last_deadline: AtomicU64,
+    next_deadline: AtomicU64,
+    // Concurrency: updated by application code and the deadline handler, read by application code.
+    // Counters uses interior mutability.
+    counters: Counters,
+    // Concurrency:
+    // - recorded by the deadline handler and the timer backend,
+    // - read by application code.
+    // DeadlineTiming uses interior mutability.
+    deadline_timing: DeadlineTiming,
+    // Optional NIC model
+    // Concurrency: used by application code, the deadline handler and the flusher, Nic uses
+    // interior mutability
+    nic: Option<Nic>,
+    // Optional fault injection
+    // Concurrency: used by the deadline handler and the flusher, which hold connector. FaultLayer
+    // uses interior mutability.
+    faults: Option<FaultLayer>,
+    // Optional timeline
+    // Concurrency: events are recorded by application code, the deadline handler and the flusher.
+    // Tracer uses interior mutability.
+    tracer: Option<Tracer>,
+    // Optional early streaming of outgoing packets
+    // Concurrency: woken up by application code
+    flusher: Option<Flusher>,
     // Concurrency: none
     // Prevents application from starting twice
     start_once: Once,
+    // Prevents application from loading a saved state twice
+    // Concurrency: used by application code only, atomic
+    state_loaded: AtomicBool,
 }
 
 impl std::fmt::Debug for Context {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::result::Result<(), std::fmt::Error> {
-        write!(f, "Context {{ address: {:0x}, connector: {:?}, input_queue: {:?}, timer_context: {:?}, output_buffer_pool: {:?}, outgoing_messages: {:?}, start_once: {:?} }}", self.address, self.connector, self.input_queue, self.timer_context, self.output_buffer_pool, self.outgoing_messages, self.start_once)
+        write!(f, "Context {{ addresses: {:?}, connector: {:?}, input_queue: {:?}, timer_context: {:?}, output_buffer_pool: {:?}, outgoing_messages: {:?}, start_once: {:?} }}", self.addresses, self.connector, self.input_queue, self.timer_context, self.output_buffer_pool, self.outgoing_messages, self.start_once)
+    }
+}
+
+impl Drop for Context {
+    fn drop(&mut self) {
+        log_ring::LOG_RING.drain_to_log();
+        let lost = log_ring::LOG_RING.lost();
+        if lost > 0 {
+            info!("{} deadline handler log records lost", lost);
+        }
     }
 }
 
 #[derive(Debug)]
 enum AfterDeadline {
     NextDeadline(Duration),
-    EndSimulation,
+    EndSimulation(TerminationReason),
 }
 
 impl Context {
     fn new(config: &Config, recv_callback: RecvCallback, deadline_callback: DeadlineCallback) -> Result<Arc<Context>> {
-        let address = config.address;
+        let mut addresses = vec![config.address];
+        for alias in &config.aliases {
+            if !addresses.contains(alias) {
+                addresses.push(*alias);
+            }
+        }
+        log_ring::start_drainer();
         let connector = ConnectorImpl::new(config)?;
-        let input_queue = WaitfreeArrayQueue::new(config.num_buffers.get());
+        let connection_state = connector.connection_state();
+        let input_queue = PeekableQueue::new(config.num_buffers.get());
         let timer_context = TimerContext::new(config)?;
         let output_buffer_pool = BufferPool::<FbBuffer>::new(crate::MAX_PACKET_SIZE, config.num_buffers.get());
         let outgoing_messages = OutputMsgSet::new(config.num_buffers.get());
         let upcoming_messages = VecDeque::with_capacity(config.num_buffers.get());
+        let (fragmenter, reassembler) = if config.fragment {
+            let (fragmenter, reassembler) = fragment::new_layer(config.num_buffers.get(), Duration::from_micros(config.reassembly_timeout));
+            (Some(fragmenter), Some(reassembler))
+        } else {
+            (None, None)
+        };
+        let tracer = match &config.trace {
+            Some(path) => Some(Tracer::new(path, &config.address.to_string())?),
+            None => None,
+        };
+        let faults = if config.faults.is_empty() {
+            None
+        } else {
+            info!("Fault injection enabled (seed {}): {:?}", config.fault_seed, config.faults);
+            let header_len = if fragmenter.is_some() { FRAGMENT_HEADER_SIZE } else { 0 };
+            // Copies may be of reassembled messages
+            let max_msg_size = fragmenter.as_ref().map_or(crate::MAX_PACKET_SIZE, |f| f.max_message_size());
+            Some(FaultLayer::new(&config.faults, config.fault_seed, header_len, max_msg_size, config.num_buffers.get()))
+        };
+        let control_init = control::start(config)?;
+        let (flusher, flusher_init) = if config.stream {
+            let (flusher, sender) = Flusher::start()?;
+            (Some(flusher), Some(sender))
+        } else {
+            (None, None)
+        };
 
         let context = Arc::new(Context {
-            address: address,
+            addresses,
             connector: Mutex::new(connector),
+            connection_state,
             input_queue: input_queue,
             recv_callback: recv_callback,
             deadline_callback: deadline_callback,
@@ -115,9 +359,40 @@ impl Context {
             output_buffer_pool: output_buffer_pool,
             outgoing_messages: outgoing_messages,
             start_once: Once::new(),
+            state_loaded: AtomicBool::new(false),
             upcoming_messages: Mutex::new(upcoming_messages),
+            fragmenter,
+            reassembler,
+            groups: GroupSet::new(config.num_buffers.get()),
+            next_seq_id: AtomicU64::new(1),
+            disk_requests: WaitfreeArrayQueue::new(config.num_buffers.get()),
+            markers: WaitfreeArrayQueue::new(config.num_buffers.get()),
+            harness: Harness::new(config.num_buffers.get()),
+            executor: Executor::new()?,
+            idle_until: AtomicU64::new(NO_IDLE_HINT),
+            report_callback: CallbackSlot::new(),
+            link_state_callback: CallbackSlot::new(),
+            disk_callback: CallbackSlot::new(),
+            end_callback: CallbackSlot::new(),
+            termination_reason: AtomicU8::new(0),
+            last_deadline: AtomicU64::new(0),
+            next_deadline: AtomicU64::new(0),
+            counters: Counters::new(),
+            deadline_timing: DeadlineTiming::new(),
+            nic: config.nic_rate.map(|rate| Nic::new(rate.get(), config.nic_burst)),
+            faults,
+            tracer,
+            flusher,
         });
         timer::register(&context)?;
+        if let Some(sender) = control_init {
+            // The control thread exits if the context is gone
+            let _ = sender.send(Arc::downgrade(&context));
+        }
+        if let Some(sender) = flusher_init {
+            // The flusher exits if the context is gone
+            let _ = sender.send(Arc::downgrade(&context));
+        }
 
         Ok(context)
     }
@@ -135,7 +410,18 @@ impl Context {
             match msg {
                 // Writing Ok(...?) helps the compiler to know how to convert std::io::Error to Error
                 MsgIn::GoToDeadline(deadline) => {
+                    // Time cannot go back to before a restored state
+                    if deadline < self.timer_context.deadlines().1 {
+                        return Err(Error::ProtocolViolation);
+                    }
+                    // The actor must know the aliases before routing the packets of this time slice
+                    if self.addresses.len() > 1 {
+                        let mut connector = self.connector.lock().unwrap();
+                        connector.send(MsgOut::Addresses(self.addresses.clone()))?;
+                    }
                     (self.deadline_callback)(deadline);
+                    self.trace(deadline, TraceKind::Callback("deadline_callback"));
+                    self.next_deadline.store(deadline.as_nanos() as u64, Ordering::Release);
                     Ok(self.timer_context.start(deadline)?)
                 },
                 _ => Err(Error::ProtocolViolation),
@@ -153,6 +439,36 @@ impl Context {
     }
 
     fn at_deadline(&self) -> AfterDeadline {
+        let start = Instant::now();
+        let host_start = trace::host_now();
+        let after_deadline = self.handle_deadline();
+        let handler_time = start.elapsed();
+        self.counters.count_deadline(handler_time);
+        self.deadline_timing.record_deadline(start, handler_time);
+        if let Some(tracer) = &self.tracer {
+            tracer.record(host_start, self.timer_context.simulation_next_deadline(), TraceKind::AtDeadline { duration: handler_time });
+        }
+        if let AfterDeadline::EndSimulation(reason) = after_deadline {
+            self.end_simulation(reason);
+        }
+        after_deadline
+    }
+
+    fn end_simulation(&self, reason: TerminationReason) {
+        info!("Simulation ended: {:?}", reason);
+        // SeqCst pairs with ::execute(), which stores the request id before checking
+        // termination_reason
+        self.termination_reason.store(reason.to_raw(), Ordering::SeqCst);
+        // Do not leave the application blocked in a computation
+        self.executor.abort(|| self.timer_context.set_fast_forward(false));
+        if self.end_callback.call(|end_callback| end_callback(reason)) {
+            self.trace(self.timer_context.simulation_next_deadline(), TraceKind::Callback("end_callback"));
+        } else {
+            timer::end_not_notified();
+        }
+    }
+
+    fn handle_deadline(&self) -> AfterDeadline {
         let mut connector = self.connector.lock().unwrap();
 
         // First, send all messages from this last time slice to others
@@ -165,27 +481,19 @@ impl Context {
             // FIXME(msimonin): the trait `InnerBufferDisplay` is not implemented for `flatbuilder_buffer::FbBuilder<'static, connector::InFbInitializer>`
             deadline_handler_debug!("Context::at_deadline() message to send (send_time = {:?}, src = {}, dst = {})",
                 send_time,
-                vsg_address::to_ipv4addr(send_packet_builder.src()),
-                vsg_address::to_ipv4addr(send_packet_builder.dst()));
-
-            let send_time = if send_time < previous_deadline {
-                // This message was time-stamped before the previous deadline but inserted after.
-                // Fix the timestamp to stay between the deadlines.
-                deadline_handler_debug!("Context::at_deadline() fixing send_time to {:?}", previous_deadline);
-                previous_deadline
-            } else {
-                if send_time > current_deadline {
-                    // The kernel was too slow to fire the timer...
-                    error!("send_time = {:?} is beyond current_deadline = {:?}! Aborting", send_time, current_deadline);
-                    return AfterDeadline::EndSimulation;
-                }
-                send_time
-            };
+                send_packet_builder.src(),
+                send_packet_builder.dst());
+
+            if send_time > current_deadline {
+                // The kernel was too slow to fire the timer...
+                error!("send_time = {:?} is beyond current_deadline = {:?}! Aborting", send_time, current_deadline);
+                return AfterDeadline::EndSimulation(TerminationReason::DeadlineOverrun);
+            }
             // so, the payload is a Buffer<FbBuffer> partially built with the actual payload inside
             // we finish the construction here and send it over the wire
-            if let Err(_e) = connector.send(MsgOut::SendPacket(send_packet_builder.finish(send_time))) {
+            if let Err(_e) = self.send_slice_packet(&mut connector, send_packet_builder, previous_deadline, current_deadline) {
                 error!("send(SendPacket) failed: {}", _e);
-                return AfterDeadline::EndSimulation;
+                return AfterDeadline::EndSimulation(TerminationReason::IoError);
             }
         }
 
@@ -197,21 +505,79 @@ impl Context {
             let send_time = message.send_time();
             deadline_handler_debug!("Context::at_deadline() message to send (send_time = {:?}, src = {}, dst = {})",
                 send_time,
-                vsg_address::to_ipv4addr(message.src()),
-                vsg_address::to_ipv4addr(message.dst()));
+                message.src(),
+                message.dst());
 
-            if let Err(_e) = connector.send(MsgOut::SendPacket(message.finish(send_time))) {
+            if let Err(_e) = self.send_packet(&mut connector, message, send_time) {
                 error!("send(SendPacket) failed: {}", _e);
-                return AfterDeadline::EndSimulation;
+                return AfterDeadline::EndSimulation(TerminationReason::IoError);
             }
         }
+        let idle_hint = self.idle_hint(current_deadline, &upcoming_messages);
         drop(upcoming_messages);
 
+        if let Some(mut request) = self.executor.pop_request() {
+            if request.time < previous_deadline {
+                request.time = previous_deadline;
+                self.counters.count_late_timestamp();
+            }
+            deadline_handler_debug!("Context::at_deadline() sending {:?}", request);
+            if let Err(_e) = connector.send(MsgOut::Execute(request)) {
+                error!("send(Execute) failed: {}", _e);
+                return AfterDeadline::EndSimulation(TerminationReason::IoError);
+            }
+        }
+
+        // Disk requests are subject to the same time-stamping race as messages
+        while let Some(mut request) = self.disk_requests.pop() {
+            if request.time < previous_deadline {
+                request.time = previous_deadline;
+                self.counters.count_late_timestamp();
+            }
+            deadline_handler_debug!("Context::at_deadline() sending {:?}", request);
+            if let Err(_e) = connector.send(MsgOut::DiskRequest(request)) {
+                error!("send(DiskRequest) failed: {}", _e);
+                return AfterDeadline::EndSimulation(TerminationReason::IoError);
+            }
+        }
+
+        if let Some(reassembler) = &self.reassembler {
+            // Same time base as ::handle_actor_msg()
+            for _ in 0..reassembler.expire(self.timer_context.simulation_next_deadline()) {
+                self.counters.count_local_drop(LocalDrop::Reassembly);
+            }
+        }
+
+        while let Some(mut marker) = self.markers.pop() {
+            if marker.time < previous_deadline {
+                marker.time = previous_deadline;
+                self.counters.count_late_timestamp();
+            }
+            deadline_handler_debug!("Context::at_deadline() sending {:?}", marker);
+            if let Err(_e) = connector.send(MsgOut::Marker(marker)) {
+                error!("send(Marker) failed: {}", _e);
+                return AfterDeadline::EndSimulation(TerminationReason::IoError);
+            }
+        }
+
+        // Report multicast group membership changes before the actor delivers the next messages
+        let mut membership_result = Ok(());
+        self.groups.apply_pending(|change| {
+            if membership_result.is_ok() {
+                deadline_handler_debug!("Context::at_deadline() sending GroupMembership({:?})", change);
+                membership_result = connector.send(MsgOut::GroupMembership(change));
+            }
+        });
+        if let Err(_e) = membership_result {
+            error!("send(GroupMembership) failed: {}", _e);
+            return AfterDeadline::EndSimulation(TerminationReason::IoError);
+        }
+
         // Second, notify that we reached the deadline
-        deadline_handler_debug!("Context::at_deadline() sending AtDeadline");
-        if let Err(_e) = connector.send(MsgOut::AtDeadline) {
+        deadline_handler_debug!("Context::at_deadline() sending AtDeadline (idle_hint = {:?})", idle_hint);
+        if let Err(_e) = connector.send(MsgOut::AtDeadline(idle_hint)) {
             error!("send(AtDeadline) failed: {}", _e);
-            return AfterDeadline::EndSimulation;
+            return AfterDeadline::EndSimulation(TerminationReason::IoError);
         }
 
         // Third, receive messages from others, followed by next deadline
@@ -225,40 +591,471 @@ impl Context {
                 Ok(msg) => if let Some(after_deadline) = self.handle_actor_msg(msg) {
                     break after_deadline;
                 },
-                Err(_e) => {
-                    error!("recv failed: {}", _e);
-                    break AfterDeadline::EndSimulation;
+                Err(e) => {
+                    error!("recv failed: {}", e);
+                    break AfterDeadline::EndSimulation(TerminationReason::from_io_error(&e));
                 }
             }
         };
 
+        if let Some(faults) = &self.faults {
+            faults.deliver_delayed(current_deadline, |input_msg| self.deliver(input_msg));
+        }
+
+        // Injected messages are delivered after the messages of the simulator
+        self.harness.drain_injected(|input| {
+            let src = input.src;
+            let size = input.payload.len();
+            self.counters.count_received(size);
+            self.trace(current_deadline, TraceKind::DeliverPacket { src, size });
+            self.withdraw_idle_hint();
+            if input_queue.push(InputMsg::Injected(input)).is_err() {
+                info!("Dropping injected packet from {} of {} bytes", src, size);
+                self.counters.count_local_drop(LocalDrop::InputQueueFull);
+            }
+        });
+
         if may_notify && !input_queue.is_empty() {
             deadline_handler_debug!("Context::at_deadline() calling recv_callback()");
             (self.recv_callback)();
+            self.trace(current_deadline, TraceKind::Callback("recv_callback"));
         }
 
+        self.last_deadline.store(current_deadline.as_nanos() as u64, Ordering::Release);
         if let AfterDeadline::NextDeadline(deadline) = after_deadline {
+            self.next_deadline.store(deadline.as_nanos() as u64, Ordering::Release);
             (self.deadline_callback)(deadline);
+            self.trace(deadline, TraceKind::Callback("deadline_callback"));
         }
 
         deadline_handler_debug!("Context::at_deadline() after_deadline = {:?}", after_deadline);
         after_deadline
     }
 
+    // Streams the packets queued since the last flush, up to the next deadline, and leaves the
+    // others to the deadline handler. Called by the flusher, which stops when this returns false.
+    fn flush(&self) -> bool {
+        let mut connector = self.connector.lock().unwrap();
+        if self.termination_reason.load(Ordering::Acquire) != 0 {
+            return false;
+        }
+        // Both are updated by the deadline handler while it holds connector, so they match the
+        // time slice that the actor expects packets for
+        let previous_deadline = Duration::from_nanos(self.last_deadline.load(Ordering::Acquire));
+        let next_deadline = Duration::from_nanos(self.next_deadline.load(Ordering::Acquire));
+        if next_deadline == Duration::from_nanos(0) {
+            // Not started yet
+            return true;
+        }
+        for msg in self.outgoing_messages.drain_until(next_deadline) {
+            deadline_handler_debug!("Context::flush() message to send (send_time = {:?}, src = {}, dst = {})",
+                msg.send_time(),
+                msg.src(),
+                msg.dst());
+            if let Err(e) = self.send_slice_packet(&mut connector, msg, previous_deadline, next_deadline) {
+                // The deadline handler ends the simulation at its next send
+                error!("Streaming send(SendPacket) failed: {}", e);
+                return false;
+            }
+        }
+        true
+    }
+
+    // Sends a packet time-stamped before deadline. Called by the deadline handler and the flusher.
+    fn send_slice_packet(&self, connector: &mut ConnectorImpl, msg: OutputMsg, previous_deadline: Duration, deadline: Duration) -> std::io::Result<()> {
+        let send_time = msg.send_time();
+        let send_time = if send_time < previous_deadline {
+            // This message was time-stamped before the previous deadline but inserted after.
+            // Fix the timestamp to stay between the deadlines.
+            deadline_handler_debug!("Context::send_slice_packet() fixing send_time to {:?}", previous_deadline);
+            self.counters.count_late_timestamp();
+            previous_deadline
+        } else {
+            send_time
+        };
+        // With the NIC model, packets still being serialized at the deadline are sent at a later
+        // deadline
+        let send_time = self.nic_send_time(&msg, send_time);
+        self.send_with_faults(connector, msg, send_time, deadline)
+    }
+
+    fn send_packet(&self, connector: &mut ConnectorImpl, msg: OutputMsg, send_time: Duration) -> std::io::Result<()> {
+        self.harness.tap(send_time, &msg);
+        let size = msg.payload().len();
+        let dst = msg.dst();
+        connector.send(MsgOut::SendPacket(msg.finish(send_time)))?;
+        self.counters.count_sent(size);
+        self.trace(send_time, TraceKind::SendPacket { dst, size });
+        Ok(())
+    }
+
+    // Sends a packet of the time slice ending at deadline, through fault injection if enabled
+    //
+    // Packets time-stamped after a deadline by the KVM backend (see ::send_at()) bypass fault
+    // injection.
+    fn send_with_faults(&self, connector: &mut ConnectorImpl, msg: OutputMsg, send_time: Duration, deadline: Duration) -> std::io::Result<()> {
+        match &self.faults {
+            Some(faults) => faults.send(msg, send_time, &self.output_buffer_pool, &self.counters, |msg, send_time| {
+                self.send_or_defer(connector, msg, send_time, deadline)
+            }),
+            None => self.send_or_defer(connector, msg, send_time, deadline),
+        }
+    }
+
+    // Packets time-stamped after deadline, because of fault injection or the NIC model, are moved
+    // to upcoming_messages, which must not be locked by the caller
+    fn send_or_defer(&self, connector: &mut ConnectorImpl, msg: OutputMsg, send_time: Duration, deadline: Duration) -> std::io::Result<()> {
+        if send_time > deadline {
+            self.defer(msg, send_time);
+            Ok(())
+        } else {
+            self.send_packet(connector, msg, send_time)
+        }
+    }
+
+    // The message is sent by the deadline handler once send_time is reached
+    fn defer(&self, mut msg: OutputMsg, send_time: Duration) {
+        msg.set_send_time(send_time);
+        let mut upcoming_messages = self.upcoming_messages.lock().unwrap();
+        let pos = upcoming_messages.iter().position(|m| m.send_time() > send_time).unwrap_or(upcoming_messages.len());
+        upcoming_messages.insert(pos, msg);
+    }
+
+    // The packet is sent once its last bit leaves the NIC. The NIC is charged, so this must be
+    // called once per packet, after it is queued.
+    fn nic_send_time(&self, msg: &OutputMsg, send_time: Duration) -> Duration {
+        self.nic.as_ref().map_or(send_time, |nic| nic.send_packet(send_time, &msg.attributes(), msg.payload().len()))
+    }
+
+    fn deliver(&self, input_msg: InputMsg) {
+        let src = input_msg.src();
+        let size = input_msg.payload_len();
+        self.withdraw_idle_hint();
+        if self.input_queue.push(input_msg).is_err() {
+            info!("Dropping input packet from {} of {} bytes", src, size);
+            self.counters.count_local_drop(LocalDrop::InputQueueFull);
+        }
+    }
+
+    fn deliver_with_faults(&self, input_msg: InputMsg) {
+        match &self.faults {
+            Some(faults) => {
+                let now = self.timer_context.simulation_next_deadline();
+                faults.deliver(input_msg, now, &self.counters, |input_msg| self.deliver(input_msg));
+            },
+            None => self.deliver(input_msg),
+        }
+    }
+
+    fn trace(&self, sim_time: Duration, kind: TraceKind) {
+        if let Some(tracer) = &self.tracer {
+            tracer.record(trace::host_now(), sim_time, kind);
+        }
+    }
+
+    // The application may send messages at any time after handling a message or a callback, so
+    // the idle hint it gave before no longer holds. See ::set_idle_hint().
+    fn withdraw_idle_hint(&self) {
+        self.idle_until.store(NO_IDLE_HINT, Ordering::Release);
+    }
+
+    // Earliest simulation time at which this context may send a message, if it is known and after
+    // the current deadline
+    fn idle_hint(&self, current_deadline: Duration, upcoming_messages: &VecDeque<OutputMsg>) -> Option<Duration> {
+        let idle_until = self.idle_until.load(Ordering::Acquire);
+        // Pending input messages may trigger new messages at any time
+        let delayed_input = self.faults.as_ref().map_or(false, |faults| faults.has_delayed_input());
+        if idle_until == NO_IDLE_HINT || !self.input_queue.is_empty() || delayed_input {
+            return None;
+        }
+        let mut idle_until = Duration::from_nanos(idle_until);
+        if let Some(message) = upcoming_messages.front() {
+            idle_until = idle_until.min(message.send_time());
+        }
+        if idle_until > current_deadline {
+            Some(idle_until)
+        } else {
+            None
+        }
+    }
+
     fn handle_actor_msg(&self, msg: MsgIn) -> Option<AfterDeadline> {
         deadline_handler_debug!("Context::handle_actor_msg() received msg = {}", msg);
         match msg {
             MsgIn::DeliverPacket(d) => {
                 let src = d.src();
                 let size = d.payload().len();
-                if self.input_queue.push(d).is_err() {
-                    info!("Dropping input packet from {} of {} bytes", src, size);
+                self.counters.count_received(size);
+                if self.tracer.is_some() {
+                    self.trace(self.timer_context.simulation_next_deadline(), TraceKind::DeliverPacket { src, size });
+                }
+                if !self.groups.accepts(d.dst()) {
+                    debug!("Dropping input packet from {} to unjoined group {}", src, d.dst());
+                    self.counters.count_local_drop(LocalDrop::UnjoinedGroup);
+                    return None;
+                }
+                let input_msg = match &self.reassembler {
+                    Some(reassembler) => match reassembler.push(d, self.timer_context.simulation_next_deadline()) {
+                        Ok(pushed) => {
+                            for _ in 0..pushed.evicted {
+                                self.counters.count_local_drop(LocalDrop::Reassembly);
+                            }
+                            pushed.msg.map(InputMsg::Reassembled)
+                        },
+                        Err(e) => {
+                            info!("Dropping input packet from {} of {} bytes: {}", src, size, e);
+                            self.counters.count_local_drop(LocalDrop::Reassembly);
+                            None
+                        },
+                    },
+                    None => Some(InputMsg::Packet(d)),
+                };
+                if let Some(input_msg) = input_msg {
+                    self.deliver_with_faults(input_msg);
+                }
+                None
+            },
+            MsgIn::DeliveryReport(report) => {
+                self.withdraw_idle_hint();
+                if let Some(reason) = report.drop_reason {
+                    self.counters.count_reported_drop(reason);
+                }
+                if self.report_callback.call(|report_callback| report_callback(report)) {
+                    self.trace(report.time, TraceKind::Callback("report_callback"));
+                } else {
+                    info!("Dropping delivery report {:?}", report);
+                }
+                None
+            },
+            MsgIn::LinkState(address, up) => {
+                info!("Link of {} is {}", address, if up { "up" } else { "down" });
+                self.withdraw_idle_hint();
+                if self.link_state_callback.call(|link_state_callback| link_state_callback(address, up)) {
+                    self.trace(self.timer_context.simulation_next_deadline(), TraceKind::Callback("link_state_callback"));
+                } else {
+                    info!("Dropping link state change of {}", address);
+                }
+                None
+            },
+            MsgIn::DiskCompletion(completion) => {
+                self.withdraw_idle_hint();
+                if self.disk_callback.call(|disk_callback| disk_callback(completion)) {
+                    self.trace(completion.time, TraceKind::Callback("disk_callback"));
+                } else {
+                    info!("Dropping disk completion {:?}", completion);
+                }
+                None
+            },
+            MsgIn::ExecuteCompletion(completion) => {
+                // Let the application resume at the current deadline
+                self.withdraw_idle_hint();
+                if !self.executor.complete(&completion, || self.timer_context.set_fast_forward(false)) {
+                    info!("Dropping unexpected execute completion {:?}", completion);
                 }
                 None
             },
             MsgIn::GoToDeadline(deadline) => Some(AfterDeadline::NextDeadline(deadline)),
-            MsgIn::EndSimulation => Some(AfterDeadline::EndSimulation),
+            MsgIn::EndSimulation => Some(AfterDeadline::EndSimulation(TerminationReason::ActorRequest)),
+        }
+    }
+
+    // The callbacks of the set_*_callback() methods are called by the deadline handler, like the
+    // receive and deadline callbacks, possibly in signal handler context. They must not lock nor
+    // allocate.
+    //
+    // Packets sent while a callback is set request a delivery report from the simulator.
+    pub fn set_report_callback(&self, report_callback: Option<ReportCallback>) {
+        self.report_callback.set(report_callback)
+    }
+
+    // The callback is called by the deadline handler when the simulated link of one of the
+    // addresses of this context goes down (false) or up (true).
+    pub fn set_link_state_callback(&self, link_state_callback: Option<LinkStateCallback>) {
+        self.link_state_callback.set(link_state_callback)
+    }
+
+    // The callback is called by the deadline handler when the simulation ends, for any reason
+    pub fn set_end_callback(&self, end_callback: Option<EndCallback>) {
+        self.end_callback.set(end_callback)
+    }
+
+    // The callback is called with each outgoing packet just before it is sent to the simulator,
+    // for instance to check the traffic of an application in tests. Packets are passed after
+    // fragmentation, if enabled. With --stream, the callback is also called by the flusher
+    // thread.
+    pub fn set_tap_callback(&self, tap_callback: Option<TapCallback>) {
+        self.harness.set_tap_callback(tap_callback)
+    }
+
+    // Delivers a message from src to the default address of this context at the next deadline,
+    // as if the simulator had delivered it, for instance to drive an application in tests. The
+    // message bypasses reassembly and group filtering.
+    pub fn inject_delivery(&self, src: VsgAddress, payload: &[u8]) -> Result<()> {
+        if let Err(e) = self.harness.inject(src, self.addresses[0], payload) {
+            error!("inject_delivery error: {}", e);
+            return Err(e.into());
+        }
+        Ok(())
+    }
+
+    // None while the simulation runs
+    pub fn termination_reason(&self) -> Option<TerminationReason> {
+        TerminationReason::from_raw(self.termination_reason.load(Ordering::Acquire))
+    }
+
+    // The callback is called by the deadline handler when a disk request completes
+    pub fn set_disk_callback(&self, disk_callback: Option<DiskCallback>) {
+        self.disk_callback.set(disk_callback)
+    }
+
+    // Submits a block I/O request of size bytes to the disk named disk in the simulated platform.
+    // The request is sent at the next deadline. Returns the request id, as found in the
+    // completion.
+    pub fn disk_submit(&self, disk: &str, op: DiskOp, size: u64) -> Result<u64> {
+        let time = self.timer_context.simulation_now();
+        let request_id = self.next_seq_id.fetch_add(1, Ordering::Relaxed);
+        let request = DiskRequest::new(request_id, time, op, size, disk)?;
+        if self.disk_requests.push(request).is_err() {
+            error!("disk_submit error at time {:?}: no slot available", time);
+            return Err(Error::NoMemoryAvailable);
+        }
+        debug!("new disk request: time = {:?}, request_id = {}, op = {:?}, size = {}, disk = {}", time, request_id, op, size, disk);
+
+        Ok(request_id)
+    }
+
+    // Records an application event at the current simulation time. Markers are forwarded to the
+    // actor at the next deadline, to be shown in the simulator trace.
+    pub fn mark(&self, label: &str, value: i64) -> Result<()> {
+        let time = self.timer_context.simulation_now();
+        let marker = Marker::new(time, label, value)?;
+        if self.markers.push(marker).is_err() {
+            error!("mark error at time {:?}: no slot available", time);
+            return Err(Error::NoMemoryAvailable);
+        }
+        Ok(())
+    }
+
+    // Charges a computation of flops floating-point operations to the simulated host and blocks
+    // until the simulator reports its completion. Returns the simulated completion time.
+    //
+    // With the process backend, simulation time fast-forwards from deadline to deadline once the
+    // request is sent, so the computation does not cost real time. Other application threads
+    // should be idle meanwhile. Concurrent calls are serialized.
+    pub fn execute(&self, flops: f64) -> Result<Duration> {
+        let _execute_guard = self.executor.lock();
+
+        if !self.start_once.is_completed() {
+            return Err(Error::NotStarted);
+        }
+        if self.termination_reason().is_some() {
+            return Err(Error::SimulationEnded);
+        }
+
+        let request = ExecuteRequest {
+            request_id: self.next_seq_id.fetch_add(1, Ordering::Relaxed),
+            time: self.timer_context.simulation_now(),
+            flops,
+        };
+        // SeqCst pairs with ::end_simulation()
+        let ended = || self.termination_reason.load(Ordering::SeqCst) != 0;
+        Ok(self.executor.run(request, ended, |enable| self.timer_context.set_fast_forward(enable))?)
+    }
+
+    // Membership changes take effect at the next deadline
+    pub fn join_group(&self, group: VsgAddress) -> Result<()> {
+        Ok(self.groups.join(group)?)
+    }
+
+    pub fn leave_group(&self, group: VsgAddress) -> Result<()> {
+        Ok(self.groups.leave(group)?)
+    }
+
+    // Tells that the application will not send any message during the next `delay` of
+    // simulation time, unless it receives a message first. This is typically the delay until the
+    // next armed timer. The actor can use this hint to skip idle time slices. None withdraws the
+    // hint, as does any message or completion delivered to the application.
+    pub fn set_idle_hint(&self, delay: Option<Duration>) {
+        let idle_until = match delay {
+            Some(delay) => {
+                let idle_until = self.timer_context.simulation_now() + delay;
+                std::cmp::min(idle_until.as_nanos(), (NO_IDLE_HINT - 1) as u128) as u64
+            },
+            None => NO_IDLE_HINT,
+        };
+        self.idle_until.store(idle_until, Ordering::Release);
+    }
+
+    // Serializes the state of this context that is not part of the application memory: messages
+    // not sent to the actor yet, received messages not read yet, and deadlines. ::load_state()
+    // restores it in a new context, typically along with a VM snapshot.
+    //
+    // The deadline handler must not run meanwhile, that is the simulation is not started or has
+    // ended, or the VM is paused (QEMU backends).
+    pub fn save_state(&self) -> Result<Vec<u8>> {
+        if !self.timer_context.deadline_handler_stopped() {
+            return Err(Error::SimulationRunning);
+        }
+
+        // The flusher also consumes outgoing_messages
+        let _connector = self.connector.lock().unwrap();
+        let (prev_deadline, next_deadline) = self.timer_context.deadlines();
+        let state = State {
+            prev_deadline,
+            next_deadline,
+            next_seq_id: self.next_seq_id.load(Ordering::Relaxed),
+            outgoing: state::save_outgoing(&self.outgoing_messages),
+            upcoming: self.upcoming_messages.lock().unwrap().iter().map(SavedPacket::new).collect(),
+            input: state::save_input(&self.input_queue),
+        };
+        Ok(state.encode())
+    }
+
+    // Loads a state saved by ::save_state(), possibly from another simulation. Must be called
+    // before ::start(). Simulation time then resumes at the next deadline of the saved state, so
+    // the actor must give a later first deadline. A state can be loaded only once. The context is
+    // unusable if loading a valid state fails.
+    pub fn load_state(&self, data: &[u8]) -> Result<()> {
+        if self.start_once.is_completed() {
+            return Err(Error::AlreadyStarted);
+        }
+        let state = State::decode(data)?;
+        if self.state_loaded.swap(true, Ordering::AcqRel) {
+            return Err(Error::AlreadyLoaded);
+        }
+
+        self.timer_context.restore_deadlines(state.prev_deadline, state.next_deadline);
+        // The flusher fixes the timestamps of restored packets like the deadline handler
+        self.last_deadline.store(state.next_deadline.as_nanos() as u64, Ordering::Release);
+        self.next_seq_id.fetch_max(state.next_seq_id, Ordering::Relaxed);
+
+        for packet in &state.outgoing {
+            self.outgoing_messages.insert(packet.restore(&self.output_buffer_pool)?)?;
+        }
+        let mut upcoming_messages = self.upcoming_messages.lock().unwrap();
+        for packet in &state.upcoming {
+            upcoming_messages.push_back(packet.restore(&self.output_buffer_pool)?);
+        }
+        drop(upcoming_messages);
+        for input in state.input {
+            if self.input_queue.push(InputMsg::Restored(input)).is_err() {
+                return Err(Error::NoMemoryAvailable);
+            }
         }
+
+        Ok(())
+    }
+
+    // Counters since the creation of this context, for instance to check the health of a VM at
+    // the end of an experiment
+    pub fn stats(&self) -> Stats {
+        self.counters.snapshot(self.output_buffer_pool.high_water_mark(), self.input_queue.high_water_mark())
+    }
+
+    // Histograms of the deadline handler timing since the creation of this context, to choose
+    // slice lengths or check timing fidelity
+    pub fn timing_report(&self) -> TimingReport {
+        self.deadline_timing.report()
     }
 
     pub fn gettimeofday(&self) -> libc::timeval {
@@ -269,8 +1066,70 @@ impl Context {
         }
     }
 
+    // Addresses of this context, starting with the default source address
+    pub fn addresses(&self) -> &[VsgAddress] {
+        &self.addresses
+    }
+
     pub fn send(&self, dst: libc::in_addr_t, msg: &[u8]) -> Result<()> {
+        self.send_to(self.addresses[0], VsgAddress::V4(dst), &PacketAttributes::default(), msg).map(|_| ())
+    }
+
+    // IPv4-mapped destination addresses are sent as IPv4 addresses
+    pub fn send6(&self, dst: [u8; 16], msg: &[u8]) -> Result<()> {
+        self.send_to(self.addresses[0], VsgAddress::from_ipv6(dst), &PacketAttributes::default(), msg).map(|_| ())
+    }
+
+    // Fails with Error::InvalidAddress if src is not one of ::addresses()
+    pub fn send_from(&self, src: VsgAddress, dst: VsgAddress, msg: &[u8]) -> Result<()> {
+        self.send_ex(src, dst, &PacketAttributes::default(), msg).map(|_| ())
+    }
+
+    // Same as ::send_from() with extended metadata for the simulator. Returns the sequence id of
+    // the message, as found in delivery reports. All fragments of a message share the same
+    // sequence id.
+    pub fn send_ex(&self, src: VsgAddress, dst: VsgAddress, attributes: &PacketAttributes, msg: &[u8]) -> Result<u64> {
+        if !self.addresses.contains(&src) {
+            return Err(Error::InvalidAddress);
+        }
+        self.send_to(src, dst, attributes, msg)
+    }
+
+    // Sends a message having no payload, that the simulator models as size bytes on the wire.
+    // Meant for traffic generators, the receiver reads it with ::recv_synthetic(). size cannot be
+    // 0, which stands for the length of the payload on the wire.
+    pub fn send_synthetic(&self, dst: VsgAddress, size: u32) -> Result<()> {
+        if size == 0 {
+            return Err(Error::InvalidSize);
+        }
+        let attributes = PacketAttributes {
+            wire_size: size,
+            ..Default::default()
+        };
+        self.send_to(self.addresses[0], dst, &attributes, &[]).map(|_| ())
+    }
+
+    fn send_to(&self, src: VsgAddress, dst: VsgAddress, attributes: &PacketAttributes, msg: &[u8]) -> Result<u64> {
         let send_time = self.timer_context.simulation_now();
+        let seq_id = self.next_seq_id.fetch_add(1, Ordering::Relaxed);
+        // Sequence id 0 tells the simulator that no report is requested
+        let wire_seq_id = if self.report_callback.is_set() {
+            seq_id
+        } else {
+            0
+        };
+
+        match &self.fragmenter {
+            // All fragments share the same send time
+            Some(fragmenter) => fragmenter.send(msg, attributes, |attributes, fragment| {
+                self.send_at(src, dst, attributes, wire_seq_id, send_time, fragment)
+            })?,
+            None => self.send_at(src, dst, attributes, wire_seq_id, send_time, msg)?,
+        }
+        Ok(seq_id)
+    }
+
+    fn send_at(&self, src: VsgAddress, dst: VsgAddress, attributes: &PacketAttributes, seq_id: u64, send_time: Duration, msg: &[u8]) -> Result<()> {
         // It is possible that the deadline is reached just after recording the send time and
         // before inserting the message, which leads to sending the message at the next deadline.
         // This would violate the property that send times must be after the previous deadline
@@ -282,6 +1141,9 @@ impl Context {
             Ok(b) => b,
             Err(e) => {
                 error!("send error at send_time {:?}: {:?}", send_time, e);
+                if let buffer_pool::Error::NoBufferAvailable = e {
+                    self.counters.count_local_drop(LocalDrop::NoBuffer);
+                }
                 return Err(e.into());
             }
         };
@@ -296,31 +1158,81 @@ impl Context {
         // (which is accurate).
         match self.timer_context.check_deadline_overrun(send_time, &self.upcoming_messages) {
             Some(send_time_overrun) => {
-                let mut upcoming_messages = self.upcoming_messages.lock().unwrap();
-                upcoming_messages.push_back(OutputMsg::new(self.address, dst, send_time_overrun, msg, buffer)?);
+                let output_msg = OutputMsg::new(src, dst, *attributes, seq_id, send_time_overrun, msg, buffer)?;
+                let send_time = self.nic_send_time(&output_msg, send_time_overrun);
+                self.defer(output_msg, send_time);
             },
             None => {
-                self.outgoing_messages.insert(OutputMsg::new(self.address,  dst, send_time, msg, buffer)?)?;
+                self.outgoing_messages.insert(OutputMsg::new(src,  dst, *attributes, seq_id, send_time, msg, buffer)?)?;
+                if let Some(flusher) = &self.flusher {
+                    flusher.wake();
+                }
             }
         }
 
-        debug!("new packet: send_time = {:?}, src = {}, dst = {}, size = {}", send_time, vsg_address::to_ipv4addr(self.address), vsg_address::to_ipv4addr(dst), msg.len());
+        debug!("new packet: send_time = {:?}, src = {}, dst = {}, size = {}", send_time, src, dst, msg.len());
 
         Ok(())
     }
 
+    // The destination address tells which address of this context received the message.
+    //
+    // Fails with Error::InvalidAddress if the next message has IPv6 addresses. The message stays
+    // in the queue for ::recv6().
     pub fn recv<'a, 'b>(&'a self, msg: &'b mut [u8]) -> Result<(libc::in_addr_t, libc::in_addr_t, &'b mut [u8])> {
-        match self.input_queue.pop() {
-            Some(msg_in) => {
-                if msg.len() >= msg_in.payload().len() {
-                    let msg = &mut msg[..msg_in.payload().len()];
-                    msg.copy_from_slice(&msg_in.payload());
-                    Ok((msg_in.src(), msg_in.dst(), msg))
+        let ((src, dst), msg) = self.recv_from_queue(msg, |msg_in| match (msg_in.src().ipv4(), msg_in.dst().ipv4()) {
+            (Some(src), Some(dst)) => Ok((src, dst)),
+            _ => Err(Error::InvalidAddress),
+        })?;
+        Ok((src, dst, msg))
+    }
+
+    // IPv4 addresses are returned as IPv4-mapped IPv6 addresses
+    pub fn recv6<'b>(&self, msg: &'b mut [u8]) -> Result<([u8; 16], [u8; 16], &'b mut [u8])> {
+        let ((src, dst), msg) = self.recv_from_queue(msg, |msg_in| Ok((msg_in.src().ipv6(), msg_in.dst().ipv6())))?;
+        Ok((src, dst, msg))
+    }
+
+    // Same as ::recv6() with the extended metadata of the message. Messages sent without
+    // attributes have default attributes.
+    pub fn recv_ex<'b>(&self, msg: &'b mut [u8]) -> Result<(VsgAddress, VsgAddress, PacketAttributes, &'b mut [u8])> {
+        let ((src, dst, attributes), msg) = self.recv_from_queue(msg, |msg_in| Ok((msg_in.src(), msg_in.dst(), msg_in.attributes())))?;
+        Ok((src, dst, attributes, msg))
+    }
+
+    // Receives the next message if it is synthetic (see ::send_synthetic()), and returns its size
+    // on the simulated wire. Other messages are left in the queue for ::recv() and friends.
+    pub fn recv_synthetic(&self) -> Result<(VsgAddress, VsgAddress, u32)> {
+        let is_synthetic = |msg_in: &InputMsg| msg_in.payload_len() == 0 && msg_in.attributes().wire_size != 0;
+        match self.input_queue.pop_if(is_synthetic) {
+            Some(Some(msg_in)) => Ok((msg_in.src(), msg_in.dst(), msg_in.attributes().wire_size)),
+            Some(None) => Err(Error::NotSynthetic),
+            None => Err(Error::NoMessageAvailable),
+        }
+    }
+
+    fn recv_from_queue<'b, H, F>(&self, msg: &'b mut [u8], header: F) -> Result<(H, &'b mut [u8])>
+        where F: FnOnce(&InputMsg) -> Result<H> {
+        // A message whose header cannot be returned is left in the queue
+        let mut header_res = None;
+        let msg_in = self.input_queue.pop_if(|msg_in| {
+            let res = header(msg_in);
+            let accept = res.is_ok();
+            header_res = Some(res);
+            accept
+        });
+        match (msg_in, header_res) {
+            (Some(Some(msg_in)), Some(Ok(header))) => {
+                if msg.len() >= msg_in.payload_len() {
+                    let msg = &mut msg[..msg_in.payload_len()];
+                    msg_in.copy_payload(msg);
+                    Ok((header, msg))
                 } else {
                     Err(Error::SizeTooBig)
                 }
             },
-            None => Err(Error::NoMessageAvailable),
+            (Some(None), Some(Err(e))) => Err(e),
+            _ => Err(Error::NoMessageAvailable),
         }
     }
 
@@ -333,7 +1245,13 @@ impl Context {
     }
 }
 
-pub fn init<I>(args: I, recv_callback: RecvCallback, deadline_callback: DeadlineCallback) -> Result<Arc<Context>>
+// Writes the latest records logged by the deadline handler to fd, even if they were already
+// forwarded to the log crate. Async-signal-safe, so that it can be called from a crash handler.
+pub fn dump_deadline_log(fd: std::os::unix::io::RawFd) -> std::io::Result<()> {
+    log_ring::LOG_RING.dump_fd(fd)
+}
+
+pub fn init<I>(args: I, recv_callback: RecvCallback, deadline_callback: DeadlineCallback) -> Result<Arc<Context>>
     where I: IntoIterator,
           I::Item: Into<std::ffi::OsString> + Clone {
     use structopt::StructOpt;
@@ -359,7 +1277,7 @@ pub mod test_helpers {
     use std::sync::Arc;
     use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
     use std::time::Duration;
-    use super::connector::{MsgIn, MsgOut, create_deliver_packet_unprefixed};
+    use super::connector::{MsgIn, MsgOut, create_deliver_packet};
     #[cfg(feature = "test-helpers")]
     pub use super::connector::test_helpers::*;
 
@@ -432,10 +1350,62 @@ pub mod test_helpers {
             actor.send(MsgIn::GoToDeadline(deadline))?;
             let msg = actor.recv()?;
             match msg {
-                MsgOut::AtDeadline => (),
+                MsgOut::AtDeadline(_) => (),
                 MsgOut::SendPacket(_) => break,
+                MsgOut::GroupMembership(_) => (),
+                MsgOut::DiskRequest(_) => (),
+                MsgOut::Execute(_) => (),
+                MsgOut::Marker(_) => (),
+                MsgOut::Addresses(_) => (),
+            }
+        }
+        actor.send(MsgIn::EndSimulation)
+    }
+
+    // Lets the VM run the time slice of RECV_ONE_MSG_ACTOR_SLICE after deadline and passes the
+    // messages it sends meanwhile to f. Returns the new deadline.
+    pub fn run_slice<F>(actor: &mut TestActor, deadline: Duration, mut f: F) -> TestResult<Duration>
+        where F: FnMut(MsgOut) -> TestResult<()> {
+        let deadline = deadline + RECV_ONE_MSG_ACTOR_SLICE;
+        actor.send(MsgIn::GoToDeadline(deadline))?;
+        loop {
+            match actor.recv()? {
+                MsgOut::AtDeadline(_) => return Ok(deadline),
+                msg => f(msg)?,
             }
         }
+    }
+
+    // Reports the first packet sent by the context as lost
+    pub fn report_actor(actor: &mut TestActor) -> TestResult<()> {
+        use crate::connector::tansiv;
+
+        let mut deadline = Duration::from_micros(0);
+        let mut seq_id = None;
+        while seq_id.is_none() {
+            deadline = run_slice(actor, deadline, |msg| {
+                if let MsgOut::SendPacket(p) = msg {
+                    let msg = flatbuffers::size_prefixed_root::<tansiv::ToTansivMsg>(p.finished_data()).unwrap();
+                    seq_id = Some(msg.content_as_send_packet().unwrap().seq_id());
+                }
+                Ok(())
+            })?;
+        }
+
+        actor.send(MsgIn::DeliveryReport(crate::DeliveryReport {
+            seq_id: seq_id.unwrap(),
+            time: deadline,
+            drop_reason: Some(crate::DropReason::Loss),
+        }))?;
+        run_slice(actor, deadline, |_| Ok(()))?;
+        actor.send(MsgIn::EndSimulation)
+    }
+
+    // Reports that the link of the default address of the context goes down at the end of the
+    // first time slice
+    pub fn link_state_actor(actor: &mut TestActor) -> TestResult<()> {
+        actor.send(MsgIn::GoToDeadline(START_ACTOR_DEADLINE))?;
+        actor.send(MsgIn::LinkState(crate::VsgAddress::V4(local_vsg_address!()), false))?;
         actor.send(MsgIn::EndSimulation)
     }
 
@@ -453,9 +1423,8 @@ pub mod test_helpers {
         while next_deadline_micros < delay_micros {
             actor.send(MsgIn::GoToDeadline(Duration::from_micros(next_deadline_micros)))?;
             loop {
-                match actor.recv()? {
-                    MsgOut::AtDeadline => break,
-                    _ => (),
+                if let MsgOut::AtDeadline(_) = actor.recv()? {
+                    break;
                 }
             }
 
@@ -467,7 +1436,7 @@ pub mod test_helpers {
         let dst = remote_vsg_address!();
 
         let mut builder = flatbuffers::FlatBufferBuilder::new();
-        create_deliver_packet_unprefixed(&mut builder, src, dst, msg);
+        create_deliver_packet(&mut builder, src, dst, msg);
         let fb = builder.finished_data();
         let size = fb.len();
         let buffer_pool = crate::BufferPool::<crate::bytes_buffer::BytesBuffer>::new(size, 1);
@@ -542,6 +1511,81 @@ pub mod test_helpers {
         }
     }
 
+    const TAPPED_PAYLOAD_SIZE: usize = 64;
+    const NUM_TAPPED: usize = 8;
+
+    #[derive(Clone, Copy)]
+    pub struct TappedPacket {
+        pub send_time: Duration,
+        pub src: crate::VsgAddress,
+        pub dst: crate::VsgAddress,
+        len: usize,
+        payload: [u8; TAPPED_PAYLOAD_SIZE],
+    }
+
+    impl TappedPacket {
+        const EMPTY: TappedPacket = TappedPacket {
+            send_time: Duration::from_secs(0),
+            src: crate::VsgAddress::V4(0),
+            dst: crate::VsgAddress::V4(0),
+            len: 0,
+            payload: [0; TAPPED_PAYLOAD_SIZE],
+        };
+
+        pub fn payload(&self) -> &[u8] {
+            &self.payload[..self.len]
+        }
+    }
+
+    struct TapRecorderData {
+        packets: Vec<seq_lock::SeqLock<TappedPacket>>,
+        count: AtomicUsize,
+    }
+
+    // Records the packets passed to the tap callback into preallocated slots, without locking
+    // nor allocating. Only the first NUM_TAPPED packets are kept and payloads are truncated to
+    // TAPPED_PAYLOAD_SIZE bytes.
+    #[derive(Clone)]
+    pub struct TapRecorder(Arc<TapRecorderData>);
+
+    impl TapRecorder {
+        pub fn new() -> TapRecorder {
+            TapRecorder(Arc::new(TapRecorderData {
+                packets: (0..NUM_TAPPED).map(|_| seq_lock::SeqLock::new(TappedPacket::EMPTY)).collect(),
+                count: AtomicUsize::new(0),
+            }))
+        }
+
+        pub fn record(&self, send_time: Duration, src: crate::VsgAddress, dst: crate::VsgAddress, payload: &[u8]) {
+            let index = self.0.count.fetch_add(1, Ordering::SeqCst);
+            if let Some(packet) = self.0.packets.get(index) {
+                packet.write(|mut packet| {
+                    let len = std::cmp::min(payload.len(), TAPPED_PAYLOAD_SIZE);
+                    packet.send_time = send_time;
+                    packet.src = src;
+                    packet.dst = dst;
+                    packet.len = len;
+                    packet.payload[..len].copy_from_slice(&payload[..len]);
+                    packet
+                });
+            }
+        }
+
+        pub fn get_callback(&self) -> crate::TapCallback {
+            let cb_recorder = self.clone();
+            Box::new(move |send_time, src, dst, payload| cb_recorder.record(send_time, src, dst, payload))
+        }
+
+        // Number of packets tapped, including those not kept
+        pub fn num_tapped(&self) -> usize {
+            self.0.count.load(Ordering::SeqCst)
+        }
+
+        pub fn packet(&self, index: usize) -> TappedPacket {
+            self.0.packets[index].read(|p| p)
+        }
+    }
+
     static INIT: std::sync::Once = std::sync::Once::new();
 
     pub fn init() {
@@ -650,6 +1694,34 @@ mod test {
         drop(actor);
     }
 
+    #[test]
+    fn end_callback() {
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        init();
+
+        let actor = TestActorDesc::new("titi", start_actor);
+        let context = super::init(valid_args!(), Box::new(dummy_recv_callback), Box::new(dummy_deadline_callback))
+            .expect("init failed");
+
+        let ended_by_actor = Arc::new(AtomicBool::new(false));
+        let cb_ended_by_actor = ended_by_actor.clone();
+        context.set_end_callback(Some(Box::new(move |reason| {
+            cb_ended_by_actor.store(reason == super::TerminationReason::ActorRequest, Ordering::Release);
+        })));
+        assert_eq!(None, context.termination_reason());
+
+        context.start()
+            .expect("start failed");
+
+        context.stop();
+        assert!(ended_by_actor.load(Ordering::Acquire));
+        assert_eq!(Some(super::TerminationReason::ActorRequest), context.termination_reason());
+
+        drop(actor);
+    }
+
     #[test]
     fn send_too_big() {
         init();
@@ -678,72 +1750,154 @@ mod test {
     }
 
     #[test]
-    fn recv() {
+    fn stats() {
         init();
 
-        const EXPECTED_MSG: &[u8] = b"Foo msg";
-        let mut buffer = [0u8; EXPECTED_MSG.len()];
-
-        let actor = TestActorDesc::new("titi", |actor| send_one_msg_actor(actor, EXPECTED_MSG));
-
-        let recv_notifier = RecvNotifier::new();
-        let deadline_notifier = DeadlineNotifier::new();
-        let context = super::init(valid_args!(), recv_notifier.get_callback(), deadline_notifier.get_callback())
+        let actor = TestActorDesc::new("titi", recv_one_msg_actor);
+        let context = super::init(valid_args!(), Box::new(dummy_recv_callback), Box::new(dummy_deadline_callback))
             .expect("init failed");
 
         context.start()
             .expect("start failed");
 
-        recv_notifier.wait(1000);
+        let dst = remote_vsg_address!();
+        context.send(dst, b"Foo msg")
+            .expect("send failed");
 
-        let (src, dst, buffer) = context.recv(&mut buffer)
-            .expect("recv failed");
+        context.stop();
 
-        assert_eq!(src, local_vsg_address!());
-        assert_eq!(dst, remote_vsg_address!());
-        assert_eq!(buffer, EXPECTED_MSG);
+        let stats = context.stats();
+        assert_eq!(1, stats.packets_sent);
+        assert_eq!(7, stats.bytes_sent);
+        assert_eq!(0, stats.packets_received);
+        assert_eq!(1, stats.output_buffers_high_water);
+        assert_eq!(0, stats.input_queue_high_water);
+        assert!(stats.deadlines > 0);
+        assert!(stats.deadline_handler_max_time <= stats.deadline_handler_time);
 
-        assert_eq!(1, deadline_notifier.num_called());
-        assert_eq!(SEND_ONE_MSG_ACTOR_DELAY, deadline_notifier.deadline());
+        drop(actor);
+    }
+
+    #[test]
+    fn timing_report() {
+        init();
+
+        let actor = TestActorDesc::new("titi", recv_one_msg_actor);
+        let context = super::init(valid_args!(), Box::new(dummy_recv_callback), Box::new(dummy_deadline_callback))
+            .expect("init failed");
+
+        context.start()
+            .expect("start failed");
+
+        let dst = remote_vsg_address!();
+        context.send(dst, b"Foo msg")
+            .expect("send failed");
 
         context.stop();
 
+        let deadlines = context.stats().deadlines;
+        let report = context.timing_report();
+        assert_eq!(deadlines, report.jitter.count);
+        assert_eq!(deadlines, report.frozen.count);
+        assert_eq!(deadlines - 1, report.slice.count);
+
+        let mut csv = Vec::new();
+        report.write_csv(&mut csv)
+            .expect("write_csv failed");
+        let csv = String::from_utf8(csv).unwrap();
+        assert_eq!(Some("histogram,low_ns,high_ns,count"), csv.lines().next());
+        assert_eq!(deadlines as usize, csv.lines().filter(|line| line.starts_with("frozen,"))
+            .map(|line| line.rsplit(',').next().unwrap().parse::<usize>().unwrap())
+            .sum::<usize>());
+
         drop(actor);
     }
 
-    fn recv_one_delay(slice_micros: u64, delay_micros: u64) {
-        const EXPECTED_MSG: &[u8] = b"Foo msg";
-        let mut buffer = [0u8; EXPECTED_MSG.len()];
+    #[test]
+    fn control_socket() {
+        use std::io::{BufRead, BufReader, Write};
+        use std::os::unix::net::UnixStream;
 
-        let actor = TestActorDesc::new("titi", |actor| send_one_delayed_msg_actor(actor, EXPECTED_MSG, slice_micros, delay_micros));
+        init();
 
-        let recv_notifier = RecvNotifier::new();
-        let deadline_notifier = DeadlineNotifier::new();
-        let context = super::init(valid_args!(), recv_notifier.get_callback(), deadline_notifier.get_callback())
+        let actor = TestActorDesc::new("titi", TestActor::dummy_actor);
+        let args = &["-atiti", "-n", local_vsg_address_str!(), "-t1970-01-01T00:00:00", "--control_socket", "titi-control"];
+        let context = super::init(args, Box::new(dummy_recv_callback), Box::new(dummy_deadline_callback))
             .expect("init failed");
 
-        context.start()
-            .expect("start failed");
+        let stream = UnixStream::connect("titi-control")
+            .expect("connect failed");
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut query = |query: &str| {
+            writeln!(&stream, "{}", query).expect("write failed");
+            let mut response = String::new();
+            reader.read_line(&mut response).expect("read failed");
+            response
+        };
 
-        recv_notifier.wait(delay_micros / 10);
+        let status = query("status");
+        assert!(status.contains("\"address\":\"10.0.0.1\""));
+        assert!(status.contains("\"started\":false"));
+        assert!(status.contains("\"termination_reason\":null"));
+        assert!(status.contains("\"input_queue_len\":0"));
+        assert!(query("stats").contains("\"packets_sent\":0"));
+        assert!(query("config").contains("\"num_buffers\":100"));
+        assert!(query("foo").starts_with("{\"error\":"));
 
-        let (src, dst, buffer) = context.recv(&mut buffer)
-            .expect("recv failed");
-        let tv = context.gettimeofday();
+        drop(context);
+        drop(actor);
+    }
 
-        // FIXME: Use the remote address when available in the protocol
-        assert_eq!(src, local_vsg_address!());
-        assert_eq!(dst, remote_vsg_address!());
-        assert_eq!(buffer, EXPECTED_MSG);
-        let total_usec = tv.tv_sec as u64 * 1_000_000 + tv.tv_usec as u64;
-        assert!(delay_micros <= total_usec, "Message received too early: before {}us instead of after {}us", total_usec, delay_micros);
+    // Checks that the context announces its aliases first, then runs until the context sends a
+    // packet
+    fn addresses_actor(actor: &mut TestActor) -> TestResult<()> {
+        use super::connector::{MsgIn, MsgOut};
+
+        let expected = vec![
+            crate::VsgAddress::V4(local_vsg_address!()),
+            crate::VsgAddress::V4(u32::from(std::net::Ipv4Addr::new(10, 0, 0, 2)).to_be()),
+        ];
+        let mut deadline = RECV_ONE_MSG_ACTOR_SLICE;
+        actor.send(MsgIn::GoToDeadline(deadline))?;
+        match actor.recv()? {
+            MsgOut::Addresses(addresses) => TestActor::check_eq(expected, addresses, "Wrong addresses")?,
+            _ => TestActor::check(Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "Wrong message type")), "Addresses not announced first")?,
+        }
+        loop {
+            match actor.recv()? {
+                MsgOut::AtDeadline(_) => {
+                    deadline += RECV_ONE_MSG_ACTOR_SLICE;
+                    actor.send(MsgIn::GoToDeadline(deadline))?;
+                },
+                MsgOut::SendPacket(_) => break,
+                _ => (),
+            }
+        }
+        actor.send(MsgIn::EndSimulation)
+    }
 
-        if total_usec <= 10 * delay_micros {
-            error!("Message received really too late: short before {}us instead of short after {}us", total_usec, delay_micros);
+    #[test]
+    fn send_from() {
+        init();
+
+        let actor = TestActorDesc::new("titi", addresses_actor);
+        let args = &["-atiti", "-n", local_vsg_address_str!(), "--alias", "10.0.0.2", "-t1970-01-01T00:00:00"];
+        let context = super::init(args, Box::new(dummy_recv_callback), Box::new(dummy_deadline_callback))
+            .expect("init failed");
+        assert_eq!(2, context.addresses().len());
+
+        context.start()
+            .expect("start failed");
+
+        let dst = crate::VsgAddress::V4(remote_vsg_address!());
+        match context.send_from(dst, dst, b"Foo msg").expect_err("send_from should have failed") {
+            crate::error::Error::InvalidAddress => (),
+            _ => assert!(false),
         }
 
-        assert_eq!((delay_micros / slice_micros) as usize, deadline_notifier.num_called());
-        assert_eq!(Duration::from_micros(delay_micros), deadline_notifier.deadline());
+        let alias = context.addresses()[1];
+        context.send_from(alias, dst, b"Foo msg")
+            .expect("send_from failed");
 
         context.stop();
 
@@ -751,71 +1905,1180 @@ mod test {
     }
 
     #[test]
-    fn recv_delayed() {
+    fn delivery_report() {
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
         init();
 
-        for delay_slices in 1..=100 {
-            recv_one_delay(100, delay_slices * 100);
+        let actor = TestActorDesc::new("titi", report_actor);
+        let context = super::init(valid_args!(), Box::new(dummy_recv_callback), Box::new(dummy_deadline_callback))
+            .expect("init failed");
+
+        let reported_seq_id = Arc::new(AtomicU64::new(0));
+        let reported_loss = Arc::new(AtomicBool::new(false));
+        let (cb_seq_id, cb_loss) = (reported_seq_id.clone(), reported_loss.clone());
+        context.set_report_callback(Some(Box::new(move |r| {
+            cb_loss.store(r.drop_reason == Some(super::DropReason::Loss), Ordering::Relaxed);
+            cb_seq_id.store(r.seq_id, Ordering::Release);
+        })));
+
+        context.start()
+            .expect("start failed");
+
+        let dst = crate::VsgAddress::V4(remote_vsg_address!());
+        let seq_id = context.send_ex(context.addresses()[0], dst, &Default::default(), b"Foo msg")
+            .expect("send_ex failed");
+        assert_ne!(0, seq_id);
+
+        for _ in 0..1000 {
+            if reported_seq_id.load(Ordering::Acquire) != 0 {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(1));
         }
+        assert_eq!(seq_id, reported_seq_id.load(Ordering::Acquire));
+        assert!(reported_loss.load(Ordering::Relaxed));
+
+        context.stop();
+
+        drop(actor);
     }
 
-    #[test]
-    fn recv_too_big() {
-        init();
+    const DISK_ACTOR_LATENCY: Duration = Duration::from_micros(250);
 
-        const EXPECTED_MSG: &[u8] = b"Foo msg";
-        const ORIG_BUFFER: &[u8] = b"fOO~MS";
-        let mut buffer: [u8; ORIG_BUFFER.len()] = Default::default();
-        buffer.copy_from_slice(ORIG_BUFFER);
+    fn disk_actor(actor: &mut TestActor) -> TestResult<()> {
+        use super::connector::{MsgIn, MsgOut};
 
-        let actor = TestActorDesc::new("titi", |actor| send_one_msg_actor(actor, EXPECTED_MSG));
+        let mut deadline = Duration::from_micros(0);
+        let mut request = None;
+        while request.is_none() {
+            deadline = run_slice(actor, deadline, |msg| {
+                if let MsgOut::DiskRequest(r) = msg {
+                    request = Some(r);
+                }
+                Ok(())
+            })?;
+        }
 
-        let recv_notifier = RecvNotifier::new();
-        let context = super::init(valid_args!(), recv_notifier.get_callback(), Box::new(dummy_deadline_callback))
+        let request = request.unwrap();
+        TestActor::check_eq(super::DiskOp::Write, request.op, "Wrong disk operation")?;
+        TestActor::check_eq(4096, request.size, "Wrong disk request size")?;
+        TestActor::check_eq("disk0", request.disk(), "Wrong disk name")?;
+        actor.send(MsgIn::DiskCompletion(super::DiskCompletion {
+            request_id: request.request_id,
+            time: request.time + DISK_ACTOR_LATENCY,
+        }))?;
+        run_slice(actor, deadline, |_| Ok(()))?;
+        actor.send(MsgIn::EndSimulation)
+    }
+
+    #[test]
+    fn disk_submit() {
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicU64, Ordering};
+
+        init();
+
+        let actor = TestActorDesc::new("titi", disk_actor);
+        let context = super::init(valid_args!(), Box::new(dummy_recv_callback), Box::new(dummy_deadline_callback))
             .expect("init failed");
 
+        let completed_request_id = Arc::new(AtomicU64::new(0));
+        let cb_request_id = completed_request_id.clone();
+        context.set_disk_callback(Some(Box::new(move |c| {
+            cb_request_id.store(c.request_id, Ordering::Release);
+        })));
+
         context.start()
             .expect("start failed");
 
-        recv_notifier.wait(1000);
-
-        match context.recv(&mut buffer).expect_err("recv should have failed") {
+        let name = "d".repeat(super::disk::MAX_DISK_NAME_LEN + 1);
+        match context.disk_submit(&name, super::DiskOp::Write, 4096).expect_err("disk_submit should have failed") {
             crate::error::Error::SizeTooBig => (),
             _ => assert!(false),
         }
 
-        assert_eq!(buffer, ORIG_BUFFER);
+        let request_id = context.disk_submit("disk0", super::DiskOp::Write, 4096)
+            .expect("disk_submit failed");
+        assert_ne!(0, request_id);
+
+        for _ in 0..1000 {
+            if completed_request_id.load(Ordering::Acquire) != 0 {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(request_id, completed_request_id.load(Ordering::Acquire));
 
         context.stop();
 
         drop(actor);
     }
 
+    const EXECUTE_ACTOR_FLOPS: f64 = 5e9;
+
+    fn execute_actor(actor: &mut TestActor) -> TestResult<()> {
+        use super::connector::{MsgIn, MsgOut};
+
+        let mut deadline = Duration::from_micros(0);
+        let mut request = None;
+        while request.is_none() {
+            deadline = run_slice(actor, deadline, |msg| {
+                if let MsgOut::Execute(r) = msg {
+                    request = Some(r);
+                }
+                Ok(())
+            })?;
+        }
+
+        let request = request.unwrap();
+        TestActor::check_eq(EXECUTE_ACTOR_FLOPS, request.flops, "Wrong number of flops")?;
+        // Complete the computation at the deadline just reached
+        actor.send(MsgIn::ExecuteCompletion(super::compute::ExecuteCompletion {
+            request_id: request.request_id,
+            time: deadline,
+        }))?;
+        run_slice(actor, deadline, |_| Ok(()))?;
+        actor.send(MsgIn::EndSimulation)
+    }
+
     #[test]
-    fn poll() {
+    fn execute() {
         init();
 
-        const EXPECTED_MSG: &[u8] = b"Foo msg";
-        let mut buffer = [0u8; EXPECTED_MSG.len()];
+        let actor = TestActorDesc::new("titi", execute_actor);
+        let context = super::init(valid_args!(), Box::new(dummy_recv_callback), Box::new(dummy_deadline_callback))
+            .expect("init failed");
 
-        let actor = TestActorDesc::new("titi", |actor| send_one_msg_actor(actor, EXPECTED_MSG));
+        context.start()
+            .expect("start failed");
+
+        let completion_time = context.execute(EXECUTE_ACTOR_FLOPS)
+            .expect("execute failed");
+        assert_ne!(0, completion_time.as_nanos());
+        assert_eq!(0, completion_time.as_nanos() % RECV_ONE_MSG_ACTOR_SLICE.as_nanos());
+
+        context.stop();
+
+        drop(actor);
+    }
+
+    #[test]
+    fn execute_not_started() {
+        init();
+
+        let actor = TestActorDesc::new("titi", TestActor::dummy_actor);
+        let context = super::init(valid_args!(), Box::new(dummy_recv_callback), Box::new(dummy_deadline_callback))
+            .expect("init failed");
+
+        match context.execute(EXECUTE_ACTOR_FLOPS) {
+            Err(crate::error::Error::NotStarted) => (),
+            res => panic!("execute returned {:?}", res),
+        }
+
+        drop(actor);
+    }
+
+    #[test]
+    fn execute_ended() {
+        init();
 
+        let actor = TestActorDesc::new("titi", start_actor);
         let context = super::init(valid_args!(), Box::new(dummy_recv_callback), Box::new(dummy_deadline_callback))
             .expect("init failed");
 
         context.start()
             .expect("start failed");
+        context.stop();
 
-        while !context.poll().is_some() {
-            std::thread::sleep(std::time::Duration::from_micros(1000));
+        match context.execute(EXECUTE_ACTOR_FLOPS) {
+            Err(crate::error::Error::SimulationEnded) => (),
+            res => panic!("execute returned {:?}", res),
         }
 
-        let (src, dst, buffer) = context.recv(&mut buffer)
-            .expect("recv failed");
+        drop(actor);
+    }
 
-        assert_eq!(src, local_vsg_address!());
-        assert_eq!(dst, remote_vsg_address!());
-        assert_eq!(buffer, EXPECTED_MSG);
+    fn marker_actor(actor: &mut TestActor) -> TestResult<()> {
+        use super::connector::{MsgIn, MsgOut};
+
+        let mut deadline = Duration::from_micros(0);
+        let mut marker = None;
+        while marker.is_none() {
+            deadline = run_slice(actor, deadline, |msg| {
+                if let MsgOut::Marker(m) = msg {
+                    marker = Some(m);
+                }
+                Ok(())
+            })?;
+        }
+
+        let marker = marker.unwrap();
+        TestActor::check_eq("leader elected", marker.label(), "Wrong marker label")?;
+        TestActor::check_eq(42, marker.value, "Wrong marker value")?;
+        TestActor::check_eq(true, marker.time <= deadline, "Marker is after deadline")?;
+        actor.send(MsgIn::EndSimulation)
+    }
+
+    #[test]
+    fn mark() {
+        init();
+
+        let actor = TestActorDesc::new("titi", marker_actor);
+        let context = super::init(valid_args!(), Box::new(dummy_recv_callback), Box::new(dummy_deadline_callback))
+            .expect("init failed");
+
+        context.start()
+            .expect("start failed");
+
+        context.mark("leader elected", 42)
+            .expect("mark failed");
+
+        context.stop();
+
+        drop(actor);
+    }
+
+    #[test]
+    fn save_load_state() {
+        use super::state::{SavedInput, SavedPacket, State};
+        use super::{PacketAttributes, VsgAddress};
+
+        init();
+
+        let actor = TestActorDesc::new("titi", TestActor::dummy_actor);
+        let context = super::init(valid_args!(), Box::new(dummy_recv_callback), Box::new(dummy_deadline_callback))
+            .expect("init failed");
+
+        let local = VsgAddress::V4(local_vsg_address!());
+        let remote = VsgAddress::V4(remote_vsg_address!());
+        let attributes = PacketAttributes {
+            protocol: 17,
+            src_port: 1234,
+            dst_port: 53,
+            priority: 0,
+            flow_id: 0,
+            wire_size: 0,
+        };
+        let state = State {
+            prev_deadline: Duration::from_micros(100),
+            next_deadline: Duration::from_micros(200),
+            next_seq_id: 42,
+            outgoing: vec![SavedPacket {
+                src: local,
+                dst: remote,
+                attributes,
+                seq_id: 41,
+                send_time: Duration::from_micros(150),
+                payload: b"Foo msg".to_vec(),
+            }],
+            upcoming: vec![SavedPacket {
+                src: local,
+                dst: remote,
+                attributes: PacketAttributes::default(),
+                seq_id: 0,
+                send_time: Duration::from_micros(250),
+                payload: b"Bar msg".to_vec(),
+            }],
+            input: vec![SavedInput {
+                src: remote,
+                dst: local,
+                attributes,
+                payload: b"Baz msg".to_vec(),
+            }],
+        };
+
+        match context.load_state(b"garbage") {
+            Err(crate::error::Error::InvalidState) => (),
+            _ => assert!(false),
+        }
+        context.load_state(&state.encode())
+            .expect("load_state failed");
+
+        match context.load_state(&state.encode()) {
+            Err(crate::error::Error::AlreadyLoaded) => (),
+            res => panic!("load_state returned {:?}", res),
+        }
+
+        // Saving does not consume anything
+        let saved = State::decode(&context.save_state().expect("save_state failed"))
+            .expect("Saved state is invalid");
+        assert_eq!(state, saved);
+
+        let mut buffer = [0u8; crate::MAX_PACKET_SIZE];
+        let (src, dst, recv_attributes, msg) = context.recv_ex(&mut buffer)
+            .expect("recv_ex failed");
+        assert_eq!(remote, src);
+        assert_eq!(local, dst);
+        assert_eq!(attributes, recv_attributes);
+        assert_eq!(b"Baz msg", msg);
+
+        drop(actor);
+    }
+
+    #[test]
+    fn save_state_running() {
+        init();
+
+        let actor = TestActorDesc::new("titi", recv_one_msg_actor);
+        let context = super::init(valid_args!(), Box::new(dummy_recv_callback), Box::new(dummy_deadline_callback))
+            .expect("init failed");
+
+        context.start()
+            .expect("start failed");
+
+        match context.save_state() {
+            Err(crate::error::Error::SimulationRunning) => (),
+            res => panic!("save_state returned {:?}", res.map(|_| ())),
+        }
+
+        // Terminate gracefully
+        let dst = remote_vsg_address!();
+        context.send(dst, b"Foo msg")
+            .expect("send failed");
+
+        context.stop();
+
+        context.save_state()
+            .expect("save_state failed");
+
+        drop(actor);
+    }
+
+    fn idle_hint_actor(actor: &mut TestActor) -> TestResult<()> {
+        use super::connector::{MsgIn, MsgOut};
+
+        let mut deadline = Duration::from_micros(0);
+        loop {
+            deadline += RECV_ONE_MSG_ACTOR_SLICE;
+            actor.send(MsgIn::GoToDeadline(deadline))?;
+            match actor.recv()? {
+                MsgOut::AtDeadline(Some(idle_until)) => {
+                    TestActor::check_eq(true, idle_until > deadline, "Idle hint is not after deadline")?;
+                    break;
+                },
+                _ => (),
+            }
+        }
+        actor.send(MsgIn::EndSimulation)
+    }
+
+    #[test]
+    fn idle_hint() {
+        init();
+
+        let actor = TestActorDesc::new("titi", idle_hint_actor);
+        let context = super::init(valid_args!(), Box::new(dummy_recv_callback), Box::new(dummy_deadline_callback))
+            .expect("init failed");
+
+        context.start()
+            .expect("start failed");
+
+        context.set_idle_hint(Some(Duration::from_secs(1)));
+
+        context.stop();
+
+        drop(actor);
+    }
+
+    // Delivers a message once the application gives an idle hint, then checks that the hint is
+    // withdrawn until the application answers
+    fn idle_hint_cancel_actor(actor: &mut TestActor) -> TestResult<()> {
+        use super::connector::{create_deliver_packet, MsgIn, MsgOut};
+
+        let mut deadline = Duration::from_micros(0);
+        loop {
+            deadline += RECV_ONE_MSG_ACTOR_SLICE;
+            actor.send(MsgIn::GoToDeadline(deadline))?;
+            if let MsgOut::AtDeadline(Some(_)) = actor.recv()? {
+                break;
+            }
+        }
+
+        let mut builder = flatbuffers::FlatBufferBuilder::new();
+        create_deliver_packet(&mut builder, remote_vsg_address!(), local_vsg_address!(), b"Foo msg");
+        let fb = builder.finished_data();
+        let buffer_pool = crate::BufferPool::<crate::bytes_buffer::BytesBuffer>::new(fb.len(), 1);
+        let mut buffer = TestActor::check(buffer_pool.allocate_buffer(fb.len()), "Buffer allocation failed")?;
+        buffer.copy_from_slice(fb);
+        actor.send(TestActor::check(MsgIn::new_deliver_packet(buffer), "Invalid DeliverPacket")?)?;
+
+        let mut answered = false;
+        loop {
+            deadline += RECV_ONE_MSG_ACTOR_SLICE;
+            actor.send(MsgIn::GoToDeadline(deadline))?;
+            loop {
+                match actor.recv()? {
+                    MsgOut::AtDeadline(idle_until) => {
+                        TestActor::check_eq(None, idle_until, "Idle hint not withdrawn by delivery")?;
+                        break;
+                    },
+                    MsgOut::SendPacket(_) => answered = true,
+                    _ => (),
+                }
+            }
+            if answered {
+                break;
+            }
+        }
+        actor.send(MsgIn::EndSimulation)
+    }
+
+    #[test]
+    fn idle_hint_cancel() {
+        init();
+
+        let actor = TestActorDesc::new("titi", idle_hint_cancel_actor);
+        let recv_notifier = RecvNotifier::new();
+        let context = super::init(valid_args!(), recv_notifier.get_callback(), Box::new(dummy_deadline_callback))
+            .expect("init failed");
+
+        context.start()
+            .expect("start failed");
+
+        context.set_idle_hint(Some(Duration::from_secs(1)));
+
+        recv_notifier.wait(1000);
+
+        let mut buffer = [0u8; 32];
+        context.recv(&mut buffer)
+            .expect("recv failed");
+        context.send(remote_vsg_address!(), b"Bar msg")
+            .expect("send failed");
+
+        context.stop();
+
+        drop(actor);
+    }
+
+    // Delivers back the fragments of the first message sent, after passing them to edit, which
+    // returns false to drop a fragment. Then lets the reassembly timeout expire. Fragments may be
+    // sent across several time slices.
+    fn echo_fragments_actor(actor: &mut TestActor, edit: fn(usize, &mut Vec<u8>) -> bool) -> TestResult<()> {
+        use super::connector::{create_deliver_packet, tansiv, MsgIn, MsgOut};
+        use super::fragment::FragmentHeader;
+
+        let mut deadline = Duration::from_micros(0);
+        let mut fragments: Vec<Vec<u8>> = vec![];
+        while fragments.is_empty() || fragments.len() < TestActor::check(FragmentHeader::read(&fragments[0]), "Invalid fragment")?.count as usize {
+            deadline = run_slice(actor, deadline, |msg| {
+                if let MsgOut::SendPacket(p) = msg {
+                    let msg = flatbuffers::size_prefixed_root::<tansiv::ToTansivMsg>(p.finished_data()).unwrap();
+                    fragments.push(msg.content_as_send_packet().unwrap().payload().unwrap().to_vec());
+                }
+                Ok(())
+            })?;
+        }
+
+        let buffer_pool = crate::BufferPool::<crate::bytes_buffer::BytesBuffer>::new(crate::connector::INPUT_BUFFER_SIZE, 1);
+        for (index, mut fragment) in fragments.into_iter().enumerate() {
+            if !edit(index, &mut fragment) {
+                continue;
+            }
+            let mut builder = flatbuffers::FlatBufferBuilder::new();
+            create_deliver_packet(&mut builder, remote_vsg_address!(), local_vsg_address!(), &fragment);
+            let fb = builder.finished_data();
+            let mut buffer = TestActor::check(buffer_pool.allocate_buffer(fb.len()), "Buffer allocation failed")?;
+            buffer.copy_from_slice(fb);
+            actor.send(TestActor::check(MsgIn::new_deliver_packet(buffer), "Invalid DeliverPacket")?)?;
+        }
+
+        // Fragmented tests use a reassembly timeout of one time slice
+        for _ in 0..3 {
+            deadline = run_slice(actor, deadline, |_| Ok(()))?;
+        }
+        actor.send(MsgIn::EndSimulation)
+    }
+
+    macro_rules! fragment_args {
+        () => {
+            &["-atiti", "-n", local_vsg_address_str!(), "-t1970-01-01T00:00:00", "--fragment", "--reassembly_timeout", "100"]
+        }
+    }
+
+    fn fragmented_msg() -> Vec<u8> {
+        (0..(3 * crate::MAX_PACKET_SIZE)).map(|i| i as u8).collect()
+    }
+
+    // Waits for the end of the simulation and returns the number of messages dropped by reassembly
+    fn wait_reassembly_drops(context: &super::Context) -> u64 {
+        for _ in 0..1000 {
+            if context.termination_reason().is_some() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(Some(crate::TerminationReason::ActorRequest), context.termination_reason());
+        context.stats().drops_reassembly
+    }
+
+    #[test]
+    fn send_fragmented() {
+        init();
+
+        let actor = TestActorDesc::new("titi", |actor| echo_fragments_actor(actor, |_, _| true));
+        let recv_notifier = RecvNotifier::new();
+        let context = super::init(fragment_args!(), recv_notifier.get_callback(), Box::new(dummy_deadline_callback))
+            .expect("init failed");
+
+        context.start()
+            .expect("start failed");
+
+        let msg = fragmented_msg();
+        context.send(remote_vsg_address!(), &msg)
+            .expect("send failed");
+
+        recv_notifier.wait(1000);
+
+        let mut buffer = vec![0u8; msg.len() + 1];
+        let (src, dst, payload) = context.recv(&mut buffer)
+            .expect("recv failed");
+        assert_eq!(remote_vsg_address!(), src);
+        assert_eq!(local_vsg_address!(), dst);
+        assert_eq!(&msg[..], payload);
+        assert!(context.stats().packets_received > 1);
+        assert_eq!(0, wait_reassembly_drops(&context));
+
+        context.stop();
+
+        drop(actor);
+    }
+
+    fn recv_fragmented_drop(edit: fn(usize, &mut Vec<u8>) -> bool) -> u64 {
+        init();
+
+        let actor = TestActorDesc::new("titi", move |actor| echo_fragments_actor(actor, edit));
+        let context = super::init(fragment_args!(), Box::new(dummy_recv_callback), Box::new(dummy_deadline_callback))
+            .expect("init failed");
+
+        context.start()
+            .expect("start failed");
+
+        context.send(remote_vsg_address!(), &fragmented_msg())
+            .expect("send failed");
+
+        let drops = wait_reassembly_drops(&context);
+        let mut buffer = [0u8; 4 * crate::MAX_PACKET_SIZE];
+        match context.recv(&mut buffer) {
+            Err(crate::error::Error::NoMessageAvailable) => (),
+            _ => assert!(false),
+        }
+
+        context.stop();
+
+        drop(actor);
+        drops
+    }
+
+    #[test]
+    fn recv_fragmented_lost() {
+        // The incomplete message expires
+        assert_eq!(1, recv_fragmented_drop(|index, _| index != 1));
+    }
+
+    #[test]
+    fn recv_fragmented_corrupt() {
+        use super::fragment::FragmentHeader;
+
+        // The corrupt fragment is rejected, the incomplete message expires
+        assert_eq!(2, recv_fragmented_drop(|index, fragment| {
+            if index == 0 {
+                let mut header = FragmentHeader::read(fragment).unwrap();
+                header.total_len += 1000;
+                header.write(fragment);
+            }
+            true
+        }));
+    }
+
+    #[test]
+    fn recv() {
+        init();
+
+        const EXPECTED_MSG: &[u8] = b"Foo msg";
+        let mut buffer = [0u8; EXPECTED_MSG.len()];
+
+        let actor = TestActorDesc::new("titi", |actor| send_one_msg_actor(actor, EXPECTED_MSG));
+
+        let recv_notifier = RecvNotifier::new();
+        let deadline_notifier = DeadlineNotifier::new();
+        let context = super::init(valid_args!(), recv_notifier.get_callback(), deadline_notifier.get_callback())
+            .expect("init failed");
+
+        context.start()
+            .expect("start failed");
+
+        recv_notifier.wait(1000);
+
+        let (src, dst, buffer) = context.recv(&mut buffer)
+            .expect("recv failed");
+
+        assert_eq!(src, local_vsg_address!());
+        assert_eq!(dst, remote_vsg_address!());
+        assert_eq!(buffer, EXPECTED_MSG);
+
+        assert_eq!(1, deadline_notifier.num_called());
+        assert_eq!(SEND_ONE_MSG_ACTOR_DELAY, deadline_notifier.deadline());
+
+        context.stop();
+
+        drop(actor);
+    }
+
+    fn recv_one_delay(slice_micros: u64, delay_micros: u64) {
+        const EXPECTED_MSG: &[u8] = b"Foo msg";
+        let mut buffer = [0u8; EXPECTED_MSG.len()];
+
+        let actor = TestActorDesc::new("titi", |actor| send_one_delayed_msg_actor(actor, EXPECTED_MSG, slice_micros, delay_micros));
+
+        let recv_notifier = RecvNotifier::new();
+        let deadline_notifier = DeadlineNotifier::new();
+        let context = super::init(valid_args!(), recv_notifier.get_callback(), deadline_notifier.get_callback())
+            .expect("init failed");
+
+        context.start()
+            .expect("start failed");
+
+        recv_notifier.wait(delay_micros / 10);
+
+        let (src, dst, buffer) = context.recv(&mut buffer)
+            .expect("recv failed");
+        let tv = context.gettimeofday();
+
+        // FIXME: Use the remote address when available in the protocol
+        assert_eq!(src, local_vsg_address!());
+        assert_eq!(dst, remote_vsg_address!());
+        assert_eq!(buffer, EXPECTED_MSG);
+        let total_usec = tv.tv_sec as u64 * 1_000_000 + tv.tv_usec as u64;
+        assert!(delay_micros <= total_usec, "Message received too early: before {}us instead of after {}us", total_usec, delay_micros);
+
+        if total_usec <= 10 * delay_micros {
+            error!("Message received really too late: short before {}us instead of short after {}us", total_usec, delay_micros);
+        }
+
+        assert_eq!((delay_micros / slice_micros) as usize, deadline_notifier.num_called());
+        assert_eq!(Duration::from_micros(delay_micros), deadline_notifier.deadline());
+
+        context.stop();
+
+        drop(actor);
+    }
+
+    #[test]
+    fn recv_delayed() {
+        init();
+
+        for delay_slices in 1..=100 {
+            recv_one_delay(100, delay_slices * 100);
+        }
+    }
+
+    #[test]
+    fn recv_too_big() {
+        init();
+
+        const EXPECTED_MSG: &[u8] = b"Foo msg";
+        const ORIG_BUFFER: &[u8] = b"fOO~MS";
+        let mut buffer: [u8; ORIG_BUFFER.len()] = Default::default();
+        buffer.copy_from_slice(ORIG_BUFFER);
+
+        let actor = TestActorDesc::new("titi", |actor| send_one_msg_actor(actor, EXPECTED_MSG));
+
+        let recv_notifier = RecvNotifier::new();
+        let context = super::init(valid_args!(), recv_notifier.get_callback(), Box::new(dummy_deadline_callback))
+            .expect("init failed");
+
+        context.start()
+            .expect("start failed");
+
+        recv_notifier.wait(1000);
+
+        match context.recv(&mut buffer).expect_err("recv should have failed") {
+            crate::error::Error::SizeTooBig => (),
+            _ => assert!(false),
+        }
+
+        assert_eq!(buffer, ORIG_BUFFER);
+
+        context.stop();
+
+        drop(actor);
+    }
+
+    #[test]
+    fn poll() {
+        init();
+
+        const EXPECTED_MSG: &[u8] = b"Foo msg";
+        let mut buffer = [0u8; EXPECTED_MSG.len()];
+
+        let actor = TestActorDesc::new("titi", |actor| send_one_msg_actor(actor, EXPECTED_MSG));
+
+        let context = super::init(valid_args!(), Box::new(dummy_recv_callback), Box::new(dummy_deadline_callback))
+            .expect("init failed");
+
+        context.start()
+            .expect("start failed");
+
+        while !context.poll().is_some() {
+            std::thread::sleep(std::time::Duration::from_micros(1000));
+        }
+
+        let (src, dst, buffer) = context.recv(&mut buffer)
+            .expect("recv failed");
+
+        assert_eq!(src, local_vsg_address!());
+        assert_eq!(dst, remote_vsg_address!());
+        assert_eq!(buffer, EXPECTED_MSG);
+
+        context.stop();
+
+        drop(actor);
+    }
+
+    #[test]
+    fn inject_delivery() {
+        use super::VsgAddress;
+
+        init();
+
+        const EXPECTED_MSG: &[u8] = b"Foo msg";
+        let mut buffer = [0u8; EXPECTED_MSG.len()];
+
+        let actor = TestActorDesc::new("titi", recv_one_msg_actor);
+
+        let recv_notifier = RecvNotifier::new();
+        let context = super::init(valid_args!(), recv_notifier.get_callback(), Box::new(dummy_deadline_callback))
+            .expect("init failed");
+
+        let src = VsgAddress::V4(remote_vsg_address!());
+        context.inject_delivery(src, EXPECTED_MSG)
+            .expect("inject_delivery failed");
+
+        context.start()
+            .expect("start failed");
+
+        // Let the actor end the simulation. Sending after recv_notifier.wait() would race with
+        // the deadline handler, which calls the receive callback before resuming time.
+        context.send(remote_vsg_address!(), b"Bar msg")
+            .expect("send failed");
+
+        recv_notifier.wait(1000);
+
+        let (msg_src, msg_dst, buffer) = context.recv(&mut buffer)
+            .expect("recv failed");
+
+        assert_eq!(VsgAddress::V4(msg_src), src);
+        assert_eq!(msg_dst, local_vsg_address!());
+        assert_eq!(buffer, EXPECTED_MSG);
+        assert_eq!(1, context.stats().packets_received);
+
+        context.stop();
+
+        drop(actor);
+    }
+
+    #[test]
+    fn recv_ipv6_not_lost() {
+        use super::VsgAddress;
+
+        init();
+
+        const EXPECTED_MSG: &[u8] = b"Foo msg";
+        let mut buffer = [0u8; EXPECTED_MSG.len()];
+
+        let actor = TestActorDesc::new("titi", recv_one_msg_actor);
+
+        let recv_notifier = RecvNotifier::new();
+        let context = super::init(valid_args!(), recv_notifier.get_callback(), Box::new(dummy_deadline_callback))
+            .expect("init failed");
+
+        let src = std::net::Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 1).octets();
+        context.inject_delivery(VsgAddress::V6(src), EXPECTED_MSG)
+            .expect("inject_delivery failed");
+
+        context.start()
+            .expect("start failed");
+
+        // See inject_delivery()
+        context.send(remote_vsg_address!(), b"Bar msg")
+            .expect("send failed");
+
+        recv_notifier.wait(1000);
+
+        match context.recv(&mut buffer) {
+            Err(crate::error::Error::InvalidAddress) => (),
+            res => panic!("recv returned {:?}", res),
+        }
+        let (msg_src, _, msg) = context.recv6(&mut buffer)
+            .expect("recv6 failed");
+        assert_eq!(src, msg_src);
+        assert_eq!(msg, EXPECTED_MSG);
+
+        context.stop();
+
+        drop(actor);
+    }
+
+    #[test]
+    fn tap_callback() {
+        use super::VsgAddress;
+
+        init();
+
+        let actor = TestActorDesc::new("titi", recv_one_msg_actor);
+        let context = super::init(valid_args!(), Box::new(dummy_recv_callback), Box::new(dummy_deadline_callback))
+            .expect("init failed");
+
+        let tapped = TapRecorder::new();
+        context.set_tap_callback(Some(tapped.get_callback()));
+
+        context.start()
+            .expect("start failed");
+
+        let dst = remote_vsg_address!();
+        context.send(dst, b"Foo msg")
+            .expect("send failed");
+
+        context.stop();
+
+        assert_eq!(1, tapped.num_tapped());
+        let packet = tapped.packet(0);
+        assert!(packet.send_time <= context.timer_context.simulation_next_deadline());
+        assert_eq!(VsgAddress::V4(local_vsg_address!()), packet.src);
+        assert_eq!(VsgAddress::V4(remote_vsg_address!()), packet.dst);
+        assert_eq!(b"Foo msg", packet.payload());
+
+        drop(actor);
+    }
+
+    #[test]
+    fn send_faults() {
+        init();
+
+        let actor = TestActorDesc::new("titi", recv_one_msg_actor);
+        let args = &["-atiti", "-n", local_vsg_address_str!(), "-t1970-01-01T00:00:00", "--fault", "corrupt,dir=send", "--fault", "duplicate,dir=send"];
+        let context = super::init(args, Box::new(dummy_recv_callback), Box::new(dummy_deadline_callback))
+            .expect("init failed");
+
+        let tapped = TapRecorder::new();
+        context.set_tap_callback(Some(tapped.get_callback()));
+
+        context.start()
+            .expect("start failed");
+
+        let dst = remote_vsg_address!();
+        context.send(dst, b"Foo msg")
+            .expect("send failed");
+
+        context.stop();
+
+        assert_eq!(2, tapped.num_tapped());
+        assert_eq!(tapped.packet(0).payload(), tapped.packet(1).payload());
+        let flipped_bits: u32 = tapped.packet(0).payload().iter().zip(b"Foo msg").map(|(a, b)| (a ^ b).count_ones()).sum();
+        assert_eq!(1, flipped_bits);
+        let stats = context.stats();
+        assert_eq!(1, stats.faults);
+        assert_eq!(2, stats.packets_sent);
+
+        drop(actor);
+    }
+
+    #[test]
+    fn send_faults_fragmented() {
+        use super::fragment::{FragmentHeader, FRAGMENT_HEADER_SIZE};
+
+        init();
+
+        let actor = TestActorDesc::new("titi", recv_one_msg_actor);
+        let args = &["-atiti", "-n", local_vsg_address_str!(), "-t1970-01-01T00:00:00", "--fragment", "--fault", "corrupt,dir=send"];
+        let context = super::init(args, Box::new(dummy_recv_callback), Box::new(dummy_deadline_callback))
+            .expect("init failed");
+
+        let tapped = TapRecorder::new();
+        context.set_tap_callback(Some(tapped.get_callback()));
+
+        context.start()
+            .expect("start failed");
+
+        let dst = remote_vsg_address!();
+        context.send(dst, b"Foo msg")
+            .expect("send failed");
+
+        context.stop();
+
+        // Only the payload is corrupted, not the fragment header
+        assert_eq!(1, tapped.num_tapped());
+        let packet = tapped.packet(0);
+        let header = FragmentHeader::read(packet.payload())
+            .expect("Invalid fragment header");
+        assert_eq!((0, 1, 7), (header.index, header.count, header.total_len));
+        let flipped_bits: u32 = packet.payload()[FRAGMENT_HEADER_SIZE..].iter().zip(b"Foo msg").map(|(a, b)| (a ^ b).count_ones()).sum();
+        assert_eq!(1, flipped_bits);
+
+        drop(actor);
+    }
+
+    #[test]
+    fn recv_faults_corrupt() {
+        init();
+
+        const EXPECTED_MSG: &[u8] = b"Foo msg";
+        let mut buffer = [0u8; EXPECTED_MSG.len()];
+
+        let actor = TestActorDesc::new("titi", |actor| send_one_msg_actor(actor, EXPECTED_MSG));
+
+        let recv_notifier = RecvNotifier::new();
+        let args = &["-atiti", "-n", local_vsg_address_str!(), "-t1970-01-01T00:00:00", "--fault", "corrupt,dir=recv"];
+        let context = super::init(args, recv_notifier.get_callback(), Box::new(dummy_deadline_callback))
+            .expect("init failed");
+
+        context.start()
+            .expect("start failed");
+
+        recv_notifier.wait(1000);
+
+        let (_, _, msg) = context.recv(&mut buffer)
+            .expect("recv failed");
+        let flipped_bits: u32 = msg.iter().zip(EXPECTED_MSG).map(|(a, b)| (a ^ b).count_ones()).sum();
+        assert_eq!(1, flipped_bits);
+        assert_eq!(1, context.stats().faults);
+
+        context.stop();
+
+        drop(actor);
+    }
+
+    const SYNTHETIC_SIZE: u32 = 1234;
+
+    // Echoes the first message, which must be synthetic
+    fn synthetic_echo_actor(actor: &mut TestActor) -> TestResult<()> {
+        use super::connector::{create_deliver_packet_with_attributes, tansiv, MsgIn, MsgOut};
+        use super::PacketAttributes;
+
+        let mut deadline = Duration::from_micros(0);
+        let mut wire_size = None;
+        while wire_size.is_none() {
+            deadline = run_slice(actor, deadline, |msg| {
+                if let MsgOut::SendPacket(p) = msg {
+                    let msg = flatbuffers::size_prefixed_root::<tansiv::ToTansivMsg>(p.finished_data()).unwrap();
+                    let send_packet = msg.content_as_send_packet().unwrap();
+                    TestActor::check_eq(0, send_packet.payload().unwrap().len(), "Synthetic message has a payload")?;
+                    wire_size = Some(send_packet.wire_size());
+                }
+                Ok(())
+            })?;
+        }
+
+        let attributes = PacketAttributes {
+            wire_size: wire_size.unwrap(),
+            ..Default::default()
+        };
+        let mut builder = flatbuffers::FlatBufferBuilder::new();
+        create_deliver_packet_with_attributes(&mut builder, remote_vsg_address!(), local_vsg_address!(), &attributes, &[]);
+        let fb = builder.finished_data();
+        let buffer_pool = crate::BufferPool::<crate::bytes_buffer::BytesBuffer>::new(fb.len(), 1);
+        let mut buffer = TestActor::check(buffer_pool.allocate_buffer(fb.len()), "Buffer allocation failed")?;
+        buffer.copy_from_slice(fb);
+        actor.send(TestActor::check(MsgIn::new_deliver_packet(buffer), "Invalid DeliverPacket")?)?;
+
+        run_slice(actor, deadline, |_| Ok(()))?;
+        actor.send(MsgIn::EndSimulation)
+    }
+
+    #[test]
+    fn synthetic() {
+        use super::VsgAddress;
+
+        init();
+
+        let actor = TestActorDesc::new("titi", synthetic_echo_actor);
+        let recv_notifier = RecvNotifier::new();
+        let context = super::init(valid_args!(), recv_notifier.get_callback(), Box::new(dummy_deadline_callback))
+            .expect("init failed");
+
+        context.start()
+            .expect("start failed");
+
+        match context.send_synthetic(VsgAddress::V4(remote_vsg_address!()), 0) {
+            Err(crate::error::Error::InvalidSize) => (),
+            res => panic!("send_synthetic returned {:?}", res),
+        }
+        context.send_synthetic(VsgAddress::V4(remote_vsg_address!()), SYNTHETIC_SIZE)
+            .expect("send_synthetic failed");
+
+        recv_notifier.wait(1000);
+
+        let (src, dst, size) = context.recv_synthetic()
+            .expect("recv_synthetic failed");
+        assert_eq!(VsgAddress::V4(remote_vsg_address!()), src);
+        assert_eq!(VsgAddress::V4(local_vsg_address!()), dst);
+        assert_eq!(SYNTHETIC_SIZE, size);
+        match context.recv_synthetic() {
+            Err(crate::error::Error::NoMessageAvailable) => (),
+            _ => assert!(false),
+        }
+
+        context.stop();
+
+        drop(actor);
+    }
+
+    #[test]
+    fn recv_synthetic_not_synthetic() {
+        init();
+
+        const EXPECTED_MSG: &[u8] = b"Foo msg";
+        let mut buffer = [0u8; EXPECTED_MSG.len()];
+
+        let actor = TestActorDesc::new("titi", |actor| send_one_msg_actor(actor, EXPECTED_MSG));
+
+        let recv_notifier = RecvNotifier::new();
+        let context = super::init(valid_args!(), recv_notifier.get_callback(), Box::new(dummy_deadline_callback))
+            .expect("init failed");
+
+        context.start()
+            .expect("start failed");
+
+        recv_notifier.wait(1000);
+
+        // The message is not lost
+        match context.recv_synthetic() {
+            Err(crate::error::Error::NotSynthetic) => (),
+            res => panic!("recv_synthetic returned {:?}", res),
+        }
+        let (_, _, msg) = context.recv(&mut buffer)
+            .expect("recv failed");
+        assert_eq!(msg, EXPECTED_MSG);
+
+        context.stop();
+
+        drop(actor);
+    }
+
+    #[test]
+    fn recv_faults() {
+        init();
+
+        const EXPECTED_MSG: &[u8] = b"Foo msg";
+        let mut buffer = [0u8; EXPECTED_MSG.len()];
+
+        let actor = TestActorDesc::new("titi", |actor| send_one_msg_actor(actor, EXPECTED_MSG));
+
+        let recv_notifier = RecvNotifier::new();
+        let args = &["-atiti", "-n", local_vsg_address_str!(), "-t1970-01-01T00:00:00", "--fault", "duplicate,dir=recv"];
+        let context = super::init(args, recv_notifier.get_callback(), Box::new(dummy_deadline_callback))
+            .expect("init failed");
+
+        context.start()
+            .expect("start failed");
+
+        recv_notifier.wait(1000);
+
+        for _ in 0..2 {
+            let (_, _, msg) = context.recv(&mut buffer)
+                .expect("recv failed");
+            assert_eq!(msg, EXPECTED_MSG);
+        }
+        assert_eq!(1, context.stats().faults);
+
+        context.stop();
+
+        drop(actor);
+    }
+
+    fn recv_two_msgs_actor(actor: &mut TestActor) -> TestResult<()> {
+        use super::connector::{MsgIn, MsgOut};
+
+        let mut deadline = Duration::from_micros(0);
+        let mut received = 0;
+        while received < 2 {
+            deadline = run_slice(actor, deadline, |msg| {
+                if let MsgOut::SendPacket(_) = msg {
+                    received += 1;
+                }
+                Ok(())
+            })?;
+        }
+        actor.send(MsgIn::EndSimulation)
+    }
+
+    #[test]
+    fn nic_rate() {
+        init();
+
+        let actor = TestActorDesc::new("titi", recv_two_msgs_actor);
+        // 1000 bytes per millisecond
+        let args = &["-atiti", "-n", local_vsg_address_str!(), "-t1970-01-01T00:00:00", "--nic_rate", "8000000"];
+        let context = super::init(args, Box::new(dummy_recv_callback), Box::new(dummy_deadline_callback))
+            .expect("init failed");
+
+        let tapped = TapRecorder::new();
+        context.set_tap_callback(Some(tapped.get_callback()));
+
+        context.start()
+            .expect("start failed");
+
+        let dst = remote_vsg_address!();
+        let msg = [0u8; 1000];
+        context.send(dst, &msg)
+            .expect("send failed");
+        context.send(dst, &msg)
+            .expect("send failed");
+
+        context.stop();
+
+        assert_eq!(2, tapped.num_tapped());
+        let (first, second) = (tapped.packet(0).send_time, tapped.packet(1).send_time);
+        assert!(first >= Duration::from_millis(1));
+        assert!(second - first >= Duration::from_millis(1));
+
+        drop(actor);
+    }
+
+    const STREAM_ACTOR_DEADLINE: Duration = Duration::from_millis(500);
+
+    // Runs a long time slice, during which the application sends one packet
+    fn stream_actor(actor: &mut TestActor) -> TestResult<()> {
+        use super::connector::{MsgIn, MsgOut};
+
+        actor.send(MsgIn::GoToDeadline(STREAM_ACTOR_DEADLINE))?;
+        loop {
+            if let MsgOut::AtDeadline(_) = actor.recv()? {
+                break;
+            }
+        }
+        actor.send(MsgIn::EndSimulation)
+    }
+
+    #[test]
+    fn stream() {
+        init();
+
+        let actor = TestActorDesc::new("titi", stream_actor);
+        let args = &["-atiti", "-n", local_vsg_address_str!(), "-t1970-01-01T00:00:00", "--stream"];
+        let context = super::init(args, Box::new(dummy_recv_callback), Box::new(dummy_deadline_callback))
+            .expect("init failed");
+
+        let tapped = TapRecorder::new();
+        context.set_tap_callback(Some(tapped.get_callback()));
+
+        context.start()
+            .expect("start failed");
+
+        context.send(remote_vsg_address!(), b"Foo msg")
+            .expect("send failed");
+
+        // The packet is sent long before the end of the time slice
+        for _ in 0..100 {
+            if tapped.num_tapped() > 0 {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(1, tapped.num_tapped());
+        assert_eq!(0, context.stats().deadlines);
 
         context.stop();
 
@@ -0,0 +1,281 @@
+use log::Level;
+use std::cell::UnsafeCell;
+use std::fmt::{self, Write};
+use std::os::unix::io::RawFd;
+use std::sync::{Mutex, Once};
+use std::sync::atomic::{fence, AtomicU64, Ordering};
+use std::time::Duration;
+
+// Longer messages are truncated
+const RECORD_TEXT_SIZE: usize = 240;
+const NUM_SLOTS: usize = 1024;
+const DRAIN_PERIOD: Duration = Duration::from_millis(10);
+
+#[derive(Clone, Copy)]
+struct Record {
+    // CLOCK_MONOTONIC, in nanoseconds
+    time: u64,
+    level: u8,
+    len: u16,
+    text: [u8; RECORD_TEXT_SIZE],
+}
+
+const EMPTY_RECORD: Record = Record {
+    time: 0,
+    level: 0,
+    len: 0,
+    text: [0; RECORD_TEXT_SIZE],
+};
+
+impl Record {
+    fn text(&self) -> &[u8] {
+        &self.text[..self.len as usize]
+    }
+}
+
+// Formats into a record without allocating
+struct RecordWriter<'a>(&'a mut Record);
+
+impl fmt::Write for RecordWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let len = self.0.len as usize;
+        let copy_len = std::cmp::min(s.len(), RECORD_TEXT_SIZE - len);
+        self.0.text[len..(len + copy_len)].copy_from_slice(&s.as_bytes()[..copy_len]);
+        self.0.len += copy_len as u16;
+        Ok(())
+    }
+}
+
+// seq is 0 until the slot is first used, then 2 * (index + 1) - 1 while record index is written
+// and 2 * (index + 1) once it is complete, index being the position in the whole log.
+struct Slot {
+    seq: AtomicU64,
+    record: UnsafeCell<Record>,
+}
+
+// Only used to initialize the slots of LogRing, each one is a copy
+#[allow(clippy::declare_interior_mutable_const)]
+const EMPTY_SLOT: Slot = Slot {
+    seq: AtomicU64::new(0),
+    record: UnsafeCell::new(EMPTY_RECORD),
+};
+
+// Preallocated ring of fixed-size log records, safe to write from signal handlers: writers
+// neither allocate nor lock. When the ring is full the oldest records are overwritten.
+//
+// Readers use the sequence number of each slot, as in a sequence lock, to skip records
+// overwritten while being read.
+pub struct LogRing {
+    slots: [Slot; NUM_SLOTS],
+    // Index of the next record to write
+    head: AtomicU64,
+    // Index of the next record to drain
+    // Concurrency: drainers are normal threads
+    tail: Mutex<u64>,
+    // Records overwritten before being drained
+    lost: AtomicU64,
+}
+
+// Safety: records are only accessed through the sequence protocol above
+unsafe impl Sync for LogRing {}
+
+pub static LOG_RING: LogRing = LogRing::new();
+
+fn monotonic_now() -> u64 {
+    let mut now = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+    // Safety: clock_gettime() is async-signal-safe and only writes to now
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut now) };
+    now.tv_sec as u64 * 1_000_000_000 + now.tv_nsec as u64
+}
+
+fn level_from_raw(level: u8) -> Level {
+    match level {
+        1 => Level::Error,
+        2 => Level::Warn,
+        3 => Level::Info,
+        4 => Level::Debug,
+        _ => Level::Trace,
+    }
+}
+
+impl LogRing {
+    pub const fn new() -> LogRing {
+        LogRing {
+            slots: [EMPTY_SLOT; NUM_SLOTS],
+            head: AtomicU64::new(0),
+            tail: Mutex::new(0),
+            lost: AtomicU64::new(0),
+        }
+    }
+
+    // Async-signal-safe as long as formatting args does not allocate
+    pub fn record(&self, level: Level, args: fmt::Arguments) {
+        let index = self.head.fetch_add(1, Ordering::Relaxed);
+        let slot = &self.slots[(index % NUM_SLOTS as u64) as usize];
+        slot.seq.store(2 * (index + 1) - 1, Ordering::Relaxed);
+        fence(Ordering::Release);
+        // Safety: readers discard the record if seq changes meanwhile
+        let record = unsafe { &mut *slot.record.get() };
+        record.time = monotonic_now();
+        record.level = level as u8;
+        record.len = 0;
+        let _ = RecordWriter(record).write_fmt(args);
+        slot.seq.store(2 * (index + 1), Ordering::Release);
+    }
+
+    // Reads record index. Returns None if it is not complete yet and Some(None) if it was
+    // overwritten.
+    fn read(&self, index: u64) -> Option<Option<Record>> {
+        let slot = &self.slots[(index % NUM_SLOTS as u64) as usize];
+        let done = 2 * (index + 1);
+        let seq = slot.seq.load(Ordering::Acquire);
+        if seq < done {
+            return None;
+        }
+        if seq > done {
+            return Some(None);
+        }
+        // Safety: the copy is discarded if a writer modified it meanwhile
+        let record = unsafe { std::ptr::read_volatile(slot.record.get()) };
+        fence(Ordering::Acquire);
+        if slot.seq.load(Ordering::Relaxed) == seq {
+            Some(Some(record))
+        } else {
+            Some(None)
+        }
+    }
+
+    // Passes the complete records not drained yet to f, in order. Not async-signal-safe.
+    pub fn drain<F>(&self, mut f: F)
+        where F: FnMut(Duration, Level, &str) {
+        let mut tail = self.tail.lock().unwrap();
+        let head = self.head.load(Ordering::Acquire);
+        if head - *tail > NUM_SLOTS as u64 {
+            self.lost.fetch_add(head - *tail - NUM_SLOTS as u64, Ordering::Relaxed);
+            *tail = head - NUM_SLOTS as u64;
+        }
+        while *tail < head {
+            match self.read(*tail) {
+                // Being written, retry at the next drain
+                None => break,
+                Some(None) => {
+                    self.lost.fetch_add(1, Ordering::Relaxed);
+                },
+                Some(Some(record)) => {
+                    f(Duration::from_nanos(record.time), level_from_raw(record.level), &String::from_utf8_lossy(record.text()));
+                },
+            }
+            *tail += 1;
+        }
+    }
+
+    pub fn drain_to_log(&self) {
+        self.drain(|time, level, text| {
+            log::log!(level, "[{}.{:09}] {}", time.as_secs(), time.subsec_nanos(), text);
+        });
+    }
+
+    pub fn lost(&self) -> u64 {
+        self.lost.load(Ordering::Relaxed)
+    }
+
+    // Writes the records still in the ring to fd, drained or not, one per line. Async-signal-safe,
+    // for instance to dump the log from a crash handler.
+    pub fn dump_fd(&self, fd: RawFd) -> std::io::Result<()> {
+        let head = self.head.load(Ordering::Acquire);
+        let first = head.saturating_sub(NUM_SLOTS as u64);
+        for index in first..head {
+            if let Some(Some(record)) = self.read(index) {
+                // Formatted on the stack
+                let mut line = EMPTY_RECORD;
+                let _ = write!(RecordWriter(&mut line), "[{}.{:09}] {} ", record.time / 1_000_000_000, record.time % 1_000_000_000, level_from_raw(record.level));
+                write_fd(fd, line.text())?;
+                write_fd(fd, record.text())?;
+                write_fd(fd, b"\n")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn write_fd(fd: RawFd, mut data: &[u8]) -> std::io::Result<()> {
+    while !data.is_empty() {
+        // Safety: write() is async-signal-safe and only reads data
+        let res = unsafe { libc::write(fd, data.as_ptr() as *const libc::c_void, data.len()) };
+        if res < 0 {
+            let error = std::io::Error::last_os_error();
+            if error.kind() != std::io::ErrorKind::Interrupted {
+                return Err(error);
+            }
+        } else {
+            data = &data[res as usize..];
+        }
+    }
+    Ok(())
+}
+
+// Forwards the records of LOG_RING to the log crate from a helper thread. Idempotent.
+pub fn start_drainer() {
+    static START: Once = Once::new();
+
+    START.call_once(|| {
+        let res = std::thread::Builder::new()
+            .name("tansiv-log".to_string())
+            .spawn(|| {
+                // The deadline handler must not interrupt the drainer while it holds locks, e.g. in
+                // the logger
+                if let Err(e) = crate::timer::block_deadline_signal() {
+                    log::error!("Deadline log drainer failed to block the deadline signal: {}", e);
+                    return;
+                }
+                loop {
+                    LOG_RING.drain_to_log();
+                    std::thread::sleep(DRAIN_PERIOD);
+                }
+            });
+        if let Err(e) = res {
+            log::error!("Failed to start the deadline log drainer: {}", e);
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use log::Level;
+    use super::{LogRing, NUM_SLOTS};
+
+    #[test]
+    fn drain() {
+        let ring = Box::new(LogRing::new());
+        ring.record(Level::Debug, format_args!("deadline = {:?}", std::time::Duration::from_micros(100)));
+        ring.record(Level::Info, format_args!("{}", "x".repeat(1000)));
+
+        let mut records = Vec::new();
+        ring.drain(|_, level, text| records.push((level, text.to_string())));
+        assert_eq!(2, records.len());
+        assert_eq!((Level::Debug, "deadline = 100µs".to_string()), records[0]);
+        assert_eq!(Level::Info, records[1].0);
+        assert_eq!(super::RECORD_TEXT_SIZE, records[1].1.len());
+
+        // Already drained
+        ring.drain(|_, _, _| panic!("Record drained twice"));
+    }
+
+    #[test]
+    fn overwrite() {
+        let ring = Box::new(LogRing::new());
+        for i in 0..(NUM_SLOTS + 10) {
+            ring.record(Level::Debug, format_args!("{}", i));
+        }
+
+        let mut first = None;
+        let mut count = 0;
+        ring.drain(|_, _, text| {
+            first.get_or_insert(text.parse::<usize>().unwrap());
+            count += 1;
+        });
+        assert_eq!(Some(10), first);
+        assert_eq!(NUM_SLOTS, count);
+        assert_eq!(10, ring.lost());
+    }
+}
@@ -0,0 +1,24 @@
+use crate::short_str::{self, ShortStr};
+use std::time::Duration;
+
+// Application event, such as a milestone, stamped with simulation time
+#[derive(Debug, Clone, Copy)]
+pub struct Marker {
+    pub time: Duration,
+    label: ShortStr,
+    pub value: i64,
+}
+
+impl Marker {
+    pub fn new(time: Duration, label: &str, value: i64) -> Result<Marker, short_str::Error> {
+        Ok(Marker {
+            time,
+            label: ShortStr::new(label)?,
+            value,
+        })
+    }
+
+    pub fn label(&self) -> &str {
+        self.label.as_str()
+    }
+}
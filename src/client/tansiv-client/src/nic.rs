@@ -0,0 +1,109 @@
+use crate::packet_attributes::PacketAttributes;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+// Token bucket model of the network interface of a context, as a generic cell rate algorithm
+//
+// Packets are serialized at rate bits per second. Up to burst bytes can be handed to the NIC
+// back-to-back without waiting for the previous packets, as after an idle period.
+//
+// The model is lock-free, so that application threads can send concurrently.
+#[derive(Debug)]
+pub struct Nic {
+    rate: u64,
+    // Time to serialize burst bytes, in nanoseconds
+    tolerance: u64,
+    // Theoretical arrival time of the next packet, in nanoseconds of simulation time
+    tat: AtomicU64,
+}
+
+// In nanoseconds, rounded up
+fn serialization_delay(rate: u64, size: u64) -> u64 {
+    let delay = (size as u128 * 8 * 1_000_000_000 + rate as u128 - 1) / rate as u128;
+    std::cmp::min(delay, u64::MAX as u128) as u64
+}
+
+impl Nic {
+    // rate must not be 0
+    pub fn new(rate: u64, burst: u64) -> Nic {
+        Nic {
+            rate,
+            tolerance: serialization_delay(rate, burst),
+            tat: AtomicU64::new(0),
+        }
+    }
+
+    // Returns the time at which the last bit of a packet of size bytes, handed to the NIC at
+    // simulation time now, leaves the NIC
+    pub fn send(&self, now: Duration, size: usize) -> Duration {
+        let now = now.as_nanos() as u64;
+        let delay = serialization_delay(self.rate, size as u64);
+        let mut tat = self.tat.load(Ordering::Relaxed);
+        loop {
+            let start = std::cmp::max(now, tat.saturating_sub(self.tolerance));
+            let next_tat = std::cmp::max(tat, now).saturating_add(delay);
+            match self.tat.compare_exchange_weak(tat, next_tat, Ordering::Relaxed, Ordering::Relaxed) {
+                Ok(_) => return Duration::from_nanos(start.saturating_add(delay)),
+                Err(current) => tat = current,
+            }
+        }
+    }
+
+    // Same as ::send() for a packet of len bytes having attributes. Its size on the wire is the
+    // wire size, if set.
+    pub fn send_packet(&self, now: Duration, attributes: &PacketAttributes, len: usize) -> Duration {
+        let size = if attributes.wire_size != 0 {
+            attributes.wire_size as usize
+        } else {
+            len
+        };
+        self.send(now, size)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::packet_attributes::PacketAttributes;
+    use std::time::Duration;
+    use super::Nic;
+
+    #[test]
+    fn serialization() {
+        // 1000 bytes per millisecond
+        let nic = Nic::new(8_000_000, 0);
+        let now = Duration::from_millis(10);
+
+        assert_eq!(Duration::from_millis(11), nic.send(now, 1000));
+        // Back-to-back
+        assert_eq!(Duration::from_millis(12), nic.send(now, 1000));
+        assert_eq!(Duration::from_micros(12_500), nic.send(now + Duration::from_micros(100), 500));
+        // After an idle period
+        assert_eq!(Duration::from_millis(21), nic.send(Duration::from_millis(20), 1000));
+    }
+
+    #[test]
+    fn burst() {
+        let nic = Nic::new(8_000_000, 2000);
+        let now = Duration::from_millis(10);
+
+        // The burst starts at once
+        assert_eq!(Duration::from_millis(11), nic.send(now, 1000));
+        assert_eq!(Duration::from_millis(11), nic.send(now, 1000));
+        assert_eq!(Duration::from_millis(11), nic.send(now, 1000));
+        // Then the rate applies
+        assert_eq!(Duration::from_millis(12), nic.send(now, 1000));
+    }
+
+    #[test]
+    fn wire_size() {
+        let nic = Nic::new(8_000_000, 0);
+        let now = Duration::from_millis(10);
+        let attributes = PacketAttributes {
+            wire_size: 2000,
+            ..Default::default()
+        };
+
+        assert_eq!(Duration::from_millis(12), nic.send_packet(now, &attributes, 10));
+        assert_eq!(Duration::from_millis(13), nic.send_packet(now, &PacketAttributes::default(), 1000));
+    }
+}
@@ -2,6 +2,7 @@ use crate::connector::SendPacketBuilder;
 use std::cell::UnsafeCell;
 use std::fmt;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
 #[derive(Debug)]
 pub enum Error {
@@ -23,6 +24,9 @@ impl std::error::Error for Error {}
 
 pub type OutputMsg = SendPacketBuilder;
 
+// Messages are inserted concurrently by application code. Consumers (::drain() and
+// ::drain_until()) must not run concurrently, which the users of the set ensure by holding the
+// connector lock.
 #[derive(Debug)]
 pub struct OutputMsgSet {
     slots: Vec<UnsafeCell<Option<OutputMsg>>>,
@@ -33,6 +37,8 @@ pub struct OutputMsgSet {
 pub struct OutputMsgDrain<'a> {
     msg_set: &'a OutputMsgSet,
     index: usize,
+    // Messages time-stamped later are left in the set
+    until: Option<Duration>,
 }
 
 impl<'a> Iterator for OutputMsgDrain<'a> {
@@ -43,7 +49,7 @@ impl<'a> Iterator for OutputMsgDrain<'a> {
         let num_slots = msg_set.slots.len();
         let next_index = self.index;
         for index in next_index..num_slots {
-            let val = msg_set.take_slot(index);
+            let val = msg_set.take_slot(index, self.until);
             if val.is_some() {
                 self.index = index + 1;
                 // let val = val.unwrap();
@@ -89,12 +95,29 @@ impl OutputMsgSet {
         OutputMsgDrain {
             msg_set: self,
             index: 0,
+            until: None,
         }
     }
 
-    fn take_slot(&self, index: usize) -> Option<OutputMsg> {
+    // Like ::drain(), but only takes the messages time-stamped until the given time (included)
+    pub fn drain_until<'a>(&'a self, until: Duration) -> OutputMsgDrain<'a> {
+        OutputMsgDrain {
+            msg_set: self,
+            index: 0,
+            until: Some(until),
+        }
+    }
+
+    fn take_slot(&self, index: usize, until: Option<Duration>) -> Option<OutputMsg> {
         if let Some(slot) = self.slot_valid.get(index) {
             if slot.load(Ordering::Acquire) {
+                // Valid slots are only modified by consumers
+                let send_time = unsafe { (*self.slots[index].get()).as_ref().map(|msg| msg.send_time()) };
+                if let (Some(until), Some(send_time)) = (until, send_time) {
+                    if send_time > until {
+                        return None;
+                    }
+                }
                 let output_msg = unsafe { self.slots[index].get().replace(None) };
 
                 slot.store(false, Ordering::Release);
@@ -0,0 +1,29 @@
+// Optional metadata of a packet, for the simulator to apply per-flow or per-class treatment
+// without parsing payloads
+//
+// Default (all-zero) attributes mean that the packet has no attributes. They are not sent on the
+// wire.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct PacketAttributes {
+    // L4 protocol number, as in the IP header (e.g. 6 for TCP, 17 for UDP)
+    pub protocol: u8,
+    pub src_port: u16,
+    pub dst_port: u16,
+    // DSCP value or any other priority class understood by the simulator
+    pub priority: u8,
+    // User-defined flow identifier
+    pub flow_id: u64,
+    // Size of the packet on the simulated wire, 0 for the length of the payload actually carried
+    pub wire_size: u32,
+}
+
+impl PacketAttributes {
+    pub fn is_empty(&self) -> bool {
+        *self == PacketAttributes::default()
+    }
+
+    // Whether the flow part of the attributes is set, that is everything but wire_size
+    pub fn has_flow(&self) -> bool {
+        PacketAttributes { wire_size: 0, ..*self } != PacketAttributes::default()
+    }
+}
@@ -0,0 +1,30 @@
+use std::time::Duration;
+
+// Why the simulator dropped a packet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DropReason {
+    Unknown,
+    // No route to the destination
+    Unreachable,
+    // Lost by the network model
+    Loss,
+    // A queue on the path was full
+    QueueFull,
+}
+
+// Outcome of a packet sent with delivery reports enabled
+//
+// seq_id is the sequence id returned when the packet was sent. time is the simulated time of
+// delivery, or of drop if drop_reason is set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeliveryReport {
+    pub seq_id: u64,
+    pub time: Duration,
+    pub drop_reason: Option<DropReason>,
+}
+
+impl DeliveryReport {
+    pub fn is_delivered(&self) -> bool {
+        self.drop_reason.is_none()
+    }
+}
@@ -0,0 +1,55 @@
+use std::fmt;
+
+#[derive(Debug)]
+pub enum Error {
+    TooLong,
+}
+
+type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let msg = match self {
+            Error::TooLong => "String too long",
+        };
+        write!(f, "{}", msg)
+    }
+}
+
+impl std::error::Error for Error {}
+
+pub const MAX_SHORT_STR_LEN: usize = 64;
+
+// String stored inline so that it can be queued and sent by the deadline handler without
+// allocation
+#[derive(Clone, Copy)]
+pub struct ShortStr {
+    len: usize,
+    bytes: [u8; MAX_SHORT_STR_LEN],
+}
+
+impl ShortStr {
+    pub fn new(s: &str) -> Result<ShortStr> {
+        let len = s.len();
+        if len > MAX_SHORT_STR_LEN {
+            return Err(Error::TooLong);
+        }
+        let mut bytes = [0u8; MAX_SHORT_STR_LEN];
+        bytes[..len].copy_from_slice(s.as_bytes());
+        Ok(ShortStr {
+            len,
+            bytes,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        // Safety: bytes[..len] was copied from a str
+        unsafe { std::str::from_utf8_unchecked(&self.bytes[..self.len]) }
+    }
+}
+
+impl fmt::Debug for ShortStr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
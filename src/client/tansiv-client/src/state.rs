@@ -0,0 +1,349 @@
+use crate::buffer_pool::BufferPool;
+use crate::connector::FbBuffer;
+use crate::input_msg::InputMsg;
+use crate::output_msg_set::{OutputMsg, OutputMsgSet};
+use crate::packet_attributes::PacketAttributes;
+use crate::vsg_address::VsgAddress;
+use crate::waitfree_array_queue::PeekableQueue;
+use std::convert::TryInto;
+use std::fmt;
+use std::time::Duration;
+
+#[derive(Debug)]
+pub enum Error {
+    BadMagic,
+    UnsupportedVersion(u32),
+    Truncated,
+    InvalidTime,
+}
+
+type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::BadMagic => write!(f, "Not a saved state"),
+            Error::UnsupportedVersion(version) => write!(f, "Unsupported saved state version {}", version),
+            Error::Truncated => write!(f, "Saved state is truncated"),
+            Error::InvalidTime => write!(f, "Time out of bounds"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+const MAGIC: &[u8; 4] = b"TSVS";
+// Bump when the layout below changes
+pub const STATE_VERSION: u32 = 2;
+
+// Outgoing packet, not sent to the actor yet
+#[derive(Debug, Clone, PartialEq)]
+pub struct SavedPacket {
+    pub src: VsgAddress,
+    pub dst: VsgAddress,
+    pub attributes: PacketAttributes,
+    pub seq_id: u64,
+    pub send_time: Duration,
+    pub payload: Vec<u8>,
+}
+
+impl SavedPacket {
+    pub fn new(msg: &OutputMsg) -> SavedPacket {
+        SavedPacket {
+            src: msg.src(),
+            dst: msg.dst(),
+            attributes: msg.attributes(),
+            seq_id: msg.seq_id(),
+            send_time: msg.send_time(),
+            payload: msg.payload().to_vec(),
+        }
+    }
+
+    pub fn restore(&self, buffer_pool: &BufferPool<FbBuffer>) -> crate::Result<OutputMsg> {
+        let buffer = buffer_pool.allocate_buffer(self.payload.len())?;
+        Ok(OutputMsg::new(self.src, self.dst, self.attributes, self.seq_id, self.send_time, &self.payload, buffer)?)
+    }
+}
+
+// Received packet, not read by the application yet
+#[derive(Debug, Clone, PartialEq)]
+pub struct SavedInput {
+    pub src: VsgAddress,
+    pub dst: VsgAddress,
+    pub attributes: PacketAttributes,
+    pub payload: Vec<u8>,
+}
+
+impl SavedInput {
+    pub fn new(msg: &InputMsg) -> SavedInput {
+        let mut payload = vec![0; msg.payload_len()];
+        msg.copy_payload(&mut payload);
+        SavedInput {
+            src: msg.src(),
+            dst: msg.dst(),
+            attributes: msg.attributes(),
+            payload,
+        }
+    }
+}
+
+// The messages of outgoing and input are taken out and put back in the same order. The deadline
+// handler must not run meanwhile.
+pub fn save_outgoing(outgoing: &OutputMsgSet) -> Vec<SavedPacket> {
+    let msgs: Vec<OutputMsg> = outgoing.drain().collect();
+    let saved = msgs.iter().map(SavedPacket::new).collect();
+    for msg in msgs {
+        // Cannot fail, the slots were just freed
+        let _ = outgoing.insert(msg);
+    }
+    saved
+}
+
+pub fn save_input(input: &PeekableQueue<InputMsg>) -> Vec<SavedInput> {
+    let msgs: Vec<InputMsg> = input.iter().collect();
+    let saved = msgs.iter().map(SavedInput::new).collect();
+    for msg in msgs {
+        let _ = input.push(msg);
+    }
+    saved
+}
+
+// Client state carried over a VM snapshot
+//
+// Layout, all integers in little endian:
+// - "TSVS", version (u32),
+// - previous and next deadlines, next sequence id (u64),
+// - outgoing packets, upcoming packets and input packets, each as a count (u32) followed by the
+//   packets.
+// Times are encoded as seconds (u64) and nanoseconds (u32), addresses as 16 bytes (IPv4-mapped
+// for IPv4) and payloads as a length (u32) followed by the bytes.
+#[derive(Debug, Default, PartialEq)]
+pub struct State {
+    pub prev_deadline: Duration,
+    pub next_deadline: Duration,
+    pub next_seq_id: u64,
+    pub outgoing: Vec<SavedPacket>,
+    pub upcoming: Vec<SavedPacket>,
+    pub input: Vec<SavedInput>,
+}
+
+impl State {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend_from_slice(MAGIC);
+        put_u32(&mut buf, STATE_VERSION);
+        put_time(&mut buf, self.prev_deadline);
+        put_time(&mut buf, self.next_deadline);
+        put_u64(&mut buf, self.next_seq_id);
+        for packets in &[&self.outgoing, &self.upcoming] {
+            put_u32(&mut buf, packets.len() as u32);
+            for packet in packets.iter() {
+                put_header(&mut buf, packet.src, packet.dst, &packet.attributes);
+                put_u64(&mut buf, packet.seq_id);
+                put_time(&mut buf, packet.send_time);
+                put_bytes(&mut buf, &packet.payload);
+            }
+        }
+        put_u32(&mut buf, self.input.len() as u32);
+        for packet in &self.input {
+            put_header(&mut buf, packet.src, packet.dst, &packet.attributes);
+            put_bytes(&mut buf, &packet.payload);
+        }
+        buf
+    }
+
+    pub fn decode(data: &[u8]) -> Result<State> {
+        let mut reader = Reader(data);
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(Error::BadMagic);
+        }
+        let version = reader.u32()?;
+        if version != STATE_VERSION {
+            return Err(Error::UnsupportedVersion(version));
+        }
+
+        let mut state = State {
+            prev_deadline: reader.time()?,
+            next_deadline: reader.time()?,
+            next_seq_id: reader.u64()?,
+            ..Default::default()
+        };
+        for packets in &mut [&mut state.outgoing, &mut state.upcoming] {
+            for _ in 0..reader.u32()? {
+                let (src, dst, attributes) = reader.header()?;
+                packets.push(SavedPacket {
+                    src,
+                    dst,
+                    attributes,
+                    seq_id: reader.u64()?,
+                    send_time: reader.time()?,
+                    payload: reader.bytes()?,
+                });
+            }
+        }
+        for _ in 0..reader.u32()? {
+            let (src, dst, attributes) = reader.header()?;
+            state.input.push(SavedInput {
+                src,
+                dst,
+                attributes,
+                payload: reader.bytes()?,
+            });
+        }
+        Ok(state)
+    }
+}
+
+fn put_u32(buf: &mut Vec<u8>, val: u32) {
+    buf.extend_from_slice(&val.to_le_bytes());
+}
+
+fn put_u64(buf: &mut Vec<u8>, val: u64) {
+    buf.extend_from_slice(&val.to_le_bytes());
+}
+
+fn put_time(buf: &mut Vec<u8>, time: Duration) {
+    put_u64(buf, time.as_secs());
+    put_u32(buf, time.subsec_nanos());
+}
+
+fn put_bytes(buf: &mut Vec<u8>, bytes: &[u8]) {
+    put_u32(buf, bytes.len() as u32);
+    buf.extend_from_slice(bytes);
+}
+
+fn put_header(buf: &mut Vec<u8>, src: VsgAddress, dst: VsgAddress, attributes: &PacketAttributes) {
+    buf.extend_from_slice(&src.ipv6());
+    buf.extend_from_slice(&dst.ipv6());
+    buf.push(attributes.protocol);
+    buf.push(attributes.priority);
+    buf.extend_from_slice(&attributes.src_port.to_le_bytes());
+    buf.extend_from_slice(&attributes.dst_port.to_le_bytes());
+    put_u64(buf, attributes.flow_id);
+    put_u32(buf, attributes.wire_size);
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.0.len() < len {
+            return Err(Error::Truncated);
+        }
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn time(&mut self) -> Result<Duration> {
+        let seconds = self.u64()?;
+        let nseconds = self.u32()?;
+        if nseconds < 1_000_000_000 {
+            Ok(Duration::new(seconds, nseconds))
+        } else {
+            Err(Error::InvalidTime)
+        }
+    }
+
+    fn address(&mut self) -> Result<VsgAddress> {
+        Ok(VsgAddress::from_ipv6(self.take(16)?.try_into().unwrap()))
+    }
+
+    fn bytes(&mut self) -> Result<Vec<u8>> {
+        let len = self.u32()? as usize;
+        Ok(self.take(len)?.to_vec())
+    }
+
+    fn header(&mut self) -> Result<(VsgAddress, VsgAddress, PacketAttributes)> {
+        let src = self.address()?;
+        let dst = self.address()?;
+        let protocol = self.u8()?;
+        let priority = self.u8()?;
+        let attributes = PacketAttributes {
+            protocol,
+            priority,
+            src_port: self.u16()?,
+            dst_port: self.u16()?,
+            flow_id: self.u64()?,
+            wire_size: self.u32()?,
+        };
+        Ok((src, dst, attributes))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::packet_attributes::PacketAttributes;
+    use crate::vsg_address::VsgAddress;
+    use std::time::Duration;
+    use super::{Error, SavedInput, SavedPacket, State, STATE_VERSION};
+
+    fn make_state() -> State {
+        let attributes = PacketAttributes {
+            protocol: 17,
+            src_port: 1234,
+            dst_port: 53,
+            priority: 46,
+            flow_id: 42,
+            wire_size: 1500,
+        };
+        let src = VsgAddress::V4(u32::from(std::net::Ipv4Addr::new(10, 0, 0, 1)).to_be());
+        let dst = VsgAddress::V6(std::net::Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 1).octets());
+        State {
+            prev_deadline: Duration::new(1, 100),
+            next_deadline: Duration::new(1, 200),
+            next_seq_id: 7,
+            outgoing: vec![SavedPacket {
+                src,
+                dst,
+                attributes,
+                seq_id: 6,
+                send_time: Duration::new(1, 150),
+                payload: b"Foo msg".to_vec(),
+            }],
+            upcoming: vec![],
+            input: vec![SavedInput {
+                src: dst,
+                dst: src,
+                attributes: PacketAttributes::default(),
+                payload: b"Bar msg".to_vec(),
+            }],
+        }
+    }
+
+    #[test]
+    fn roundtrip() {
+        let state = make_state();
+        let decoded = State::decode(&state.encode()).expect("decode failed");
+        assert_eq!(state, decoded);
+    }
+
+    #[test]
+    fn invalid_data() {
+        let mut data = make_state().encode();
+
+        assert!(matches!(State::decode(&data[..data.len() - 1]), Err(Error::Truncated)));
+
+        data[4..8].copy_from_slice(&(STATE_VERSION + 1).to_le_bytes());
+        assert!(matches!(State::decode(&data), Err(Error::UnsupportedVersion(version)) if version == STATE_VERSION + 1));
+
+        data[0] = 0;
+        assert!(matches!(State::decode(&data), Err(Error::BadMagic)));
+    }
+}
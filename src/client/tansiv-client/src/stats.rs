@@ -0,0 +1,147 @@
+use crate::report::DropReason;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+// Snapshot of the counters of a context, see Context::stats()
+//
+// Fragments are counted as separate packets. Durations are host time.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+    // Packets sent to and delivered by the actor
+    pub packets_sent: u64,
+    pub bytes_sent: u64,
+    pub packets_received: u64,
+    pub bytes_received: u64,
+    // Packets the application could not send for lack of output buffer
+    pub drops_no_buffer: u64,
+    // Received packets dropped by this context
+    pub drops_unjoined_group: u64,
+    pub drops_reassembly: u64,
+    pub drops_input_queue_full: u64,
+    // Sent packets dropped by the simulator, as told by delivery reports
+    pub drops_unknown: u64,
+    pub drops_unreachable: u64,
+    pub drops_loss: u64,
+    pub drops_queue_full: u64,
+    // Maximum number of output buffers in use and of messages waiting in the input queue
+    pub output_buffers_high_water: u64,
+    pub input_queue_high_water: u64,
+    // Runs of the deadline handler, with the total and maximum time spent in it
+    pub deadlines: u64,
+    pub deadline_handler_time: Duration,
+    pub deadline_handler_max_time: Duration,
+    // Messages and requests time-stamped before the previous deadline, sent at the previous
+    // deadline instead
+    pub late_timestamps: u64,
+    // Packets dropped, duplicated, corrupted or delayed by fault injection (--fault)
+    pub faults: u64,
+}
+
+// Packet drops decided by this context
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LocalDrop {
+    NoBuffer,
+    UnjoinedGroup,
+    Reassembly,
+    InputQueueFull,
+}
+
+// Counters updated by application code and the deadline handler. Each counter is independent,
+// so relaxed atomics are enough.
+#[derive(Debug, Default)]
+pub struct Counters {
+    packets_sent: AtomicU64,
+    bytes_sent: AtomicU64,
+    packets_received: AtomicU64,
+    bytes_received: AtomicU64,
+    drops_no_buffer: AtomicU64,
+    drops_unjoined_group: AtomicU64,
+    drops_reassembly: AtomicU64,
+    drops_input_queue_full: AtomicU64,
+    drops_unknown: AtomicU64,
+    drops_unreachable: AtomicU64,
+    drops_loss: AtomicU64,
+    drops_queue_full: AtomicU64,
+    deadlines: AtomicU64,
+    // In nanoseconds
+    deadline_handler_time: AtomicU64,
+    deadline_handler_max_time: AtomicU64,
+    late_timestamps: AtomicU64,
+    faults: AtomicU64,
+}
+
+impl Counters {
+    pub fn new() -> Counters {
+        Default::default()
+    }
+
+    pub fn count_sent(&self, size: usize) {
+        self.packets_sent.fetch_add(1, Ordering::Relaxed);
+        self.bytes_sent.fetch_add(size as u64, Ordering::Relaxed);
+    }
+
+    pub fn count_received(&self, size: usize) {
+        self.packets_received.fetch_add(1, Ordering::Relaxed);
+        self.bytes_received.fetch_add(size as u64, Ordering::Relaxed);
+    }
+
+    pub fn count_local_drop(&self, drop: LocalDrop) {
+        let counter = match drop {
+            LocalDrop::NoBuffer => &self.drops_no_buffer,
+            LocalDrop::UnjoinedGroup => &self.drops_unjoined_group,
+            LocalDrop::Reassembly => &self.drops_reassembly,
+            LocalDrop::InputQueueFull => &self.drops_input_queue_full,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn count_reported_drop(&self, reason: DropReason) {
+        let counter = match reason {
+            DropReason::Unknown => &self.drops_unknown,
+            DropReason::Unreachable => &self.drops_unreachable,
+            DropReason::Loss => &self.drops_loss,
+            DropReason::QueueFull => &self.drops_queue_full,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn count_deadline(&self, handler_time: Duration) {
+        let nanos = handler_time.as_nanos() as u64;
+        self.deadlines.fetch_add(1, Ordering::Relaxed);
+        self.deadline_handler_time.fetch_add(nanos, Ordering::Relaxed);
+        self.deadline_handler_max_time.fetch_max(nanos, Ordering::Relaxed);
+    }
+
+    pub fn count_late_timestamp(&self) {
+        self.late_timestamps.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn count_fault(&self) {
+        self.faults.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // High-water marks are tracked by the buffer pool and the queue themselves
+    pub fn snapshot(&self, output_buffers_high_water: usize, input_queue_high_water: usize) -> Stats {
+        Stats {
+            packets_sent: self.packets_sent.load(Ordering::Relaxed),
+            bytes_sent: self.bytes_sent.load(Ordering::Relaxed),
+            packets_received: self.packets_received.load(Ordering::Relaxed),
+            bytes_received: self.bytes_received.load(Ordering::Relaxed),
+            drops_no_buffer: self.drops_no_buffer.load(Ordering::Relaxed),
+            drops_unjoined_group: self.drops_unjoined_group.load(Ordering::Relaxed),
+            drops_reassembly: self.drops_reassembly.load(Ordering::Relaxed),
+            drops_input_queue_full: self.drops_input_queue_full.load(Ordering::Relaxed),
+            drops_unknown: self.drops_unknown.load(Ordering::Relaxed),
+            drops_unreachable: self.drops_unreachable.load(Ordering::Relaxed),
+            drops_loss: self.drops_loss.load(Ordering::Relaxed),
+            drops_queue_full: self.drops_queue_full.load(Ordering::Relaxed),
+            output_buffers_high_water: output_buffers_high_water as u64,
+            input_queue_high_water: input_queue_high_water as u64,
+            deadlines: self.deadlines.load(Ordering::Relaxed),
+            deadline_handler_time: Duration::from_nanos(self.deadline_handler_time.load(Ordering::Relaxed)),
+            deadline_handler_max_time: Duration::from_nanos(self.deadline_handler_max_time.load(Ordering::Relaxed)),
+            late_timestamps: self.late_timestamps.load(Ordering::Relaxed),
+            faults: self.faults.load(Ordering::Relaxed),
+        }
+    }
+}
@@ -0,0 +1,41 @@
+// Why the simulation ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TerminationReason {
+    // The actor sent EndSimulation
+    ActorRequest,
+    // The actor sent an invalid or unexpected message
+    ProtocolError,
+    // Communication with the actor failed
+    IoError,
+    // A message was time-stamped after the deadline being handled
+    DeadlineOverrun,
+}
+
+impl TerminationReason {
+    // Encoding for atomic storage, 0 means that the simulation did not end
+    pub(crate) fn to_raw(self) -> u8 {
+        match self {
+            TerminationReason::ActorRequest => 1,
+            TerminationReason::ProtocolError => 2,
+            TerminationReason::IoError => 3,
+            TerminationReason::DeadlineOverrun => 4,
+        }
+    }
+
+    pub(crate) fn from_raw(raw: u8) -> Option<TerminationReason> {
+        match raw {
+            1 => Some(TerminationReason::ActorRequest),
+            2 => Some(TerminationReason::ProtocolError),
+            3 => Some(TerminationReason::IoError),
+            4 => Some(TerminationReason::DeadlineOverrun),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn from_io_error(error: &std::io::Error) -> TerminationReason {
+        match error.kind() {
+            std::io::ErrorKind::InvalidData => TerminationReason::ProtocolError,
+            _ => TerminationReason::IoError,
+        }
+    }
+}
@@ -71,7 +71,7 @@ pub struct TimerContext {
     // No concurrency: (mut) accessed only by the deadline handler
     // Mutex is used to show interior mutability despite sharing.
     next_deadline: Mutex<StdDuration>,
-    ///////////////////// Next fields for DEBUG only
+    ///////////////////// Next fields for DEBUG and jitter measurement only
     // Previous deadline in raw monotonic time
     // No concurrency: (mut) accessed only by the deadline handler
     // Mutex is used to show interior mutability despite sharing.
@@ -85,6 +85,12 @@ pub struct TimerContext {
     // - read by ::stop() in application context
     // - written by the deadline handler
     stopped: AtomicBool,
+    // True while the application is blocked in a simulated computation. Simulation time then jumps
+    // from deadline to deadline instead of following the system clock.
+    // Concurrency:
+    // - written by application code and the deadline handler
+    // - read by the deadline handler
+    fast_forward: AtomicBool,
 }
 
 impl TimerContext {
@@ -126,6 +132,7 @@ impl TimerContext {
             prev_deadline_raw: prev_deadline_raw,
             next_deadline_raw: next_deadline_raw,
             stopped: AtomicBool::new(true),
+            fast_forward: AtomicBool::new(false),
         })
     }
 
@@ -134,10 +141,14 @@ impl TimerContext {
         deadline_handler_debug!("TimerContext::freeze_time() system time = {:?}", now);
         *self.current_deadline.lock().unwrap() = self.application_now();
         self.at_deadline.store(true, Ordering::Release);
-        deadline_handler_debug!("TimerContext::freeze_time() jitter (now - next_deadline_raw) = {}", Duration::from_std(now).unwrap() - Duration::from_std(*self.next_deadline_raw.lock().unwrap()).unwrap());
         now
     }
 
+    // Delay from the programmed expiration of the timer to now
+    fn jitter(&self, now: StdDuration) -> StdDuration {
+        now.checked_sub(*self.next_deadline_raw.lock().unwrap()).unwrap_or_default()
+    }
+
     fn thaw_time_to_deadline(&self, freeze_time: Option<StdDuration>, deadline: StdDuration) -> Result<()> {
         let mut next_deadline = self.next_deadline.lock().unwrap();
         let next_deadline_val = *next_deadline;
@@ -149,9 +160,15 @@ impl TimerContext {
 
         let now = clock::gettime(Self::CLOCK).unwrap();
         deadline_handler_debug!("TimerContext::thaw_time_to_deadline() system time = {:?}", now);
-        let new_next_deadline_raw = now + (deadline - next_deadline_val);
+        let fast_forward = freeze_time.is_some() && self.fast_forward.load(Ordering::Acquire);
+        let new_next_deadline_raw = if fast_forward {
+            // Fire as soon as possible
+            now
+        } else {
+            now + (deadline - next_deadline_val)
+        };
 
-        // DEBUG only
+        // DEBUG and jitter measurement only
         let mut next_deadline_raw = self.next_deadline_raw.lock().unwrap();
         *self.prev_deadline_raw.lock().unwrap() = *next_deadline_raw;
         *next_deadline_raw = new_next_deadline_raw;
@@ -160,8 +177,14 @@ impl TimerContext {
         // **********
 
         if let Some(freeze_time) = freeze_time {
-            let elapsed_time = Duration::from_std(now - freeze_time).unwrap();
+            let mut elapsed_time = Duration::from_std(now - freeze_time).unwrap();
             deadline_handler_debug!("TimerContext::thaw_time_to_deadline() elapsed_time = {}", elapsed_time);
+            if fast_forward {
+                // Jump to the next deadline right away
+                let skipped_time = Duration::from_std(deadline - next_deadline_val).unwrap();
+                deadline_handler_debug!("TimerContext::thaw_time_to_deadline() skipped_time = {}", skipped_time);
+                elapsed_time = elapsed_time - skipped_time;
+            }
 
             self.application_time.adjust(|offset| offset - elapsed_time);
             self.simulation_time.adjust(|offset| offset - elapsed_time);
@@ -173,9 +196,11 @@ impl TimerContext {
             deadline_handler_debug!("TimerContext::thaw_time_to_deadline() application time offset = {}", current_deadline - local_now);
             self.application_time.adjust(|_| current_deadline - local_now);
 
-            // Time starts at 0 in global simulation time.
-            deadline_handler_debug!("TimerContext::thaw_time_to_deadline() simulation time offset = -{:?}", now);
-            self.simulation_time.adjust(|_| -Duration::from_std(now).unwrap());
+            // Time starts at 0 in global simulation time, or at the deadline restored by
+            // ::restore_deadlines().
+            let start_time = Duration::from_std(next_deadline_val).unwrap();
+            deadline_handler_debug!("TimerContext::thaw_time_to_deadline() simulation time offset = {:?} - {:?}", next_deadline_val, now);
+            self.simulation_time.adjust(|_| start_time - Duration::from_std(now).unwrap());
         }
 
         self.at_deadline.store(false, Ordering::Release);
@@ -239,6 +264,31 @@ impl TimerContext {
     pub fn check_deadline_overrun(&self, _send_time: StdDuration, mut _upcoming_messages: &Mutex<VecDeque<OutputMsg>>) -> Option<StdDuration> {
         return None;
     }
+
+    /// Returns the previous and next deadlines in global simulation time
+    pub fn deadlines(&self) -> (StdDuration, StdDuration) {
+        (*self.prev_deadline.lock().unwrap(), *self.next_deadline.lock().unwrap())
+    }
+
+    /// Resumes time from a saved state. Must be called before ::start(), which then starts
+    /// simulation time at next_deadline instead of 0. The saved deadlines replace the current ones.
+    pub fn restore_deadlines(&self, prev_deadline: StdDuration, next_deadline: StdDuration) {
+        *self.prev_deadline.lock().unwrap() = prev_deadline;
+        *self.next_deadline.lock().unwrap() = next_deadline;
+        *self.current_deadline.lock().unwrap() = NaiveDateTime::from_timestamp(0, 0) + self.time_offset + Duration::from_std(next_deadline).unwrap();
+    }
+
+    /// Returns true if the deadline handler cannot run, that is before ::start() or once the
+    /// simulation has ended.
+    pub fn deadline_handler_stopped(&self) -> bool {
+        self.stopped.load(Ordering::Acquire)
+    }
+
+    /// While enabled, time slices elapse immediately after their deadline is handled, both in
+    /// simulation and in application time.
+    pub fn set_fast_forward(&self, enable: bool) {
+        self.fast_forward.store(enable, Ordering::Release);
+    }
 }
 
 impl Drop for TimerContext {
@@ -283,17 +333,36 @@ pub fn register(context: &Arc<Context>) -> Result<()> {
     Ok(())
 }
 
+// Prevents the deadline handler from interrupting the calling thread. Helper threads that may hold
+// locks also taken by the deadline handler, including in the allocator, must call this first.
+pub fn block_deadline_signal() -> Result<()> {
+    use nix::sys::signal::SigSet;
+
+    let mut mask = SigSet::empty();
+    mask.add(TimerContext::DEADLINE_SIG);
+    mask.thread_block().map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
+}
+
+// Called when the simulation ends without an end callback. The application keeps running, with
+// time following the system clock.
+pub fn end_not_notified() {
+    log::info!("End of simulation not notified");
+}
+
 extern "C" fn deadline_handler(_: libc::c_int) {
     use crate::AfterDeadline;
 
     deadline_handler_debug!("deadline_handler() called");
     if let Some(context) = CONTEXT.read().unwrap().upgrade() {
         let freeze_time = context.timer_context.freeze_time();
+        let jitter = context.timer_context.jitter(freeze_time);
+        deadline_handler_debug!("deadline_handler() jitter (now - next_deadline_raw) = {:?}", jitter);
+        context.deadline_timing.record_jitter(jitter);
         match context.at_deadline() {
             AfterDeadline::NextDeadline(deadline) => {
                 context.timer_context.thaw_time_to_deadline(Some(freeze_time), deadline).expect("thaw_time_to_deadline failed")
             },
-            AfterDeadline::EndSimulation => context.timer_context.stopped.store(true, Ordering::Release),
+            AfterDeadline::EndSimulation(_) => context.timer_context.stopped.store(true, Ordering::Release),
         }
     }
 }
@@ -121,7 +121,9 @@ impl TimerContextInner {
         // - qemu_clock_get_ns() only accesses Qemu's internal data
         // - qemu_clock_get_ns() does not require locking
         let vm_time = unsafe { qemu_clock_get_ns(QEMUClockType::QEMU_CLOCK_VIRTUAL) };
-        let vm_time = Duration::nanoseconds(vm_time);
+        // Simulation time starts at 0, or at the deadline restored by ::restore_deadlines()
+        let start_time = Duration::from_std(*self.next_deadline.lock().unwrap()).unwrap();
+        let vm_time = Duration::nanoseconds(vm_time) - start_time;
         *self.offset.lock().unwrap() = vm_time;
 
         self.set_next_deadline(deadline);
@@ -174,9 +176,42 @@ impl TimerContextInner {
         *self.next_deadline.lock().unwrap()
     }
 
+    /// Returns the previous and next deadlines in global simulation time
+    pub fn deadlines(&self) -> (StdDuration, StdDuration) {
+        (*self.prev_deadline.lock().unwrap(), *self.next_deadline.lock().unwrap())
+    }
+
+    /// Resumes time from a saved state. Must be called before ::start(). The saved deadlines
+    /// replace the current ones.
+    pub fn restore_deadlines(&self, prev_deadline: StdDuration, next_deadline: StdDuration) {
+        *self.prev_deadline.lock().unwrap() = prev_deadline;
+        *self.next_deadline.lock().unwrap() = next_deadline;
+    }
+
+    /// QEMU only saves the state of a paused VM, and the deadline handler does not run while the
+    /// VM is paused.
+    pub fn deadline_handler_stopped(&self) -> bool {
+        true
+    }
+
+    // Delay in VM time from the expiration of the timer to now
+    fn jitter(&self) -> StdDuration {
+        // Safety:
+        // - Qemu clocks are assumed initialized when self is created
+        // - qemu_clock_get_ns() only accesses Qemu's internal data
+        // - qemu_clock_get_ns() does not require locking
+        let vm_time = unsafe { qemu_clock_get_ns(QEMUClockType::QEMU_CLOCK_VIRTUAL) };
+        let timer_deadline = *self.offset.lock().unwrap() + Duration::from_std(*self.next_deadline.lock().unwrap()).unwrap();
+        (Duration::nanoseconds(vm_time) - timer_deadline).to_std().unwrap_or_default()
+    }
+
     pub fn check_deadline_overrun(&self, _send_time: StdDuration, mut _upcoming_messages: &Mutex<VecDeque<OutputMsg>>) -> Option<StdDuration> {
         return None;
     }
+
+    // Time is driven by QEMU, blocked computations just wait for their completion
+    pub fn set_fast_forward(&self, _enable: bool) {
+    }
 }
 
 pub fn register(context: &Arc<crate::Context>) -> Result<()> {
@@ -185,17 +220,31 @@ pub fn register(context: &Arc<crate::Context>) -> Result<()> {
     Ok(())
 }
 
+// The deadline handler runs in a QEMU thread and never interrupts other threads.
+pub fn block_deadline_signal() -> Result<()> {
+    Ok(())
+}
+
+// Called when the simulation ends without an end callback. The timer is not re-armed and nothing
+// else would stop the VM, which would wait forever for the next deadline.
+pub fn end_not_notified() {
+    log::error!("End of simulation not notified, aborting the VM");
+    std::process::abort();
+}
+
 extern "C" fn deadline_handler(opaque: *mut ::std::os::raw::c_void) {
     use crate::AfterDeadline;
 
     // Safety: TODO
     let timer_context = unsafe { (opaque as *const TimerContextInner).as_ref().unwrap() };
     if let Some(context) = timer_context.context.lock().unwrap().upgrade() {
+        context.deadline_timing.record_jitter(timer_context.jitter());
         match context.at_deadline() {
             AfterDeadline::NextDeadline(deadline) => {
                 context.timer_context.set_next_deadline(deadline);
             },
-            AfterDeadline::EndSimulation => (),
+            // The timer is not re-armed. The end callback lets the VM shut down.
+            AfterDeadline::EndSimulation(_) => (),
         }
     }
 }
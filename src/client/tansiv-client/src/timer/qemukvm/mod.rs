@@ -7,13 +7,14 @@ use std::ops::Deref;
 use std::pin::Pin;
 use std::ptr::null_mut;
 use std::sync::{Arc, Mutex, Weak};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Duration as StdDuration;
 
 use crate::output_msg_set::{OutputMsg};
 
 use core::arch::x86_64::{_rdtsc};
 
-use log::debug;
+use log::{debug, error};
 
 extern {
     fn open_device() -> c_int;
@@ -44,6 +45,8 @@ pub struct TimerContextInner {
     tsc_freq : Mutex<f64>, // frequency of guest TSC in GHz,
     fd: Mutex<c_int>, // file descriptor of the kernel module
     tsc_infos: *mut TimerTSCInfos,
+    // Set once the simulation ended, the deadline handler then only keeps the VM running
+    ended: AtomicBool,
 }
 
 // Wrapper struct to avoid conflicts between Pin::new() and TimerContextInner::new()
@@ -87,7 +90,8 @@ impl TimerContextInner {
                 vmx_timer_value,
                 tsc_freq,
                 fd,
-                tsc_infos
+                tsc_infos,
+                ended: AtomicBool::new(false),
             }
         }
     }
@@ -199,6 +203,30 @@ impl TimerContextInner {
         *self.next_deadline.lock().unwrap()
     }
 
+    /// Returns the previous and next deadlines in global simulation time
+    pub fn deadlines(&self) -> (StdDuration, StdDuration) {
+        (*self.prev_deadline.lock().unwrap(), *self.next_deadline.lock().unwrap())
+    }
+
+    /// Resumes time from a saved state. Must be called before ::start(). The saved deadlines
+    /// replace the current ones.
+    pub fn restore_deadlines(&self, prev_deadline: StdDuration, next_deadline: StdDuration) {
+        *self.prev_deadline.lock().unwrap() = prev_deadline;
+        *self.next_deadline.lock().unwrap() = next_deadline;
+    }
+
+    /// QEMU only saves the state of a paused VM, and the deadline handler does not run while the
+    /// VM is paused.
+    pub fn deadline_handler_stopped(&self) -> bool {
+        true
+    }
+
+    // Delay from the expiration of the VMX preemption timer to the VM exit at guest_tsc
+    fn jitter(&self, guest_tsc: u64) -> StdDuration {
+        let late_ticks = guest_tsc.saturating_sub(*self.vmx_timer_value.lock().unwrap());
+        StdDuration::from_nanos((late_ticks as f64 / *self.tsc_freq.lock().unwrap()) as u64)
+    }
+
     pub fn check_deadline_overrun(&self, send_time: StdDuration, list: &Mutex<VecDeque<OutputMsg>>) -> Option<StdDuration> {
         if send_time > self.simulation_next_deadline() {
             let upcoming_messages = list.lock().unwrap();
@@ -220,6 +248,9 @@ impl TimerContextInner {
         return None;
     }
 
+    // Time is driven by QEMU, blocked computations just wait for their completion
+    pub fn set_fast_forward(&self, _enable: bool) {
+    }
 }
 
 pub fn register(context: &Arc<crate::Context>) -> Result<()> {
@@ -228,6 +259,21 @@ pub fn register(context: &Arc<crate::Context>) -> Result<()> {
     Ok(())
 }
 
+// The deadline handler runs in a QEMU thread and never interrupts other threads.
+pub fn block_deadline_signal() -> Result<()> {
+    Ok(())
+}
+
+// Called when the simulation ends without an end callback. Nothing else would stop the VM, which
+// would wait forever for the next deadline.
+pub fn end_not_notified() {
+    error!("End of simulation not notified, aborting the VM");
+    std::process::abort();
+}
+
+// Returns the length of the next time slice in nanoseconds. QEMU re-arms the timer with whatever
+// is returned, so once the simulation ended the last slice length is returned again and the VM keeps
+// running until the end callback shuts it down.
 #[no_mangle]
 pub extern "C" fn deadline_handler(opaque: *mut ::std::os::raw::c_void, guest_tsc: u64) -> u64 {
     use crate::AfterDeadline;
@@ -235,15 +281,18 @@ pub extern "C" fn deadline_handler(opaque: *mut ::std::os::raw::c_void, guest_ts
     let context_arg = unsafe { (opaque as *const crate::Context).as_ref().unwrap() };
     let timer_context = &context_arg.timer_context;
     *timer_context.guest_tsc.lock().unwrap() = guest_tsc;
+    if timer_context.ended.load(Ordering::Acquire) {
+        return timer_context.next_deadline.lock().unwrap().as_nanos() as u64 - timer_context.prev_deadline.lock().unwrap().as_nanos() as u64;
+    }
     if let Some(context) = timer_context.context.lock().unwrap().upgrade() {
+        context_arg.deadline_timing.record_jitter(timer_context.jitter(guest_tsc));
         match context_arg.at_deadline() {
             AfterDeadline::NextDeadline(deadline) => {
                 context.timer_context.set_next_deadline(deadline);
 
             },
-            AfterDeadline::EndSimulation => {
-                panic!("Ending simulation, at_deadline_failed!");
-            }
+            // No next deadline. The end callback lets the VM shut down.
+            AfterDeadline::EndSimulation(_) => timer_context.ended.store(true, Ordering::Release),
         }
     }
     // return timer_context.prev_deadline.lock().unwrap().as_nanos() as u64;
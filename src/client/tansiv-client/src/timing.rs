@@ -0,0 +1,82 @@
+use crate::histogram::{Histogram, HistogramSnapshot};
+use std::io::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+const NO_DEADLINE: u64 = u64::MAX;
+
+// Timing of the deadline handler, in host time
+//
+// - jitter: delay from the expected time of a deadline to the start of its handling, as measured
+//   by the timer backend,
+// - frozen: time spent in Context::at_deadline(), during which simulation time is frozen,
+// - slice: time from the start of a deadline handler to the start of the next one.
+#[derive(Debug)]
+pub struct DeadlineTiming {
+    jitter: Histogram,
+    frozen: Histogram,
+    slice: Histogram,
+    // Origin of last_start
+    epoch: Instant,
+    // No concurrency: written only by the deadline handler
+    // Start of the last deadline handler in nanoseconds since epoch, or NO_DEADLINE
+    last_start: AtomicU64,
+}
+
+impl DeadlineTiming {
+    pub fn new() -> DeadlineTiming {
+        DeadlineTiming {
+            jitter: Histogram::new(),
+            frozen: Histogram::new(),
+            slice: Histogram::new(),
+            epoch: Instant::now(),
+            last_start: AtomicU64::new(NO_DEADLINE),
+        }
+    }
+
+    pub fn record_jitter(&self, jitter: Duration) {
+        self.jitter.record(jitter)
+    }
+
+    pub fn record_deadline(&self, start: Instant, frozen: Duration) {
+        self.frozen.record(frozen);
+        let start = start.duration_since(self.epoch).as_nanos() as u64;
+        let last_start = self.last_start.swap(start, Ordering::Relaxed);
+        if last_start != NO_DEADLINE {
+            self.slice.record(Duration::from_nanos(start.saturating_sub(last_start)));
+        }
+    }
+
+    pub fn report(&self) -> TimingReport {
+        TimingReport {
+            jitter: self.jitter.snapshot(),
+            frozen: self.frozen.snapshot(),
+            slice: self.slice.snapshot(),
+        }
+    }
+}
+
+// Snapshot of the deadline handler timing of a context, see Context::timing_report()
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TimingReport {
+    // Delay from the expected time of a deadline to the start of its handling
+    pub jitter: HistogramSnapshot,
+    // Time spent handling a deadline, during which simulation time is frozen
+    pub frozen: HistogramSnapshot,
+    // Host time from the start of a deadline handler to the start of the next one
+    pub slice: HistogramSnapshot,
+}
+
+impl TimingReport {
+    // Writes the non-empty buckets of all histograms as CSV, with header
+    // histogram,low_ns,high_ns,count
+    pub fn write_csv<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        writeln!(writer, "histogram,low_ns,high_ns,count")?;
+        for (name, histogram) in &[("jitter", &self.jitter), ("frozen", &self.frozen), ("slice", &self.slice)] {
+            for bucket in &histogram.buckets {
+                writeln!(writer, "{},{},{},{}", name, bucket.low.as_nanos(), bucket.high.as_nanos(), bucket.count)?;
+            }
+        }
+        Ok(())
+    }
+}
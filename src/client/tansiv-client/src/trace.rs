@@ -0,0 +1,228 @@
+use crate::vsg_address::VsgAddress;
+use crate::waitfree_array_queue::WaitfreeArrayQueue;
+use log::{error, info};
+use std::fs::File;
+use std::io::{BufWriter, Result, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Events waiting to be written. Events recorded while the queue is full are lost.
+const TRACE_QUEUE_SIZE: usize = 16384;
+const WRITE_PERIOD: Duration = Duration::from_millis(10);
+
+// Track ids of the contexts of this process
+static NEXT_TRACK_ID: AtomicU64 = AtomicU64::new(1);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TraceKind {
+    // Handling of a deadline, simulation time is frozen meanwhile
+    AtDeadline {
+        duration: Duration,
+    },
+    SendPacket {
+        dst: VsgAddress,
+        size: usize,
+    },
+    DeliverPacket {
+        src: VsgAddress,
+        size: usize,
+    },
+    Callback(&'static str),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TraceEvent {
+    // Host time since the Unix epoch, in nanoseconds, so that traces of different VMs line up
+    pub host_time: u64,
+    pub sim_time: Duration,
+    pub kind: TraceKind,
+}
+
+// Async-signal-safe
+pub fn host_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_nanos() as u64).unwrap_or(0)
+}
+
+#[derive(Debug)]
+struct Shared {
+    events: WaitfreeArrayQueue<TraceEvent>,
+    lost: AtomicU64,
+    closed: AtomicBool,
+}
+
+// Writes the events of a context to a file in Chrome trace-event JSON format, as loaded by
+// Perfetto or chrome://tracing. Each context is a track of the process, so traces of several VMs
+// can be loaded together.
+//
+// Events are queued without locking nor allocating, so that the deadline handler can record them,
+// and written by a helper thread.
+#[derive(Debug)]
+pub struct Tracer {
+    shared: Arc<Shared>,
+    writer: Option<JoinHandle<()>>,
+}
+
+impl Tracer {
+    pub fn new(path: &Path, name: &str) -> Result<Tracer> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        let pid = std::process::id();
+        let tid = NEXT_TRACK_ID.fetch_add(1, Ordering::Relaxed);
+        write_header(&mut writer, pid, tid, name)?;
+
+        let shared = Arc::new(Shared {
+            events: WaitfreeArrayQueue::new(TRACE_QUEUE_SIZE),
+            lost: AtomicU64::new(0),
+            closed: AtomicBool::new(false),
+        });
+        let writer_shared = shared.clone();
+        let handle = std::thread::Builder::new()
+            .name("tansiv-tracer".to_string())
+            .spawn(move || writer_main(writer_shared, writer, pid, tid))?;
+
+        Ok(Tracer {
+            shared,
+            writer: Some(handle),
+        })
+    }
+
+    pub fn record(&self, host_time: u64, sim_time: Duration, kind: TraceKind) {
+        let event = TraceEvent {
+            host_time,
+            sim_time,
+            kind,
+        };
+        if self.shared.events.push(event).is_err() {
+            self.shared.lost.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+impl Drop for Tracer {
+    fn drop(&mut self) {
+        self.shared.closed.store(true, Ordering::Release);
+        if let Some(writer) = self.writer.take() {
+            writer.thread().unpark();
+            let _ = writer.join();
+        }
+    }
+}
+
+fn writer_main(shared: Arc<Shared>, mut writer: BufWriter<File>, pid: u32, tid: u64) {
+    // The deadline handler must not interrupt the writer while it holds locks, e.g. in malloc()
+    if let Err(e) = crate::timer::block_deadline_signal() {
+        error!("trace writer failed to block the deadline signal: {}", e);
+        return;
+    }
+
+    if let Err(e) = write_events(&shared, &mut writer, pid, tid) {
+        error!("Writing trace failed: {}", e);
+    }
+    let lost = shared.lost.load(Ordering::Relaxed);
+    if lost > 0 {
+        info!("{} trace events lost", lost);
+    }
+}
+
+// Writes events until the tracer is dropped, then closes the JSON array
+fn write_events<W: Write>(shared: &Shared, writer: &mut W, pid: u32, tid: u64) -> Result<()> {
+    loop {
+        let closed = shared.closed.load(Ordering::Acquire);
+        while let Some(event) = shared.events.pop() {
+            write_event(writer, pid, tid, &event)?;
+        }
+        if closed {
+            break;
+        }
+        writer.flush()?;
+        std::thread::park_timeout(WRITE_PERIOD);
+    }
+    writeln!(writer, "\n]")?;
+    writer.flush()
+}
+
+// Timestamps are in microseconds
+fn write_micros<W: Write>(writer: &mut W, nanos: u64) -> Result<()> {
+    write!(writer, "{}.{:03}", nanos / 1000, nanos % 1000)
+}
+
+fn write_header<W: Write>(writer: &mut W, pid: u32, tid: u64, name: &str) -> Result<()> {
+    writeln!(writer, "[")?;
+    writeln!(writer, "{{\"name\":\"process_name\",\"ph\":\"M\",\"pid\":{},\"args\":{{\"name\":\"tansiv {}\"}}}},", pid, pid)?;
+    write!(writer, "{{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":{},\"tid\":{},\"args\":{{\"name\":\"{}\"}}}}", pid, tid, name)
+}
+
+// Events follow the header, hence the leading comma
+fn write_event<W: Write>(writer: &mut W, pid: u32, tid: u64, event: &TraceEvent) -> Result<()> {
+    let (name, category, phase) = match event.kind {
+        TraceKind::AtDeadline { .. } => ("at_deadline", "deadline", "X"),
+        TraceKind::SendPacket { .. } => ("SendPacket", "packet", "i"),
+        TraceKind::DeliverPacket { .. } => ("DeliverPacket", "packet", "i"),
+        TraceKind::Callback(name) => (name, "callback", "i"),
+    };
+    write!(writer, ",\n{{\"name\":\"{}\",\"cat\":\"{}\",\"ph\":\"{}\",\"pid\":{},\"tid\":{},\"ts\":", name, category, phase, pid, tid)?;
+    write_micros(writer, event.host_time)?;
+    match event.kind {
+        TraceKind::AtDeadline { duration } => {
+            write!(writer, ",\"dur\":")?;
+            write_micros(writer, duration.as_nanos() as u64)?;
+        },
+        _ => write!(writer, ",\"s\":\"t\"")?,
+    }
+    write!(writer, ",\"args\":{{\"sim_time_ns\":{}", event.sim_time.as_nanos())?;
+    match event.kind {
+        TraceKind::SendPacket { dst, size } => write!(writer, ",\"dst\":\"{}\",\"size\":{}", dst, size)?,
+        TraceKind::DeliverPacket { src, size } => write!(writer, ",\"src\":\"{}\",\"size\":{}", src, size)?,
+        _ => (),
+    }
+    write!(writer, "}}}}")
+}
+
+#[cfg(test)]
+mod test {
+    use crate::vsg_address::VsgAddress;
+    use std::time::Duration;
+    use super::{write_event, write_header, TraceEvent, TraceKind, Tracer};
+
+    #[test]
+    fn format() {
+        let mut out = Vec::new();
+        write_header(&mut out, 12, 1, "10.0.0.1").unwrap();
+        write_event(&mut out, 12, 1, &TraceEvent {
+            host_time: 1_000_001_500,
+            sim_time: Duration::from_micros(100),
+            kind: TraceKind::AtDeadline { duration: Duration::from_nanos(2500) },
+        }).unwrap();
+        write_event(&mut out, 12, 1, &TraceEvent {
+            host_time: 1_000_004_000,
+            sim_time: Duration::from_micros(100),
+            kind: TraceKind::SendPacket {
+                dst: VsgAddress::V4(u32::from(std::net::Ipv4Addr::new(10, 0, 1, 1)).to_be()),
+                size: 7,
+            },
+        }).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+
+        assert_eq!("[", lines[0]);
+        assert_eq!("{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":12,\"tid\":1,\"args\":{\"name\":\"10.0.0.1\"}},", lines[2]);
+        assert_eq!("{\"name\":\"at_deadline\",\"cat\":\"deadline\",\"ph\":\"X\",\"pid\":12,\"tid\":1,\"ts\":1000001.500,\"dur\":2.500,\"args\":{\"sim_time_ns\":100000}},", lines[3]);
+        assert_eq!("{\"name\":\"SendPacket\",\"cat\":\"packet\",\"ph\":\"i\",\"pid\":12,\"tid\":1,\"ts\":1000004.000,\"s\":\"t\",\"args\":{\"sim_time_ns\":100000,\"dst\":\"10.0.1.1\",\"size\":7}}", lines[4]);
+    }
+
+    #[test]
+    fn write_file() {
+        let path = std::env::temp_dir().join(format!("tansiv-trace-test-{}.json", std::process::id()));
+        let tracer = Tracer::new(&path, "10.0.0.1").expect("Tracer::new failed");
+        tracer.record(1000, Duration::from_micros(100), TraceKind::Callback("recv_callback"));
+        drop(tracer);
+
+        let trace = std::fs::read_to_string(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert!(trace.starts_with("[\n"));
+        assert!(trace.ends_with("\n]\n"));
+        assert!(trace.contains("\"name\":\"recv_callback\""));
+    }
+}
@@ -1,11 +1,115 @@
-use std::net::Ipv4Addr;
+use std::fmt;
+use std::net::{AddrParseError, IpAddr, Ipv4Addr, Ipv6Addr};
 
-pub fn from_str(ip: &str) -> std::result::Result<libc::in_addr_t , std::net::AddrParseError> {
+// Address of an application in the simulated network
+//
+// IPv4 addresses are stored in network byte order, as in libc::in_addr_t.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VsgAddress {
+    V4(libc::in_addr_t),
+    V6([u8; 16]),
+}
+
+impl VsgAddress {
+    pub fn is_ipv4(&self) -> bool {
+        match self {
+            VsgAddress::V4(_) => true,
+            VsgAddress::V6(_) => false,
+        }
+    }
+
+    // Limited broadcast address 255.255.255.255. IPv6 has no broadcast.
+    pub fn is_broadcast(&self) -> bool {
+        match self {
+            VsgAddress::V4(a) => to_ipv4addr(*a).is_broadcast(),
+            VsgAddress::V6(_) => false,
+        }
+    }
+
+    pub fn is_multicast(&self) -> bool {
+        match self {
+            VsgAddress::V4(a) => to_ipv4addr(*a).is_multicast(),
+            VsgAddress::V6(a) => Ipv6Addr::from(*a).is_multicast(),
+        }
+    }
+
+    pub fn ipv4(&self) -> Option<libc::in_addr_t> {
+        match self {
+            VsgAddress::V4(a) => Some(*a),
+            VsgAddress::V6(_) => None,
+        }
+    }
+
+    // IPv4 addresses are returned as IPv4-mapped IPv6 addresses (::ffff:a.b.c.d)
+    pub fn ipv6(&self) -> [u8; 16] {
+        match self {
+            VsgAddress::V4(a) => to_ipv4addr(*a).to_ipv6_mapped().octets(),
+            VsgAddress::V6(a) => *a,
+        }
+    }
+
+    // Inverse of ::ipv6(): IPv4-mapped IPv6 addresses become IPv4 addresses again
+    pub fn from_ipv6(address: [u8; 16]) -> VsgAddress {
+        let ipv6 = Ipv6Addr::from(address);
+        match ipv6.segments() {
+            [0, 0, 0, 0, 0, 0xffff, _, _] => VsgAddress::V4(Into::<u32>::into(Ipv4Addr::new(address[12], address[13], address[14], address[15])).to_be()),
+            _ => VsgAddress::V6(address),
+        }
+    }
+
+    // Split in two big-endian u64, as in the tansiv::Address6 wire type
+    pub fn ipv6_halves(&self) -> (u64, u64) {
+        use std::convert::TryInto;
+
+        let bytes = self.ipv6();
+        (u64::from_be_bytes(bytes[0..8].try_into().unwrap()), u64::from_be_bytes(bytes[8..16].try_into().unwrap()))
+    }
+
+    pub fn from_ipv6_halves(hi: u64, lo: u64) -> VsgAddress {
+        let mut bytes = [0u8; 16];
+        bytes[0..8].copy_from_slice(&hi.to_be_bytes());
+        bytes[8..16].copy_from_slice(&lo.to_be_bytes());
+        VsgAddress::from_ipv6(bytes)
+    }
+}
+
+impl From<libc::in_addr_t> for VsgAddress {
+    fn from(address: libc::in_addr_t) -> VsgAddress {
+        VsgAddress::V4(address)
+    }
+}
+
+impl fmt::Display for VsgAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VsgAddress::V4(a) => to_ipv4addr(*a).fmt(f),
+            VsgAddress::V6(a) => Ipv6Addr::from(*a).fmt(f),
+        }
+    }
+}
+
+pub fn from_str(ip: &str) -> std::result::Result<VsgAddress, AddrParseError> {
     use std::str::FromStr;
-    let ipv4 = Ipv4Addr::from_str(ip)?;
-    Ok(Into::<u32>::into(ipv4).to_be())
+    match IpAddr::from_str(ip)? {
+        IpAddr::V4(ipv4) => Ok(VsgAddress::V4(Into::<u32>::into(ipv4).to_be())),
+        // ::ffff:a.b.c.d is the same address as a.b.c.d
+        IpAddr::V6(ipv6) => Ok(VsgAddress::from_ipv6(ipv6.octets())),
+    }
 }
 
 pub fn to_ipv4addr(address: libc::in_addr_t) -> Ipv4Addr {
     Ipv4Addr::from(u32::from_be(address))
 }
+
+#[cfg(test)]
+mod test {
+    use super::{from_str, VsgAddress};
+
+    #[test]
+    fn from_str_ipv4_mapped() {
+        let ipv4 = from_str("10.0.0.1").unwrap();
+        assert!(ipv4.is_ipv4());
+        assert_eq!(ipv4, from_str("::ffff:10.0.0.1").unwrap());
+        assert_eq!(VsgAddress::V6([0xfe; 16]), from_str("fefe:fefe:fefe:fefe:fefe:fefe:fefe:fefe").unwrap());
+    }
+}
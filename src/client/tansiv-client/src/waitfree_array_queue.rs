@@ -1,6 +1,8 @@
 use crossbeam_queue::{ArrayQueue, PushError};
 use std::fmt;
 use std::marker::PhantomData;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 
 #[derive(Debug)]
 pub enum Error<I: std::fmt::Debug> {
@@ -27,6 +29,8 @@ impl<I: std::fmt::Debug> std::error::Error for Error<I> {}
 #[derive(Debug)]
 pub struct WaitfreeArrayQueue<I> {
     queue: ArrayQueue<I>,
+    // Statistics only
+    max_len: AtomicUsize,
 }
 
 pub struct WaitfreeArrayQueueIter<'a, I> {
@@ -46,12 +50,18 @@ impl<I: std::fmt::Debug> WaitfreeArrayQueue<I> {
     pub fn new(num_slots: usize) -> WaitfreeArrayQueue<I> {
         let queue = ArrayQueue::new(num_slots);
 
-        WaitfreeArrayQueue { queue: queue, }
+        WaitfreeArrayQueue {
+            queue,
+            max_len: AtomicUsize::new(0),
+        }
     }
 
     pub fn push(&self, item: I) -> Result<(), I> {
         match self.queue.push(item) {
-            Ok(_) => Ok(()),
+            Ok(_) => {
+                self.max_len.fetch_max(self.queue.len(), Ordering::Relaxed);
+                Ok(())
+            },
             Err(PushError(item)) => Err(Error::NoSlotAvailable { item: item, }),
         }
     }
@@ -68,7 +78,124 @@ impl<I: std::fmt::Debug> WaitfreeArrayQueue<I> {
         }
     }
 
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    // Maximum number of items seen in the queue so far
+    pub fn high_water_mark(&self) -> usize {
+        self.max_len.load(Ordering::Relaxed)
+    }
+
     pub fn is_empty(&self) -> bool {
         self.queue.is_empty()
     }
 }
+
+// WaitfreeArrayQueue whose consumers can look at the next item before popping it
+//
+// The next item is moved to a head slot shared by consumers only. Producers stay lock-less: they
+// only check whether the head slot is set, with an atomic flag. ::is_empty() can then be true
+// for a short while when a consumer moves an item to the head slot.
+#[derive(Debug)]
+pub struct PeekableQueue<I> {
+    queue: WaitfreeArrayQueue<I>,
+    // Concurrency: accessed by consumers only
+    head: Mutex<Option<I>>,
+    // Concurrency: written by consumers, read by anyone
+    head_set: AtomicBool,
+}
+
+impl<I: std::fmt::Debug> PeekableQueue<I> {
+    pub fn new(num_slots: usize) -> PeekableQueue<I> {
+        PeekableQueue {
+            queue: WaitfreeArrayQueue::new(num_slots),
+            head: Mutex::new(None),
+            head_set: AtomicBool::new(false),
+        }
+    }
+
+    pub fn push(&self, item: I) -> Result<(), I> {
+        self.queue.push(item)
+    }
+
+    pub fn pop(&self) -> Option<I> {
+        let mut head = self.head.lock().unwrap();
+        match head.take() {
+            Some(item) => {
+                self.head_set.store(false, Ordering::Release);
+                Some(item)
+            },
+            None => self.queue.pop(),
+        }
+    }
+
+    // Pops the next item only if accept() returns true for it, otherwise the item stays first in
+    // the queue. Returns None if the queue is empty, and Some(None) if the item is not accepted.
+    pub fn pop_if<F: FnOnce(&I) -> bool>(&self, accept: F) -> Option<Option<I>> {
+        let mut head = self.head.lock().unwrap();
+        if head.is_none() {
+            *head = Some(self.queue.pop()?);
+            self.head_set.store(true, Ordering::Release);
+        }
+        if accept(head.as_ref().unwrap()) {
+            self.head_set.store(false, Ordering::Release);
+            Some(head.take())
+        } else {
+            Some(None)
+        }
+    }
+
+    pub fn iter(&self) -> PeekableQueueIter<'_, I> {
+        PeekableQueueIter {
+            queue: self,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.len() + self.head_set.load(Ordering::Acquire) as usize
+    }
+
+    // Maximum number of items seen in the queue so far
+    pub fn high_water_mark(&self) -> usize {
+        self.queue.high_water_mark()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty() && !self.head_set.load(Ordering::Acquire)
+    }
+}
+
+pub struct PeekableQueueIter<'a, I> {
+    queue: &'a PeekableQueue<I>,
+}
+
+impl<I: std::fmt::Debug> Iterator for PeekableQueueIter<'_, I> {
+    type Item = I;
+
+    fn next(&mut self) -> Option<I> {
+        self.queue.pop()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::PeekableQueue;
+
+    #[test]
+    fn pop_if() {
+        let queue = PeekableQueue::new(2);
+        assert_eq!(None, queue.pop_if(|_| true));
+
+        queue.push(1).unwrap();
+        queue.push(2).unwrap();
+        assert_eq!(Some(None), queue.pop_if(|i| *i == 2));
+        assert_eq!(2, queue.len());
+        assert!(!queue.is_empty());
+        assert_eq!(Some(Some(1)), queue.pop_if(|i| *i == 1));
+        assert_eq!(Some(None), queue.pop_if(|i| *i == 1));
+        assert_eq!(Some(2), queue.pop());
+        assert!(queue.is_empty());
+        assert_eq!(None, queue.pop());
+    }
+}
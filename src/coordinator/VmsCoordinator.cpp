@@ -1,4 +1,5 @@
 #include "VmsInterface.hpp"
+#include "simgrid/Exception.hpp"
 #include "simgrid/s4u.hpp"
 #include <limits.h>
 
@@ -15,8 +16,22 @@ std::vector<simgrid::s4u::CommPtr> pending_comms;
 
 std::vector<vsg::Message*> pending_messages;
 
+// VM each pending message is delivered to
+std::vector<std::string> pending_receivers;
+
+std::vector<simgrid::s4u::IoPtr> pending_ios;
+
+std::vector<vsg::DiskRequest> pending_disk_requests;
+
+std::vector<simgrid::s4u::ExecPtr> pending_execs;
+
+std::vector<vsg::ExecRequest> pending_exec_requests;
+
 static std::vector<simgrid::s4u::ActorPtr> tansiv_actors;
 
+// Link state changes since the last deadline, in time order
+static std::vector<std::pair<const simgrid::s4u::Link*, bool>> link_state_changes;
+
 const std::string vsg_vm_name = "vsg_vm";
 
 double force_min_latency = -1;
@@ -60,6 +75,143 @@ static double get_next_event()
   return next_event_time;
 }
 
+static void start_disk_request(const vsg::DiskRequest& r)
+{
+  if (r.sent_time > simgrid::s4u::Engine::get_clock()) {
+    XBT_DEBUG("going to time %f", r.sent_time);
+    simgrid::s4u::this_actor::sleep_until(r.sent_time);
+  }
+
+  std::string host_name = vms_interface->getHostOfVm(r.vm_name);
+  xbt_assert(not host_name.empty(), "The VM %s tries to access a disk but we do not know its PM", r.vm_name.c_str());
+
+  for (auto disk : simgrid::s4u::Host::by_name(host_name)->get_disks()) {
+    if (disk->get_name() == r.disk) {
+      auto io = r.write ? disk->write_async(r.size) : disk->read_async(r.size);
+      pending_ios.push_back(io);
+      pending_disk_requests.push_back(r);
+      return;
+    }
+  }
+  XBT_WARN("the VM %s tries to access the unknown disk %s of host %s", r.vm_name.c_str(), r.disk.c_str(),
+           host_name.c_str());
+}
+
+static void start_exec_request(const vsg::ExecRequest& r)
+{
+  if (r.sent_time > simgrid::s4u::Engine::get_clock()) {
+    XBT_DEBUG("going to time %f", r.sent_time);
+    simgrid::s4u::this_actor::sleep_until(r.sent_time);
+  }
+
+  std::string host_name = vms_interface->getHostOfVm(r.vm_name);
+  xbt_assert(not host_name.empty(), "The VM %s tries to compute but we do not know its PM", r.vm_name.c_str());
+
+  pending_execs.push_back(simgrid::s4u::Host::by_name(host_name)->exec_async(r.flops));
+  pending_exec_requests.push_back(r);
+}
+
+// Starts the disk and compute requests sent until time, in sent time order
+static void start_requests_until(double time, std::vector<vsg::DiskRequest>::const_iterator& next_disk_request,
+                                 const std::vector<vsg::DiskRequest>& disk_requests,
+                                 std::vector<vsg::ExecRequest>::const_iterator& next_exec_request,
+                                 const std::vector<vsg::ExecRequest>& exec_requests)
+{
+  while (true) {
+    bool disk = next_disk_request != disk_requests.end() && next_disk_request->sent_time <= time;
+    bool exec = next_exec_request != exec_requests.end() && next_exec_request->sent_time <= time;
+    if (disk && (!exec || next_disk_request->sent_time <= next_exec_request->sent_time)) {
+      start_disk_request(*next_disk_request);
+      ++next_disk_request;
+    } else if (exec) {
+      start_exec_request(*next_exec_request);
+      ++next_exec_request;
+    } else {
+      break;
+    }
+  }
+}
+
+// Delivers the completions of finished activities
+template <typename ActivityPtr, typename Request>
+static void complete_requests(std::vector<ActivityPtr>& activities, std::vector<Request>& requests,
+                              void (vsg::VmsInterface::*deliver)(const Request&, double))
+{
+  for (size_t i = 0; i < activities.size();) {
+    if (activities[i]->test()) {
+      (vms_interface->*deliver)(requests[i], activities[i]->get_finish_time());
+      activities.erase(activities.begin() + i);
+      requests.erase(requests.begin() + i);
+    } else {
+      ++i;
+    }
+  }
+}
+
+// Whether link is the link of host in the platform, that is the first link of the routes from host
+// to the hosts of the other VMs or the last link of the routes back
+static bool is_link_of_host(const simgrid::s4u::Link* link, const simgrid::s4u::Host* host)
+{
+  for (auto const& vm_name : vms_interface->get_running_vms()) {
+    auto other = simgrid::s4u::Host::by_name(vms_interface->getHostOfVm(vm_name));
+    if (other == host) {
+      continue;
+    }
+    std::vector<simgrid::s4u::Link*> links;
+    double latency = 0;
+    host->route_to(other, links, &latency);
+    if (not links.empty() && links.front() == link) {
+      return true;
+    }
+    links.clear();
+    other->route_to(host, links, &latency);
+    if (not links.empty() && links.back() == link) {
+      return true;
+    }
+  }
+  return false;
+}
+
+// Tells the VMs about the state changes of their links
+static void deliver_link_state_changes()
+{
+  for (auto const& change : link_state_changes) {
+    for (auto const& vm_name : vms_interface->get_running_vms()) {
+      auto host = simgrid::s4u::Host::by_name(vms_interface->getHostOfVm(vm_name));
+      if (is_link_of_host(change.first, host)) {
+        vms_interface->deliverLinkState(vm_name, change.second);
+      }
+    }
+  }
+  link_state_changes.clear();
+}
+
+// Same as Comm::test_any() on pending_comms, but first drops the messages of the comms that failed,
+// for instance because a link went down
+static int test_any_pending_comm(double time)
+{
+  while (true) {
+    try {
+      return simgrid::s4u::Comm::test_any(pending_comms);
+    } catch (const simgrid::NetworkFailureException&) {
+      for (size_t i = 0; i < pending_comms.size();) {
+        if (pending_comms[i]->get_state() == simgrid::s4u::Activity::State::FAILED) {
+          vsg::Message* m = pending_messages[i];
+          XBT_INFO("[coordinator]: dropping data from vm [%s] to vm [%s] (size=%d, sent_time=%.9f): network failure",
+                   m->src.c_str(), pending_receivers[i].c_str(), m->size, m->sent_time);
+          vms_interface->deliverReport(m, time, tansiv::DropReason_Loss);
+          delete m;
+          pending_comms.erase(pending_comms.begin() + i);
+          pending_messages.erase(pending_messages.begin() + i);
+          pending_receivers.erase(pending_receivers.begin() + i);
+        } else {
+          ++i;
+        }
+      }
+    }
+  }
+}
+
 static void tansiv_actor(std::vector<std::string> args)
 {
 
@@ -106,15 +258,23 @@ static void vm_coordinator()
       min_latency = compute_min_latency();
 
     // then we go forward with the VM.
+    // When all VMs are idle until idle_until, no message can be received before idle_until + min_latency
     double time                = simgrid::s4u::Engine::get_clock();
     double next_reception_time = get_next_event();
-    double deadline            = std::min(time + min_latency, next_reception_time);
+    double idle_until          = std::max(time, vms_interface->get_idle_until());
+    double deadline            = std::min(idle_until + min_latency, next_reception_time);
 
     XBT_DEBUG("next deadline = %f [time+min_latency=%f, next_reception_time=%f]", deadline, time + min_latency,
               next_reception_time);
 
-    std::vector<vsg::Message*> messages = vms_interface->goTo(deadline);
+    std::vector<vsg::Message*> messages        = vms_interface->goTo(deadline);
+    const std::vector<vsg::DiskRequest> disk_requests = vms_interface->take_disk_requests();
+    const std::vector<vsg::ExecRequest> exec_requests = vms_interface->take_exec_requests();
+    auto next_disk_request                            = disk_requests.cbegin();
+    auto next_exec_request                            = exec_requests.cbegin();
     for (vsg::Message* m : messages) {
+      // Keep requests and messages in sent time order
+      start_requests_until(m->sent_time, next_disk_request, disk_requests, next_exec_request, exec_requests);
       time                = simgrid::s4u::Engine::get_clock();
       double send_timeeps = m->sent_time + std::numeric_limits<double>::epsilon();
       xbt_assert(
@@ -128,46 +288,63 @@ static void vm_coordinator()
       }
 
 
-      std::string src_host_name = vms_interface->getHostOfVm(m->src);
-      xbt_assert(not src_host_name.empty(), "The VM %s tries to send a message but we do not know its PM",
+      // The source address can be an alias of the sender
+      xbt_assert(vms_interface->getVmOfAddress(m->src) == m->sender_vm,
+                 "The VM %s tries to send a message from %s which is not one of its addresses", m->sender_vm.c_str(),
                  m->src.c_str());
-
-      std::string dest_host_name = vms_interface->getHostOfVm(m->dst);
-      if (not dest_host_name.empty()) {
-        auto src_host  = simgrid::s4u::Host::by_name(src_host_name);
-        auto dest_host = simgrid::s4u::Host::by_name(dest_host_name);
-	// Ethernet adds an overhead of 24 bytes per packet: preamble + frame start delimiter =  8
-	//                                                   frame checksum (FCS)             =  4
-	//                                                   inter packet gap (IGP)           = 12
-        auto comm      = simgrid::s4u::Comm::sendto_async(src_host, dest_host, m->size + 24);
+      std::string src_host_name = vms_interface->getHostOfVm(m->sender_vm);
+      xbt_assert(not src_host_name.empty(), "The VM %s tries to send a message but we do not know its PM",
+                 m->sender_vm.c_str());
+
+      // Broadcast and multicast messages are sent as one copy per receiver
+      std::vector<std::string> receivers = vms_interface->getReceiverVms(m);
+      if (receivers.empty()) {
+        XBT_WARN("the VM %s tries to send a message to %s which no VM receives", m->src.c_str(), m->dst.c_str());
+        vms_interface->deliverReport(m, simgrid::s4u::Engine::get_clock(), tansiv::DropReason_Unreachable);
+        delete m;
+        continue;
+      }
+      auto src_host = simgrid::s4u::Host::by_name(src_host_name);
+      for (size_t i = 0; i < receivers.size(); i++) {
+        auto dest_host = simgrid::s4u::Host::by_name(vms_interface->getHostOfVm(receivers[i]));
+        // Ethernet adds an overhead of 24 bytes per packet: preamble + frame start delimiter =  8
+        //                                                   frame checksum (FCS)             =  4
+        //                                                   inter packet gap (IGP)           = 12
+        auto comm = simgrid::s4u::Comm::sendto_async(src_host, dest_host, m->wire_size + 24);
         pending_comms.push_back(comm);
-        pending_messages.push_back(m);
-      } else {
-        XBT_WARN("the VM %s tries to send a message to the unknown VM %s", m->src.c_str(), m->dst.c_str());
+        pending_messages.push_back(i + 1 < receivers.size() ? new vsg::Message(*m) : m);
+        pending_receivers.push_back(receivers[i]);
       }
     }
+    start_requests_until(std::numeric_limits<double>::infinity(), next_disk_request, disk_requests, next_exec_request,
+                         exec_requests);
 
     // if deadline = infinity, then (1) there is only one remaining VM, and (2) it stops its execution
     // so we do not have to sleep until "infinity" because the simulation is done
     if (deadline != std::numeric_limits<double>::infinity()) {
       simgrid::s4u::this_actor::sleep_until(deadline);
     }
-    int changed_pos = simgrid::s4u::Comm::test_any(pending_comms);
+    deliver_link_state_changes();
+    int changed_pos = test_any_pending_comm(deadline);
     while (
         changed_pos >=
         0) { // deadline was on next_reception_time, ie, latency was high enough for the next msg to arrive before this
       simgrid::s4u::CommPtr comm = pending_comms[changed_pos];
       vsg::Message* m            = pending_messages[changed_pos];
+      std::string receiver       = pending_receivers[changed_pos];
 
       pending_comms.erase(pending_comms.begin() + changed_pos);
       pending_messages.erase(pending_messages.begin() + changed_pos);
+      pending_receivers.erase(pending_receivers.begin() + changed_pos);
 
-      XBT_INFO("[coordinator]: delivering data from vm [%s] to vm [%s] (size=%d, sent_time=%.9f, recv_time=%.9f)", m->src.c_str(), m->dst.c_str(),
+      XBT_INFO("[coordinator]: delivering data from vm [%s] to vm [%s] (size=%d, sent_time=%.9f, recv_time=%.9f)", m->src.c_str(), receiver.c_str(),
                m->size, m->sent_time, deadline);
-      vms_interface->deliverMessage(m);
+      vms_interface->deliverMessage(m, receiver, deadline);
 
-      changed_pos = simgrid::s4u::Comm::test_any(pending_comms);
+      changed_pos = test_any_pending_comm(deadline);
     }
+    complete_requests(pending_ios, pending_disk_requests, &vsg::VmsInterface::deliverDiskCompletion);
+    complete_requests(pending_execs, pending_exec_requests, &vsg::VmsInterface::deliverExecCompletion);
     XBT_DEBUG("Timestep finished preparing the next iteration [current_time=%f] [next_event = %f]",
               simgrid::s4u::Engine::get_clock(), get_next_event());
   }
@@ -230,6 +407,11 @@ int main(int argc, char* argv[])
      }
   }
 
+  // Link state changes, for instance from the state profiles of the platform, are told to the VMs at
+  // the next deadline
+  simgrid::s4u::Link::on_onoff_cb(
+      [](simgrid::s4u::Link const& link) { link_state_changes.emplace_back(&link, link.is_on()); });
+
   vms_interface = new vsg::VmsInterface(socket_name);
 
   e.register_function(vsg_vm_name, &tansiv_actor);
@@ -1,6 +1,9 @@
 #include "VmsInterface.hpp"
 #include <algorithm>
+#include <cerrno>
+#include <endian.h>
 #include <limits>
+#include <poll.h>
 #include <signal.h>
 #include <unistd.h>
 #include <xbt/log.hpp>
@@ -16,6 +19,7 @@
 // #define LOG_MESSAGES 1
 
 XBT_LOG_NEW_DEFAULT_CATEGORY(vm_interface, "Logging specific to the VmsInterface");
+XBT_LOG_NEW_SUBCATEGORY(vm_markers, vm_interface, "Application event markers sent by the VMs");
 
 namespace vsg {
 
@@ -53,10 +57,60 @@ double vmToSimgridTime(vsg_time vm_time)
   return vmToSimgridTime(vm_time.seconds, vm_time.nseconds);
 }
 
+// Address6 holds the two big-endian halves of the address
+in6_addr vmToIn6Addr(const tansiv::Address6* address)
+{
+  in6_addr in6;
+  uint64_t hi = htobe64(address->hi());
+  uint64_t lo = htobe64(address->lo());
+  memcpy(&in6.s6_addr[0], &hi, sizeof(hi));
+  memcpy(&in6.s6_addr[8], &lo, sizeof(lo));
+  return in6;
+}
+
+// Same form as Message::src and Message::dst, IPv4-mapped addresses are printed as IPv4 addresses
+std::string vmAddressToString(const tansiv::Address6* address)
+{
+  in6_addr in6 = vmToIn6Addr(address);
+  char str[INET6_ADDRSTRLEN];
+  if (IN6_IS_ADDR_V4MAPPED(&in6)) {
+    inet_ntop(AF_INET, &in6.s6_addr[12], str, INET6_ADDRSTRLEN);
+  } else {
+    inet_ntop(AF_INET6, &in6, str, INET6_ADDRSTRLEN);
+  }
+  return std::string(str);
+}
+
+// VM names are their IPv4 or IPv6 address, IPv4 addresses are returned IPv4-mapped
+in6_addr vmNameToIn6Addr(const std::string& vm_name)
+{
+  in6_addr in6;
+  in_addr in;
+  if (inet_pton(AF_INET6, vm_name.c_str(), &in6) != 1) {
+    memset(&in6, 0, sizeof(in6));
+    if (inet_pton(AF_INET, vm_name.c_str(), &in) == 1) {
+      in6.s6_addr[10] = 0xff;
+      in6.s6_addr[11] = 0xff;
+      memcpy(&in6.s6_addr[12], &in, sizeof(in));
+    }
+  }
+  return in6;
+}
+
+tansiv::Address6 in6AddrToVm(const in6_addr& in6)
+{
+  uint64_t hi;
+  uint64_t lo;
+  memcpy(&hi, &in6.s6_addr[0], sizeof(hi));
+  memcpy(&lo, &in6.s6_addr[8], sizeof(lo));
+  return tansiv::Address6(be64toh(hi), be64toh(lo));
+}
+
 VmsInterface::VmsInterface(std::string connection_socket_name, bool stop_at_any_stop)
 {
   a_vm_stopped              = false;
   simulate_until_any_stop   = stop_at_any_stop;
+  idle_until                = 0;
   socket_name               = connection_socket_name;
   const char* c_socket_name = socket_name.c_str();
 
@@ -93,6 +147,7 @@ void VmsInterface::register_vm(std::string host_name, std::string vm_name, std::
 {
 
   vm_deployments[vm_name] = host_name;
+  vm_addresses[vm_name]   = vm_name;
 
   std::vector<char*> command;
   // we inject the socket name as the first parameter
@@ -186,29 +241,60 @@ std::vector<Message*> VmsInterface::goTo(double deadline)
 
   // then, we pick up all the messages send by the VM until they reach the deadline
   std::vector<Message*> messages;
+  idle_until = std::numeric_limits<double>::infinity();
   XBT_INFO("getting the message send by the VMs");
 
   uint8_t scratch_buffer[SCRATCH_BUFFER_LEN];
 
+  // VMs streaming their packets send them while they run, so we read the messages of all the VMs
+  // as they arrive, until each VM sends an at_deadline. Otherwise a VM could stall on a full socket
+  // while we wait for another one.
+  std::vector<struct pollfd> vm_fds;
+  std::vector<std::string> vm_names;
   for (auto kv : vm_sockets) {
-    std::string vm_name = kv.first;
-    int vm_socket       = kv.second;
+    vm_fds.push_back({kv.second, POLLIN, 0});
+    vm_names.push_back(kv.first);
+  }
+  size_t num_running = vm_fds.size();
+
+  while (num_running > 0) {
+    if (poll(vm_fds.data(), vm_fds.size(), -1) < 0) {
+      if (errno == EINTR)
+        continue;
+      std::perror("unable to poll the VM sockets");
+      end_simulation();
+    }
+    for (size_t i = 0; i < vm_fds.size(); i++) {
+      // poll() ignores the negative descriptors of the VMs that reached the deadline
+      if (vm_fds[i].fd < 0 || vm_fds[i].revents == 0)
+        continue;
+      std::string vm_name = vm_names[i];
+      int vm_socket       = vm_fds[i].fd;
 
-    // we loop until we get an at_deadline
-    bool finished = false;
-    while (!finished) {
       if (fb_recv(vm_socket, scratch_buffer, SCRATCH_BUFFER_LEN) < 0) {
         XBT_INFO("can not receive the flags of VM %s. Forget about the socket that seem closed at the system level.",
                  vm_name.c_str());
         close_vm_socket(vm_name);
-        break;
+        vm_fds[i].fd = -1;
+        num_running--;
+        continue;
       }
-      auto msg = flatbuffers::GetRoot<tansiv::ToTansivMsg>(scratch_buffer);
+      // the VM is done once we get an at_deadline
+      bool finished = false;
+      auto msg      = flatbuffers::GetRoot<tansiv::ToTansivMsg>(scratch_buffer);
       switch (msg->content_type()) {
 
-        case tansiv::ToTansiv_AtDeadline:
+        case tansiv::ToTansiv_AtDeadline: {
+          auto at_deadline = msg->content_as_AtDeadline();
+          auto hint        = at_deadline == nullptr ? nullptr : at_deadline->idle_until();
+          if (hint == nullptr) {
+            idle_until = std::min(idle_until, deadline);
+          } else {
+            idle_until = std::min(idle_until, std::max(deadline, vmToSimgridTime(hint->seconds(), hint->nseconds())));
+          }
           finished = true;
           break;
+        }
 
         case tansiv::ToTansiv_SendPacket: {
           auto send_packet = msg->content_as_SendPacket();
@@ -222,9 +308,10 @@ std::vector<Message*> VmsInterface::goTo(double deadline)
           // Our schema use an fbb table (see packets.fbs) Fields on a table can
           // be null however our protocol doesn't allow null fields so we're
           // checking every single field before accepting the message
-          auto metadata = send_packet->metadata();
-          if (metadata == nullptr) {
-            XBT_ERROR("Deserialization error: metadata can't be empty");
+          auto metadata  = send_packet->metadata();
+          auto metadata6 = send_packet->metadata6();
+          if ((metadata == nullptr) == (metadata6 == nullptr)) {
+            XBT_ERROR("Deserialization error: exactly one of metadata and metadata6 must be set");
             break;
           }
           auto time = send_packet->time();
@@ -238,17 +325,115 @@ std::vector<Message*> VmsInterface::goTo(double deadline)
             break;
           }
           // build our own internal message structure and add it to the list of flying messages
-          auto message = new Message(time->seconds(), time->nseconds(), metadata->src(), metadata->dst(),
-                                     flatbuffers::VectorLength<uint8_t>(payload), (uint8_t*)payload->data());
+          Message* message;
+          if (metadata != nullptr) {
+            message = new Message(time->seconds(), time->nseconds(), metadata->src(), metadata->dst(),
+                                  flatbuffers::VectorLength<uint8_t>(payload), (uint8_t*)payload->data());
+          } else {
+            message = new Message(time->seconds(), time->nseconds(), vmToIn6Addr(&metadata6->src()),
+                                  vmToIn6Addr(&metadata6->dst()), flatbuffers::VectorLength<uint8_t>(payload),
+                                  (uint8_t*)payload->data());
+          }
+          if (send_packet->wire_size() != 0) {
+            message->wire_size = send_packet->wire_size();
+          }
+          if (send_packet->flow() != nullptr) {
+            message->has_flow = true;
+            message->flow     = *send_packet->flow();
+          }
+          message->seq_id    = send_packet->seq_id();
+          message->sender_vm = vm_name;
+          switch (send_packet->delivery()) {
+            case tansiv::Delivery_Broadcast:
+              message->delivery = Message::BROADCAST;
+              break;
+            case tansiv::Delivery_Multicast:
+              message->delivery = Message::MULTICAST;
+              break;
+            default:
+              break;
+          }
           messages.push_back(message);
           break;
         }
+        case tansiv::ToTansiv_DiskRequest: {
+          auto disk_request = msg->content_as_DiskRequest();
+          if (disk_request == nullptr || disk_request->time() == nullptr || disk_request->disk() == nullptr) {
+            XBT_ERROR("Deserialization error: malformed DiskRequest");
+            break;
+          }
+          auto time = disk_request->time();
+          disk_requests.push_back(DiskRequest{vm_name, disk_request->request_id(),
+                                              vmToSimgridTime(time->seconds(), time->nseconds()),
+                                              disk_request->op() == tansiv::DiskOp_Write, disk_request->length(),
+                                              disk_request->disk()->str()});
+          break;
+        }
+        case tansiv::ToTansiv_Execute: {
+          auto execute = msg->content_as_Execute();
+          if (execute == nullptr || execute->time() == nullptr) {
+            XBT_ERROR("Deserialization error: malformed Execute");
+            break;
+          }
+          auto time = execute->time();
+          exec_requests.push_back(ExecRequest{vm_name, execute->request_id(),
+                                              vmToSimgridTime(time->seconds(), time->nseconds()), execute->flops()});
+          break;
+        }
+        case tansiv::ToTansiv_Marker: {
+          auto marker = msg->content_as_Marker();
+          if (marker == nullptr || marker->time() == nullptr || marker->label() == nullptr) {
+            XBT_ERROR("Deserialization error: malformed Marker");
+            break;
+          }
+          auto time = marker->time();
+          XBT_CINFO(vm_markers, "[marker] vm %s: %s = %ld (time=%.9f)", vm_name.c_str(), marker->label()->c_str(),
+                    marker->value(), vmToSimgridTime(time->seconds(), time->nseconds()));
+          break;
+        }
+        case tansiv::ToTansiv_GroupMembership: {
+          auto membership = msg->content_as_GroupMembership();
+          if (membership == nullptr || membership->group() == nullptr) {
+            XBT_ERROR("Deserialization error: malformed GroupMembership");
+            break;
+          }
+          std::string group = vmAddressToString(membership->group());
+          if (membership->join()) {
+            group_members[group].insert(vm_name);
+          } else {
+            group_members[group].erase(vm_name);
+          }
+          XBT_VERB("VM %s %s group %s", vm_name.c_str(), membership->join() ? "joined" : "left", group.c_str());
+          break;
+        }
+        case tansiv::ToTansiv_Addresses: {
+          auto addresses = msg->content_as_Addresses();
+          if (addresses == nullptr || addresses->addresses() == nullptr) {
+            XBT_ERROR("Deserialization error: malformed Addresses");
+            break;
+          }
+          for (auto address : *addresses->addresses()) {
+            std::string name = vmAddressToString(address);
+            auto it          = vm_addresses.find(name);
+            if (it != vm_addresses.end() && it->second != vm_name) {
+              XBT_WARN("VM %s claims address %s of VM %s, ignored", vm_name.c_str(), name.c_str(), it->second.c_str());
+              continue;
+            }
+            vm_addresses[name] = vm_name;
+            XBT_VERB("VM %s has address %s", vm_name.c_str(), name.c_str());
+          }
+          break;
+        }
         default:
           XBT_ERROR("Unknown message received from VM %s", vm_name.c_str());
           end_simulation();
           finished = true;
           break;
       }
+      if (finished) {
+        vm_fds[i].fd = -1;
+        num_running--;
+      }
     }
   }
 
@@ -258,10 +443,72 @@ std::vector<Message*> VmsInterface::goTo(double deadline)
 
   XBT_DEBUG("forwarding all the %lu messages to SimGrid", messages.size());
   std::sort(messages.begin(), messages.end(), sortMessages);
+  std::sort(disk_requests.begin(), disk_requests.end(),
+            [](const DiskRequest& i, const DiskRequest& j) { return i.sent_time < j.sent_time; });
+  std::sort(exec_requests.begin(), exec_requests.end(),
+            [](const ExecRequest& i, const ExecRequest& j) { return i.sent_time < j.sent_time; });
 
   return messages;
 }
 
+std::vector<DiskRequest> VmsInterface::take_disk_requests()
+{
+  std::vector<DiskRequest> requests;
+  requests.swap(disk_requests);
+  return requests;
+}
+
+std::vector<ExecRequest> VmsInterface::take_exec_requests()
+{
+  std::vector<ExecRequest> requests;
+  requests.swap(exec_requests);
+  return requests;
+}
+
+void VmsInterface::deliverExecCompletion(const ExecRequest& request, double completion_time)
+{
+  idle_until = 0;
+  if (vm_sockets.find(request.vm_name) != vm_sockets.end()) {
+    int socket = vm_sockets[request.vm_name];
+
+    flatbuffers::FlatBufferBuilder builder(128);
+    struct vsg_time vm_time = simgridToVmTime(completion_time);
+    auto time               = tansiv::Time(vm_time.seconds, vm_time.nseconds);
+    auto completion         = tansiv::CreateExecuteCompletion(builder, request.request_id, &time);
+    auto msg = tansiv::CreateFromTansivMsg(builder, tansiv::FromTansiv::FromTansiv_ExecuteCompletion, completion.Union());
+    builder.FinishSizePrefixed(msg);
+    vsg_protocol_send(socket, builder.GetBufferPointer(), builder.GetSize());
+
+    XBT_VERB("computation %lu of vm %s completed at %f", request.request_id, request.vm_name.c_str(), completion_time);
+  } else {
+    XBT_WARN("computation %lu of vm %s was not completed because the vm already stopped its execution",
+             request.request_id, request.vm_name.c_str());
+  }
+}
+
+void VmsInterface::deliverDiskCompletion(const DiskRequest& request, double completion_time)
+{
+  // The VM may wake up and send messages before its idle hint
+  idle_until = 0;
+  if (vm_sockets.find(request.vm_name) != vm_sockets.end()) {
+    int socket = vm_sockets[request.vm_name];
+
+    flatbuffers::FlatBufferBuilder builder(128);
+    struct vsg_time vm_time = simgridToVmTime(completion_time);
+    auto time               = tansiv::Time(vm_time.seconds, vm_time.nseconds);
+    auto disk_completion    = tansiv::CreateDiskCompletion(builder, request.request_id, &time);
+    auto msg =
+        tansiv::CreateFromTansivMsg(builder, tansiv::FromTansiv::FromTansiv_DiskCompletion, disk_completion.Union());
+    builder.FinishSizePrefixed(msg);
+    vsg_protocol_send(socket, builder.GetBufferPointer(), builder.GetSize());
+
+    XBT_VERB("disk request %lu of vm %s completed at %f", request.request_id, request.vm_name.c_str(), completion_time);
+  } else {
+    XBT_WARN("disk request %lu of vm %s was not completed because the vm already stopped its execution",
+             request.request_id, request.vm_name.c_str());
+  }
+}
+
 std::string VmsInterface::getHostOfVm(std::string vm_name)
 {
   if (vm_deployments.find(vm_name) == vm_deployments.end()) {
@@ -270,14 +517,73 @@ std::string VmsInterface::getHostOfVm(std::string vm_name)
   return vm_deployments[vm_name];
 }
 
+std::string VmsInterface::getVmOfAddress(const std::string& address)
+{
+  auto it = vm_addresses.find(address);
+  if (it == vm_addresses.end()) {
+    return "";
+  }
+  return it->second;
+}
+
+std::vector<std::string> VmsInterface::getReceiverVms(const Message* m)
+{
+  std::vector<std::string> receivers;
+  switch (m->delivery) {
+    case Message::UNICAST: {
+      std::string receiver = getVmOfAddress(m->dst);
+      if (!receiver.empty()) {
+        receivers.push_back(receiver);
+      }
+      break;
+    }
+    case Message::BROADCAST:
+      for (auto const& kv : vm_sockets) {
+        if (kv.first != m->sender_vm) {
+          receivers.push_back(kv.first);
+        }
+      }
+      break;
+    case Message::MULTICAST: {
+      auto members = group_members.find(m->dst);
+      if (members != group_members.end()) {
+        for (auto const& vm_name : members->second) {
+          if (vm_name != m->sender_vm) {
+            receivers.push_back(vm_name);
+          }
+        }
+      }
+      break;
+    }
+  }
+  return receivers;
+}
+
 void VmsInterface::close_vm_socket(std::string vm_name)
 {
+  for (auto& kv : group_members) {
+    kv.second.erase(vm_name);
+  }
   int vm_socket = vm_sockets.at(vm_name);
   shutdown(vm_socket, SHUT_RDWR);
   close(vm_socket);
   vm_sockets_trash.push_back(vm_name);
   a_vm_stopped = true;
 }
+double VmsInterface::get_idle_until()
+{
+  return idle_until;
+}
+
+std::vector<std::string> VmsInterface::get_running_vms()
+{
+  std::vector<std::string> vms;
+  for (auto const& kv : vm_sockets) {
+    vms.push_back(kv.first);
+  }
+  return vms;
+}
+
 const std::vector<std::string> VmsInterface::get_dead_vm_hosts()
 {
   std::vector<std::string> dead_hosts;
@@ -288,37 +594,109 @@ const std::vector<std::string> VmsInterface::get_dead_vm_hosts()
   return dead_hosts;
 }
 
-void VmsInterface::deliverMessage(Message* m)
+void VmsInterface::deliverReport(const Message* m, double time, tansiv::DropReason drop_reason)
 {
-  if (vm_sockets.find(m->dst) != vm_sockets.end()) {
-    int socket = vm_sockets[m->dst];
+  if (m->seq_id == 0) {
+    return;
+  }
+  if (vm_sockets.find(m->sender_vm) != vm_sockets.end()) {
+    int socket = vm_sockets[m->sender_vm];
+
+    flatbuffers::FlatBufferBuilder builder(128);
+    struct vsg_time vm_time = simgridToVmTime(time);
+    auto report_time        = tansiv::Time(vm_time.seconds, vm_time.nseconds);
+    auto report             = tansiv::CreateDeliveryReport(builder, m->seq_id, &report_time, drop_reason);
+    auto msg = tansiv::CreateFromTansivMsg(builder, tansiv::FromTansiv::FromTansiv_DeliveryReport, report.Union());
+    builder.FinishSizePrefixed(msg);
+    vsg_protocol_send(socket, builder.GetBufferPointer(), builder.GetSize());
+
+    XBT_VERB("report of message %lu of vm %s sent (drop reason %s)", m->seq_id, m->sender_vm.c_str(),
+             tansiv::EnumNameDropReason(drop_reason));
+  } else {
+    XBT_VERB("report of message %lu of vm %s not sent because it already stopped its execution", m->seq_id,
+             m->sender_vm.c_str());
+  }
+}
+
+void VmsInterface::deliverLinkState(const std::string& vm_name, bool up)
+{
+  // The VM may react and send messages before its idle hint
+  idle_until = 0;
+  if (vm_sockets.find(vm_name) != vm_sockets.end()) {
+    int socket = vm_sockets[vm_name];
+
+    // All the addresses of the VM share its link: its name, which is its primary address, then its
+    // aliases
+    std::vector<std::string> addresses = {vm_name};
+    for (const auto& vm_address : vm_addresses) {
+      if (vm_address.second == vm_name && vm_address.first != vm_name)
+        addresses.push_back(vm_address.first);
+    }
+    for (const std::string& name : addresses) {
+      flatbuffers::FlatBufferBuilder builder(128);
+      auto address    = in6AddrToVm(vmNameToIn6Addr(name));
+      auto link_state = tansiv::CreateLinkState(builder, &address, up);
+      auto msg = tansiv::CreateFromTansivMsg(builder, tansiv::FromTansiv::FromTansiv_LinkState, link_state.Union());
+      builder.FinishSizePrefixed(msg);
+      vsg_protocol_send(socket, builder.GetBufferPointer(), builder.GetSize());
+    }
+
+    XBT_VERB("link of vm %s is %s (%zu addresses)", vm_name.c_str(), up ? "up" : "down", addresses.size());
+  }
+}
+
+void VmsInterface::deliverMessage(Message* m, const std::string& receiver_vm, double time)
+{
+  // The receiver may wake up and send messages before its idle hint
+  idle_until = 0;
+  if (vm_sockets.find(receiver_vm) != vm_sockets.end()) {
+    int socket = vm_sockets[receiver_vm];
 
     flatbuffers::FlatBufferBuilder builder(2048);
-    auto packet_meta    = tansiv::PacketMeta(m->src_enc, m->dst_enc);
     auto payload_offset = builder.CreateVector<uint8_t>(m->data, m->size);
-    auto deliver_packet = tansiv::CreateDeliverPacket(builder, &packet_meta, payload_offset);
+    uint32_t wire_size  = m->wire_size != m->size ? m->wire_size : 0;
+    const tansiv::FlowMeta* flow = m->has_flow ? &m->flow : nullptr;
+    flatbuffers::Offset<tansiv::DeliverPacket> deliver_packet;
+    if (m->ipv6) {
+      auto packet_meta6 = tansiv::PacketMeta6(in6AddrToVm(m->src6_enc), in6AddrToVm(m->dst6_enc));
+      deliver_packet = tansiv::CreateDeliverPacket(builder, nullptr, payload_offset, &packet_meta6, flow, wire_size);
+    } else {
+      auto packet_meta = tansiv::PacketMeta(m->src_enc, m->dst_enc);
+      deliver_packet   = tansiv::CreateDeliverPacket(builder, &packet_meta, payload_offset, nullptr, flow, wire_size);
+    }
     auto msg =
         tansiv::CreateFromTansivMsg(builder, tansiv::FromTansiv::FromTansiv_DeliverPacket, deliver_packet.Union());
     builder.FinishSizePrefixed(msg);
     vsg_protocol_send(socket, builder.GetBufferPointer(), builder.GetSize());
 
-    XBT_VERB("message from vm %s delivered to vm %s size=%u (on the wire size=%d)", m->src.c_str(), m->dst.c_str(),
-             m->size, builder.GetSize());
+    XBT_VERB("message from vm %s to %s delivered to vm %s size=%u (on the wire size=%d)", m->src.c_str(),
+             m->dst.c_str(), receiver_vm.c_str(), m->size, builder.GetSize());
+    deliverReport(m, time, tansiv::DropReason_None);
   } else {
     XBT_WARN("message from vm %s was not delivered to vm %s because it already stopped its execution", m->src.c_str(),
-             m->dst.c_str());
+             receiver_vm.c_str());
+    deliverReport(m, time, tansiv::DropReason_Unreachable);
   }
   delete m;
 }
 
 Message::Message(uint64_t seconds, uint64_t nseconds, in_addr_t src_enc, in_addr_t dst_enc, uint32_t size,
                  uint8_t* payload)
-    : seconds(seconds), nseconds(nseconds), src_enc(src_enc), dst_enc(dst_enc), size(size)
+    : seconds(seconds)
+    , nseconds(nseconds)
+    , src_enc(src_enc)
+    , dst_enc(dst_enc)
+    , ipv6(false)
+    , src6_enc(in6addr_any)
+    , dst6_enc(in6addr_any)
+    , size(size)
+    , delivery(UNICAST)
+    , wire_size(size)
+    , has_flow(false)
+    , flow()
+    , seq_id(0)
 {
-  // -- compute sent time the sent_time
-  this->sent_time = vmToSimgridTime(seconds, nseconds);
-
-  // -- then src and dest and make them a std::string
+  // -- src and dest and make them a std::string
   char src_addr[INET_ADDRSTRLEN];
   char dst_addr[INET_ADDRSTRLEN];
   struct in_addr _src_addr = {src_enc};
@@ -328,17 +706,62 @@ Message::Message(uint64_t seconds, uint64_t nseconds, in_addr_t src_enc, in_addr
   this->src = std::string(src_addr);
   this->dst = std::string(dst_addr);
 
+  init(payload);
+};
+
+Message::Message(uint64_t seconds, uint64_t nseconds, const in6_addr& src6_enc, const in6_addr& dst6_enc,
+                 uint32_t size, uint8_t* payload)
+    : seconds(seconds)
+    , nseconds(nseconds)
+    , src_enc(0)
+    , dst_enc(0)
+    , ipv6(true)
+    , src6_enc(src6_enc)
+    , dst6_enc(dst6_enc)
+    , size(size)
+    , delivery(UNICAST)
+    , wire_size(size)
+    , has_flow(false)
+    , flow()
+    , seq_id(0)
+{
+  char src_addr[INET6_ADDRSTRLEN];
+  char dst_addr[INET6_ADDRSTRLEN];
+  inet_ntop(AF_INET6, &(src6_enc), src_addr, INET6_ADDRSTRLEN);
+  inet_ntop(AF_INET6, &(dst6_enc), dst_addr, INET6_ADDRSTRLEN);
+  this->src = std::string(src_addr);
+  this->dst = std::string(dst_addr);
+
+  init(payload);
+};
+
+void Message::init(uint8_t* payload)
+{
+  // -- compute sent time the sent_time
+  this->sent_time = vmToSimgridTime(seconds, nseconds);
+
   // -- finally handle the payload
   this->data = new uint8_t[size];
   memcpy(this->data, payload, size);
 #ifdef LOG_MESSAGES
   fprintf(stderr, "Creating new Message@%p: size=%d, data@%p\n", this, this->size, this->data);
 #endif
-};
+}
 
 Message::Message(const Message& other)
     : Message(other.seconds, other.nseconds, other.src_enc, other.dst_enc, other.size, other.data)
 {
+  this->ipv6      = other.ipv6;
+  this->src6_enc  = other.src6_enc;
+  this->dst6_enc  = other.dst6_enc;
+  this->src       = other.src;
+  this->dst       = other.dst;
+  this->delivery  = other.delivery;
+  this->wire_size = other.wire_size;
+  this->has_flow  = other.has_flow;
+  this->flow      = other.flow;
+  this->seq_id    = other.seq_id;
+  this->sender_vm = other.sender_vm;
 #ifdef LOG_MESSAGES
   fprintf(stderr, "Copied Message[%p]: size=%d, data@%p from message[%p]\n", this, this->size, this->data, &other);
 #endif
@@ -361,7 +784,16 @@ Message& Message::operator=(Message&& other)
     this->nseconds  = other.nseconds;
     this->src_enc   = other.src_enc;
     this->dst_enc   = other.dst_enc;
+    this->ipv6      = other.ipv6;
+    this->src6_enc  = other.src6_enc;
+    this->dst6_enc  = other.dst6_enc;
     this->size      = other.size;
+    this->delivery  = other.delivery;
+    this->wire_size = other.wire_size;
+    this->has_flow  = other.has_flow;
+    this->flow      = other.flow;
+    this->seq_id    = other.seq_id;
+    this->sender_vm = other.sender_vm;
     this->sent_time = other.sent_time;
     this->src       = other.src;
     this->dst       = other.dst;
@@ -3,6 +3,8 @@
 
 #include <arpa/inet.h>
 #include <cmath>
+#include <packets_generated.h>
+#include <set>
 #include <string>
 #include <sys/socket.h>
 #include <sys/un.h>
@@ -19,6 +21,8 @@ struct vsg_time {
 class Message {
 public:
   Message(uint64_t seconds, uint64_t nseconds, in_addr_t src_enc, in_addr_t dst_enc, uint32_t size, uint8_t* payload);
+  Message(uint64_t seconds, uint64_t nseconds, const in6_addr& src6_enc, const in6_addr& dst6_enc, uint32_t size,
+          uint8_t* payload);
   Message(const Message& other);
   Message(Message&& other);
   Message& operator=(Message&& other);
@@ -27,14 +31,51 @@ public:
   u_int64_t nseconds;
   in_addr_t src_enc;
   in_addr_t dst_enc;
+  // IPv6 messages have src6_enc and dst6_enc set instead of src_enc and dst_enc
+  bool ipv6;
+  in6_addr src6_enc;
+  in6_addr dst6_enc;
   uint32_t size;
+  // How the message is delivered, see Delivery in packets.fbs
+  enum Delivery { UNICAST, BROADCAST, MULTICAST };
+  Delivery delivery;
+  // size modeled on the simulated wire, defaults to size
+  uint32_t wire_size;
+  // flow metadata of the sender, forwarded to the receivers if has_flow is set
+  bool has_flow;
+  tansiv::FlowMeta flow;
+  // sequence id of the sender, 0 if no delivery report is requested
+  uint64_t seq_id;
+  // name of the VM that sent the message
+  std::string sender_vm;
   // computed attribute below
   double sent_time;
-  // decoded attribute
+  // decoded attribute, IPv6 addresses are in the canonical form of inet_ntop()
   std::string src;
   std::string dst;
   // this will be dynamically allocated according to size
   uint8_t* data;
+
+private:
+  void init(uint8_t* payload);
+};
+
+// Block I/O request of a VM on a disk of its host
+struct DiskRequest {
+  std::string vm_name;
+  uint64_t request_id;
+  double sent_time;
+  bool write;
+  uint64_t size;
+  std::string disk;
+};
+
+// Computation of a VM on its host
+struct ExecRequest {
+  std::string vm_name;
+  uint64_t request_id;
+  double sent_time;
+  double flops;
 };
 
 class VmsInterface {
@@ -45,15 +86,40 @@ public:
   bool vmActive();
   std::vector<Message*> goTo(double deadline);
   std::string getHostOfVm(std::string vm_name);
-  void deliverMessage(Message* m);
+  // VM having address (in the form of Message::src) as its name or as an alias, or an empty string
+  std::string getVmOfAddress(const std::string& address);
+  // VMs a message is delivered to: the destination VM of a unicast message, the other running VMs
+  // for a broadcast message, the other running VMs that joined the group of a multicast message
+  std::vector<std::string> getReceiverVms(const Message* m);
+  // Delivers a message received by the simulated network at time and deletes it
+  void deliverMessage(Message* m, const std::string& receiver_vm, double time);
+  // Tells the sender of a message requesting a report that it was delivered (DropReason_None) or
+  // dropped at time
+  void deliverReport(const Message* m, double time, tansiv::DropReason drop_reason);
+  // Tells a VM that the simulated link of its addresses, aliases included, went down or up
+  void deliverLinkState(const std::string& vm_name, bool up);
+  // Disk requests received during the last goTo(), sorted by sent time
+  std::vector<DiskRequest> take_disk_requests();
+  void deliverDiskCompletion(const DiskRequest& request, double completion_time);
+  // Computations requested during the last goTo(), sorted by sent time
+  std::vector<ExecRequest> take_exec_requests();
+  void deliverExecCompletion(const ExecRequest& request, double completion_time);
   void end_simulation(bool must_unlink = true, bool must_exit = true);
   void register_vm(std::string host_name, std::string vm_name, std::string file, std::vector<std::string> args);
   const std::vector<std::string> get_dead_vm_hosts();
+  // Names of the VMs that did not stop
+  std::vector<std::string> get_running_vms();
+  // Earliest time at which a VM may send a message according to the idle hints received during the
+  // last goTo(), or the last deadline if a VM did not send any hint
+  double get_idle_until();
 
 private:
   bool all_vm_active;
   bool a_vm_stopped;
   bool simulate_until_any_stop;
+  double idle_until;
+  std::vector<DiskRequest> disk_requests;
+  std::vector<ExecRequest> exec_requests;
 
   std::string socket_name;
   int connection_socket;
@@ -61,6 +127,10 @@ private:
   std::unordered_map<std::string, int> vm_sockets;
   std::vector<std::string> vm_sockets_trash;
   std::unordered_map<std::string, std::string> vm_deployments; // VM_name |-> host name
+  // Address (in the form of Message::src) |-> VM name, VM names are addresses of their VM
+  std::unordered_map<std::string, std::string> vm_addresses;
+  // Multicast group address (in the form of Message::dst) |-> VM names
+  std::unordered_map<std::string, std::set<std::string>> group_members;
 
   void close_vm_socket(std::string vm_name);
 };